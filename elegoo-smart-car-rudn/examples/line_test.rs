@@ -0,0 +1,33 @@
+//! Prints what the line tracker sees, ten times a second, to check the sensors over a line.
+
+#![no_std]
+#![no_main]
+
+use arduino_hal::prelude::*;
+
+use elegoo_smart_car_rudn::board::Robot;
+use elegoo_smart_car_rudn::robot;
+
+#[arduino_hal::entry]
+fn main() -> ! {
+    let dp = arduino_hal::Peripherals::take().unwrap();
+    let pins = arduino_hal::pins!(dp);
+
+    let Robot { mut line_tracker, .. } = robot!(dp, pins);
+    let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
+
+    loop {
+        let position = line_tracker.measure_full();
+        let (left, center, right) = position.dark_states();
+        ufmt::uwriteln!(
+            &mut serial,
+            "{} {} {} {:?}\r",
+            left as u8,
+            center as u8,
+            right as u8,
+            position.get_bias_direction_dark(),
+        )
+        .void_unwrap();
+        arduino_hal::delay_ms(100);
+    }
+}
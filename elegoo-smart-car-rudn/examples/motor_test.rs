@@ -0,0 +1,37 @@
+//! Drives the car forward, backward, left and right for a second each, to check the wiring of the motors.
+//!
+//! Put the car on a stand first, or give it plenty of room.
+
+#![no_std]
+#![no_main]
+
+use arduino_hal::prelude::*;
+
+use elegoo_smart_car_rudn::board::Robot;
+use elegoo_smart_car_rudn::l287n_motor_driver::ChassisDirection;
+use elegoo_smart_car_rudn::robot;
+
+#[arduino_hal::entry]
+fn main() -> ! {
+    let dp = arduino_hal::Peripherals::take().unwrap();
+    let pins = arduino_hal::pins!(dp);
+
+    let Robot { mut chassis, .. } = robot!(dp, pins);
+    let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
+
+    let mut go = |direction: ChassisDirection| {
+        let (left, right) = direction.wheel_speeds(255);
+        chassis.drive(left, right);
+        arduino_hal::delay_ms(1000);
+    };
+
+    loop {
+        ufmt::uwriteln!(&mut serial, "Moving\r").void_unwrap();
+        go(ChassisDirection::Forward);
+        go(ChassisDirection::Backward);
+
+        ufmt::uwriteln!(&mut serial, "Turning\r").void_unwrap();
+        go(ChassisDirection::Left);
+        go(ChassisDirection::Right);
+    }
+}
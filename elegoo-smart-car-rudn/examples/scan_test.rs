@@ -0,0 +1,38 @@
+//! Sweeps the distance sensor with the servo, and prints the distance in every direction, once a second.
+
+#![no_std]
+#![no_main]
+
+use arduino_hal::prelude::*;
+
+use elegoo_smart_car_rudn::board::Robot;
+use elegoo_smart_car_rudn::clock;
+use elegoo_smart_car_rudn::range_scanner::{RangeScanner, ScanConfig};
+use elegoo_smart_car_rudn::robot;
+
+#[arduino_hal::entry]
+fn main() -> ! {
+    let dp = arduino_hal::Peripherals::take().unwrap();
+    let pins = arduino_hal::pins!(dp);
+
+    let Robot { servo, distance_sensor, .. } = robot!(dp, pins);
+
+    // The servo pulses and the scan timing run on the millisecond clock.
+    clock::millis_init(dp.TC2);
+    unsafe { avr_device::interrupt::enable() };
+
+    let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
+    let mut scanner = RangeScanner::new(servo, distance_sensor, ScanConfig::default()).unwrap();
+
+    loop {
+        let profile = scanner.scan();
+        for &(angle, measurement) in profile.points() {
+            ufmt::uwriteln!(&mut serial, "{}: {}\r", angle, measurement).void_unwrap();
+        }
+        if let Some(gap) = profile.widest_gap(300) {
+            ufmt::uwriteln!(&mut serial, "Widest gap at {}\r", gap.center_angle()).void_unwrap();
+        }
+        ufmt::uwriteln!(&mut serial, "\r").void_unwrap();
+        arduino_hal::delay_ms(1000);
+    }
+}
@@ -0,0 +1,103 @@
+//! The drivers of the robot, wired as the selected Arduino board and revision of the Elegoo kit say.
+//!
+//! The board is selected with a cargo feature:
+//!
+//! - `arduino-uno` (the default), which the kits come with.
+//! - `arduino-nano`, which has the same microcontroller and pin mapping as the Uno.
+//! - `arduino-mega2560`, whose timer outputs are on different pins than on the Uno,
+//!   so some of the kit is wired to other pins, see the kit modules.
+//!
+//! The kit revision is selected with another one:
+//!
+//! - `kit-v2`: the same parts as the V3 kit, with the motor driver board wired to other pins.
+//!   Only the Uno and the Nano are supported; on a Mega 2560, wire it like the V3 kit, and build with `kit-v3`.
+//! - `kit-v3` (the default): an L298N motor driver board, and a line tracker with digital outputs.
+//! - `kit-v4`: a shield with a TB6612 motor driver, and a line tracker with analog outputs.
+//!   The shield wires the motor speed pins to D5 and D6, which are on TC3 and TC4 of the Mega 2560 instead of TC0,
+//!   and the servo to D10, which is on the compare unit of TC2 that runs the millisecond clock,
+//!   so this kit is only supported on the Uno and the Nano.
+//!
+//! For example, build for the V4 kit with `cargo build --no-default-features --features arduino-uno,kit-v4`,
+//! for the V2 kit with `cargo build --no-default-features --features arduino-uno,kit-v2`,
+//! and for the V3 kit on a Mega 2560 with
+//! `cargo build --no-default-features --features arduino-mega2560,kit-v3 --target avr-specs/avr-atmega2560.json`.
+//!
+//! Every kit module has the same items: the driver types ([Chassis], [DistanceSensor], [MastServo] and [Tracker]),
+//! the [KitPins] that list where everything is wired, [Robot::new], which wires every driver,
+//! and the [robot](crate::robot) macro, which takes the kit's pins out of `arduino_hal::Pins`.
+
+use arduino_hal::port::mode::{Floating, Input};
+use arduino_hal::port::Pin;
+
+#[cfg(any(
+    all(feature = "arduino-uno", feature = "arduino-nano"),
+    all(feature = "arduino-uno", feature = "arduino-mega2560"),
+    all(feature = "arduino-nano", feature = "arduino-mega2560"),
+))]
+compile_error!("Only one Arduino board can be selected, turn off the default features to select another one.");
+
+#[cfg(not(any(feature = "arduino-uno", feature = "arduino-nano", feature = "arduino-mega2560")))]
+compile_error!("Select an Arduino board with the `arduino-uno`, `arduino-nano` or `arduino-mega2560` feature.");
+
+#[cfg(any(
+    all(feature = "kit-v2", feature = "kit-v3"),
+    all(feature = "kit-v2", feature = "kit-v4"),
+    all(feature = "kit-v3", feature = "kit-v4"),
+))]
+compile_error!("Only one kit revision can be selected, turn off the default features to select another one.");
+
+#[cfg(not(any(feature = "kit-v2", feature = "kit-v3", feature = "kit-v4")))]
+compile_error!("Select a kit revision with the `kit-v2`, `kit-v3` or `kit-v4` feature.");
+
+#[cfg(all(feature = "kit-v2", feature = "arduino-mega2560"))]
+compile_error!(
+    "The V2 kit is only supported on the Uno and the Nano. \
+     On a Mega 2560, wire it like the V3 kit (see `src/board/kit_v3.rs`), and build with `kit-v3`."
+);
+
+#[cfg(all(feature = "kit-v4", feature = "arduino-mega2560"))]
+compile_error!(
+    "The V4 kit is only supported on the Uno and the Nano. \
+     Its shield wires the motor speed pins to D5 and D6, which are on TC3 and TC4 of the Mega 2560 instead of TC0, \
+     and the servo to D10, which is on the compare unit of TC2 that runs the millisecond clock."
+);
+
+#[cfg(feature = "kit-v2")]
+mod kit_v2;
+#[cfg(feature = "kit-v2")]
+pub use kit_v2::*;
+
+#[cfg(feature = "kit-v3")]
+mod kit_v3;
+#[cfg(feature = "kit-v3")]
+pub use kit_v3::*;
+
+#[cfg(feature = "kit-v4")]
+mod kit_v4;
+#[cfg(feature = "kit-v4")]
+pub use kit_v4::*;
+
+/// A pin as it comes out of `arduino_hal::Pins`, before it is configured.
+pub type BarePin<PIN> = Pin<Input<Floating>, PIN>;
+
+/// The peripherals that the drivers use, besides the pins.
+///
+/// TC2 is not here, because it runs the millisecond clock, see [crate::clock::millis_init].
+pub struct RobotPeripherals {
+    /// Runs the PWM outputs of the motor driver, see [crate::motor_pwm].
+    pub tc0: arduino_hal::pac::TC0,
+    /// Timestamps the echo of the distance sensor, see [crate::echo_timer].
+    pub tc1: arduino_hal::pac::TC1,
+    /// Enables the pin change interrupt of the Echo pin.
+    pub exint: arduino_hal::pac::EXINT,
+    /// Reads the analog line tracker, on the kits that have one.
+    pub adc: arduino_hal::pac::ADC,
+}
+
+/// Every driver of the robot.
+pub struct Robot {
+    pub chassis: Chassis,
+    pub servo: MastServo,
+    pub distance_sensor: DistanceSensor,
+    pub line_tracker: Tracker,
+}
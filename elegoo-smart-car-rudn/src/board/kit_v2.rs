@@ -0,0 +1,119 @@
+//! The wiring of the V2 kit, on the L298N motor driver board.
+//!
+//! It has the same parts as the V3 kit, but the motor driver board is wired to other pins:
+//! ENB is on D11, and the direction pins are on D6 to D9.
+//! D11 is not an output of TC0, so [crate::motor_pwm] switches it from TC0's interrupts.
+
+use arduino_hal::hal::port::{PB0, PB1, PB2, PB3, PC4, PC5, PD2, PD3, PD4, PD5, PD6, PD7};
+use arduino_hal::port::mode::{AnyInput, Input, Output};
+use arduino_hal::port::Pin;
+use smartcar::hc_sr04_distance_sensor::HC_SR04;
+use smartcar::l287n_motor_driver::MotorChassis;
+use smartcar::line_tracker::LineTracker;
+use smartcar::servo::{Servo, ServoCalibration};
+
+use super::{BarePin, Robot, RobotPeripherals};
+use crate::echo_timer::Tc1EchoTimer;
+use crate::motor_pwm::MotorPwm;
+use crate::servo_pulses::ServoPulses;
+
+pub type Chassis = MotorChassis<MotorPwm, Pin<Output>>;
+pub type DistanceSensor = HC_SR04<Pin<Output>, Tc1EchoTimer>;
+pub type MastServo = Servo<ServoPulses>;
+pub type Tracker = LineTracker<Pin<Input<AnyInput>>>;
+
+/// The pin that the Echo pin of the distance sensor is wired to, which is timestamped by [crate::echo_timer].
+pub type EchoPin = PC4;
+
+/// The pin that the servo is wired to, which is driven by [crate::servo_pulses].
+pub type ServoPin = PD3;
+
+/// Where everything is wired on the V2 kit.
+pub struct KitPins {
+    /// D5, ENA on the motor driver board: the left motors' speed.
+    pub motor_enable_a: BarePin<PD5>,
+    /// D11, ENB on the motor driver board: the right motors' speed.
+    pub motor_enable_b: BarePin<PB3>,
+    /// D6, IN1 on the motor driver board.
+    pub motor_a1: BarePin<PD6>,
+    /// D7, IN2 on the motor driver board.
+    pub motor_a2: BarePin<PD7>,
+    /// D8, IN3 on the motor driver board.
+    pub motor_b1: BarePin<PB0>,
+    /// D9, IN4 on the motor driver board.
+    pub motor_b2: BarePin<PB1>,
+    /// A5, the Trig pin of the HC-SR04.
+    pub sonar_trigger: BarePin<PC5>,
+    /// A4, the Echo pin of the HC-SR04.
+    pub sonar_echo: BarePin<EchoPin>,
+    /// D3, the servo that turns the HC-SR04.
+    pub servo: BarePin<ServoPin>,
+    /// D2, the left line sensor.
+    pub line_left: BarePin<PD2>,
+    /// D4, the center line sensor.
+    pub line_center: BarePin<PD4>,
+    /// D10, the right line sensor.
+    pub line_right: BarePin<PB2>,
+}
+
+impl Robot {
+    /// Configure the pins of the kit, and wire every driver with them.
+    ///
+    /// The servo only moves once the millisecond clock is running, see [crate::clock::millis_init].
+    pub fn new(pins: KitPins, peripherals: RobotPeripherals) -> Self {
+        let (enable_a, enable_b) = MotorPwm::split(
+            peripherals.tc0,
+            pins.motor_enable_a.into_output(),
+            pins.motor_enable_b.into_output(),
+        );
+        let chassis = MotorChassis::new(
+            enable_a,
+            enable_b,
+            pins.motor_a1.into_output().downgrade(),
+            pins.motor_a2.into_output().downgrade(),
+            pins.motor_b1.into_output().downgrade(),
+            pins.motor_b2.into_output().downgrade(),
+        );
+
+        let distance_sensor = HC_SR04::new(
+            pins.sonar_trigger.into_output().downgrade(),
+            Tc1EchoTimer::new(peripherals.tc1, peripherals.exint, pins.sonar_echo.into_pull_up_input().forget_imode()),
+        );
+
+        let servo = Servo::new(ServoPulses::new(pins.servo.into_output()), ServoCalibration::SG90);
+
+        let line_tracker = LineTracker::new(
+            pins.line_left.forget_imode().downgrade(),
+            pins.line_center.forget_imode().downgrade(),
+            pins.line_right.forget_imode().downgrade(),
+        );
+
+        Self { chassis, servo, distance_sensor, line_tracker }
+    }
+}
+
+/// Take the pins of the V2 kit out of `arduino_hal::Pins`, and wire the [Robot] with them.
+///
+/// The serial port's pins are left in `pins`, so `arduino_hal::default_serial!` can still be used after this.
+#[macro_export]
+macro_rules! robot {
+    ($dp:ident, $pins:ident) => {
+        $crate::board::Robot::new(
+            $crate::board::KitPins {
+                motor_enable_a: $pins.d5,
+                motor_enable_b: $pins.d11,
+                motor_a1: $pins.d6,
+                motor_a2: $pins.d7,
+                motor_b1: $pins.d8,
+                motor_b2: $pins.d9,
+                sonar_trigger: $pins.a5,
+                sonar_echo: $pins.a4,
+                servo: $pins.d3,
+                line_left: $pins.d2,
+                line_center: $pins.d4,
+                line_right: $pins.d10,
+            },
+            $crate::board::RobotPeripherals { tc0: $dp.TC0, tc1: $dp.TC1, exint: $dp.EXINT, adc: $dp.ADC },
+        )
+    };
+}
@@ -0,0 +1,180 @@
+//! The wiring of the V3 kit (and the V3+), on the L298N motor driver board.
+//!
+//! On the Mega 2560, the pins of the timers that the firmware uses are elsewhere,
+//! and A4 has no pin change interrupt, so some of the kit is wired to other pins:
+//!
+//! | Part                       | Uno, Nano | Mega 2560 |
+//! |----------------------------|-----------|-----------|
+//! | ENA (left motors' speed)   | D5        | D4        |
+//! | ENB (right motors' speed)  | D6        | D13       |
+//! | IN1                        | D7        | D7        |
+//! | IN2                        | D8        | D8        |
+//! | IN3                        | D9        | D6        |
+//! | IN4                        | D11       | D11       |
+//! | HC-SR04 Trig               | A5        | A5        |
+//! | HC-SR04 Echo               | A4        | A8        |
+//! | Servo                      | D3        | D9        |
+//! | Left line sensor           | D2        | D2        |
+//! | Center line sensor         | D4        | D3        |
+//! | Right line sensor          | D10       | D10       |
+//!
+//! On the Mega 2560, the on-board LED on D13 glows along with the right motors.
+
+use arduino_hal::port::mode::{AnyInput, Input, Output};
+use arduino_hal::port::Pin;
+use smartcar::hc_sr04_distance_sensor::HC_SR04;
+use smartcar::l287n_motor_driver::MotorChassis;
+use smartcar::line_tracker::LineTracker;
+use smartcar::servo::{Servo, ServoCalibration};
+
+use super::{BarePin, Robot, RobotPeripherals};
+use crate::echo_timer::Tc1EchoTimer;
+use crate::motor_pwm::MotorPwm;
+use crate::servo_pulses::ServoPulses;
+
+pub type Chassis = MotorChassis<MotorPwm, Pin<Output>>;
+pub type DistanceSensor = HC_SR04<Pin<Output>, Tc1EchoTimer>;
+pub type MastServo = Servo<ServoPulses>;
+pub type Tracker = LineTracker<Pin<Input<AnyInput>>>;
+
+/// The port pins of the Uno and the Nano that the kit is wired to.
+#[cfg(not(feature = "arduino-mega2560"))]
+mod port {
+    pub use arduino_hal::hal::port::{
+        PB0 as MotorA2, PB1 as MotorB1, PB2 as LineRight, PB3 as MotorB2, PC4 as SonarEcho, PC5 as SonarTrigger,
+        PD2 as LineLeft, PD3 as Servo, PD4 as LineCenter, PD5 as MotorEnableA, PD6 as MotorEnableB, PD7 as MotorA1,
+    };
+}
+
+/// The port pins of the Mega 2560 that the kit is wired to.
+#[cfg(feature = "arduino-mega2560")]
+mod port {
+    pub use arduino_hal::hal::port::{
+        PB4 as LineRight, PB5 as MotorB2, PB7 as MotorEnableB, PE4 as LineLeft, PE5 as LineCenter, PF5 as SonarTrigger,
+        PG5 as MotorEnableA, PH3 as MotorB1, PH4 as MotorA1, PH5 as MotorA2, PH6 as Servo, PK0 as SonarEcho,
+    };
+}
+
+/// The pin that the Echo pin of the distance sensor is wired to, which is timestamped by [crate::echo_timer].
+pub type EchoPin = port::SonarEcho;
+
+/// The pin that the servo is wired to, which is driven by [crate::servo_pulses].
+pub type ServoPin = port::Servo;
+
+/// Where everything is wired on the V3 kit, see the table in the module documentation.
+pub struct KitPins {
+    /// ENA on the motor driver board: the left motors' speed.
+    pub motor_enable_a: BarePin<port::MotorEnableA>,
+    /// ENB on the motor driver board: the right motors' speed.
+    pub motor_enable_b: BarePin<port::MotorEnableB>,
+    /// IN1 on the motor driver board.
+    pub motor_a1: BarePin<port::MotorA1>,
+    /// IN2 on the motor driver board.
+    pub motor_a2: BarePin<port::MotorA2>,
+    /// IN3 on the motor driver board.
+    pub motor_b1: BarePin<port::MotorB1>,
+    /// IN4 on the motor driver board.
+    pub motor_b2: BarePin<port::MotorB2>,
+    /// The Trig pin of the HC-SR04.
+    pub sonar_trigger: BarePin<port::SonarTrigger>,
+    /// The Echo pin of the HC-SR04.
+    pub sonar_echo: BarePin<EchoPin>,
+    /// The servo that turns the HC-SR04.
+    pub servo: BarePin<ServoPin>,
+    /// The left line sensor.
+    pub line_left: BarePin<port::LineLeft>,
+    /// The center line sensor.
+    pub line_center: BarePin<port::LineCenter>,
+    /// The right line sensor.
+    pub line_right: BarePin<port::LineRight>,
+}
+
+impl Robot {
+    /// Configure the pins of the kit, and wire every driver with them.
+    ///
+    /// The servo only moves once the millisecond clock is running, see [crate::clock::millis_init].
+    pub fn new(pins: KitPins, peripherals: RobotPeripherals) -> Self {
+        let (enable_a, enable_b) = MotorPwm::split(
+            peripherals.tc0,
+            pins.motor_enable_a.into_output(),
+            pins.motor_enable_b.into_output(),
+        );
+        let chassis = MotorChassis::new(
+            enable_a,
+            enable_b,
+            pins.motor_a1.into_output().downgrade(),
+            pins.motor_a2.into_output().downgrade(),
+            pins.motor_b1.into_output().downgrade(),
+            pins.motor_b2.into_output().downgrade(),
+        );
+
+        let distance_sensor = HC_SR04::new(
+            pins.sonar_trigger.into_output().downgrade(),
+            Tc1EchoTimer::new(peripherals.tc1, peripherals.exint, pins.sonar_echo.into_pull_up_input().forget_imode()),
+        );
+
+        let servo = Servo::new(ServoPulses::new(pins.servo.into_output()), ServoCalibration::SG90);
+
+        let line_tracker = LineTracker::new(
+            pins.line_left.forget_imode().downgrade(),
+            pins.line_center.forget_imode().downgrade(),
+            pins.line_right.forget_imode().downgrade(),
+        );
+
+        Self { chassis, servo, distance_sensor, line_tracker }
+    }
+}
+
+/// Take the pins of the V3 kit out of `arduino_hal::Pins`, and wire the [Robot] with them.
+///
+/// The serial port's pins are left in `pins`, so `arduino_hal::default_serial!` can still be used after this.
+#[cfg(not(feature = "arduino-mega2560"))]
+#[macro_export]
+macro_rules! robot {
+    ($dp:ident, $pins:ident) => {
+        $crate::board::Robot::new(
+            $crate::board::KitPins {
+                motor_enable_a: $pins.d5,
+                motor_enable_b: $pins.d6,
+                motor_a1: $pins.d7,
+                motor_a2: $pins.d8,
+                motor_b1: $pins.d9,
+                motor_b2: $pins.d11,
+                sonar_trigger: $pins.a5,
+                sonar_echo: $pins.a4,
+                servo: $pins.d3,
+                line_left: $pins.d2,
+                line_center: $pins.d4,
+                line_right: $pins.d10,
+            },
+            $crate::board::RobotPeripherals { tc0: $dp.TC0, tc1: $dp.TC1, exint: $dp.EXINT, adc: $dp.ADC },
+        )
+    };
+}
+
+/// Take the pins of the V3 kit out of `arduino_hal::Pins`, and wire the [Robot] with them.
+///
+/// The serial port's pins are left in `pins`, so `arduino_hal::default_serial!` can still be used after this.
+#[cfg(feature = "arduino-mega2560")]
+#[macro_export]
+macro_rules! robot {
+    ($dp:ident, $pins:ident) => {
+        $crate::board::Robot::new(
+            $crate::board::KitPins {
+                motor_enable_a: $pins.d4,
+                motor_enable_b: $pins.d13,
+                motor_a1: $pins.d7,
+                motor_a2: $pins.d8,
+                motor_b1: $pins.d6,
+                motor_b2: $pins.d11,
+                sonar_trigger: $pins.a5,
+                sonar_echo: $pins.a8,
+                servo: $pins.d9,
+                line_left: $pins.d2,
+                line_center: $pins.d3,
+                line_right: $pins.d10,
+            },
+            $crate::board::RobotPeripherals { tc0: $dp.TC0, tc1: $dp.TC1, exint: $dp.EXINT, adc: $dp.ADC },
+        )
+    };
+}
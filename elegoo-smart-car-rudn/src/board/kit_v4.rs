@@ -0,0 +1,133 @@
+//! The wiring of the V4 kit, on the shield with the TB6612 motor driver.
+
+use arduino_hal::hal::port::{PB0, PB2, PB4, PB5, PC0, PC1, PC2, PD3, PD5, PD6, PD7};
+use arduino_hal::port::mode::Output;
+use arduino_hal::port::Pin;
+use smartcar::hal::AnalogLineSensors;
+use smartcar::hc_sr04_distance_sensor::HC_SR04;
+use smartcar::line_tracker::{AnalogLineTracker, LineTrackerDirection, ITR20001_DARK_RANGE};
+use smartcar::servo::{Servo, ServoCalibration};
+use smartcar::tb6612_motor_driver::Tb6612Chassis;
+
+use super::{BarePin, Robot, RobotPeripherals};
+use crate::echo_timer::Tc1EchoTimer;
+use crate::motor_pwm::MotorPwm;
+use crate::servo_pulses::ServoPulses;
+
+pub type Chassis = Tb6612Chassis<MotorPwm, Pin<Output>>;
+pub type DistanceSensor = HC_SR04<Pin<Output>, Tc1EchoTimer>;
+pub type MastServo = Servo<ServoPulses>;
+pub type Tracker = AnalogLineTracker<AdcLineSensors>;
+
+/// The pin that the Echo pin of the distance sensor is wired to, which is timestamped by [crate::echo_timer].
+pub type EchoPin = PB4;
+
+/// The pin that the servo is wired to, which is driven by [crate::servo_pulses].
+pub type ServoPin = PB2;
+
+/// Where everything is wired on the V4 kit.
+pub struct KitPins {
+    /// D5, PWMA on the shield: the right motors' speed.
+    pub motor_pwm_right: BarePin<PD5>,
+    /// D6, PWMB on the shield: the left motors' speed.
+    pub motor_pwm_left: BarePin<PD6>,
+    /// D7, AIN1 on the shield: the right motors' direction.
+    pub motor_direction_right: BarePin<PD7>,
+    /// D8, BIN1 on the shield: the left motors' direction.
+    pub motor_direction_left: BarePin<PB0>,
+    /// D3, STBY on the shield.
+    pub motor_standby: BarePin<PD3>,
+    /// D13, the Trig pin of the HC-SR04. This is also the pin of the on-board LED.
+    pub sonar_trigger: BarePin<PB5>,
+    /// D12, the Echo pin of the HC-SR04.
+    pub sonar_echo: BarePin<EchoPin>,
+    /// D10, the servo that turns the HC-SR04.
+    pub servo: BarePin<ServoPin>,
+    /// A2, the left line sensor.
+    pub line_left: BarePin<PC2>,
+    /// A1, the center line sensor.
+    pub line_center: BarePin<PC1>,
+    /// A0, the right line sensor.
+    pub line_right: BarePin<PC0>,
+}
+
+/// The ITR20001 line sensors of the V4 kit, read through the ADC.
+pub struct AdcLineSensors {
+    adc: arduino_hal::Adc,
+    left: arduino_hal::adc::Channel,
+    center: arduino_hal::adc::Channel,
+    right: arduino_hal::adc::Channel,
+}
+
+impl AnalogLineSensors for AdcLineSensors {
+    fn read(&mut self, direction: LineTrackerDirection) -> u16 {
+        let channel = match direction {
+            LineTrackerDirection::Left => &self.left,
+            LineTrackerDirection::Center => &self.center,
+            LineTrackerDirection::Right => &self.right,
+        };
+        self.adc.read_blocking(channel)
+    }
+}
+
+impl Robot {
+    /// Configure the pins of the kit, and wire every driver with them.
+    ///
+    /// The servo only moves once the millisecond clock is running, see [crate::clock::millis_init].
+    pub fn new(pins: KitPins, peripherals: RobotPeripherals) -> Self {
+        // MotorPwm splits into the outputs on D5 (OC0B) and D6 (OC0A), in that order.
+        let (pwm_right, pwm_left) = MotorPwm::split(
+            peripherals.tc0,
+            pins.motor_pwm_right.into_output(),
+            pins.motor_pwm_left.into_output(),
+        );
+        let chassis = Tb6612Chassis::new(
+            pwm_left,
+            pwm_right,
+            pins.motor_direction_left.into_output().downgrade(),
+            pins.motor_direction_right.into_output().downgrade(),
+            pins.motor_standby.into_output().downgrade(),
+        );
+
+        // The servo pulses are also timed by TC1, so it must be started before the servo is attached.
+        let distance_sensor = HC_SR04::new(
+            pins.sonar_trigger.into_output().downgrade(),
+            Tc1EchoTimer::new(peripherals.tc1, peripherals.exint, pins.sonar_echo.into_pull_up_input().forget_imode()),
+        );
+
+        let servo = Servo::new(ServoPulses::new(pins.servo.into_output()), ServoCalibration::SG90);
+
+        let mut adc = arduino_hal::Adc::new(peripherals.adc, Default::default());
+        let left = pins.line_left.into_analog_input(&mut adc).into_channel();
+        let center = pins.line_center.into_analog_input(&mut adc).into_channel();
+        let right = pins.line_right.into_analog_input(&mut adc).into_channel();
+        let line_tracker = AnalogLineTracker::new(AdcLineSensors { adc, left, center, right }, ITR20001_DARK_RANGE);
+
+        Self { chassis, servo, distance_sensor, line_tracker }
+    }
+}
+
+/// Take the pins of the V4 kit out of `arduino_hal::Pins`, and wire the [Robot] with them.
+///
+/// The serial port's pins are left in `pins`, so `arduino_hal::default_serial!` can still be used after this.
+#[macro_export]
+macro_rules! robot {
+    ($dp:ident, $pins:ident) => {
+        $crate::board::Robot::new(
+            $crate::board::KitPins {
+                motor_pwm_right: $pins.d5,
+                motor_pwm_left: $pins.d6,
+                motor_direction_right: $pins.d7,
+                motor_direction_left: $pins.d8,
+                motor_standby: $pins.d3,
+                sonar_trigger: $pins.d13,
+                sonar_echo: $pins.d12,
+                servo: $pins.d10,
+                line_left: $pins.a2,
+                line_center: $pins.a1,
+                line_right: $pins.a0,
+            },
+            $crate::board::RobotPeripherals { tc0: $dp.TC0, tc1: $dp.TC1, exint: $dp.EXINT, adc: $dp.ADC },
+        )
+    };
+}
@@ -1,7 +1,12 @@
 //! Functions for a real-time measurement of time.
 //! 
-//! Using the TC0 timer, we set up interrupts to measure milliseconds.
+//! Using the TC2 timer, we set up interrupts to measure milliseconds.
 //! You can use the [millis] function to get the time since the program was started.
+//! [millis_init] also installs it as the source of [smartcar::clock::millis], which the drivers use.
+//!
+//! TC0 is not used here because its output compare pins drive the motor speed pins
+//! with hardware PWM, see [crate::motor_pwm].
+//! On the V2 and V3 kits, the output compare B unit of TC2 also generates the servo pulses, see [crate::servo_pulses].
 //!
 //! Code taken from: https://github.com/Rahix/avr-hal/blob/main/examples/arduino-uno/src/bin/uno-millis.rs
 
@@ -28,34 +33,40 @@ const MILLIS_INCREMENT: u64 = PRESCALER * TIMER_COUNTS / 16000;
 static MILLIS_COUNTER: avr_device::interrupt::Mutex<cell::Cell<u64>> =
     avr_device::interrupt::Mutex::new(cell::Cell::new(0));
 
-/// Function to initialize timer TC0's interrupt to increment the millisecond counter.
-pub fn millis_init(tc0: arduino_hal::pac::TC0) {
+/// Function to initialize timer TC2's interrupt to increment the millisecond counter.
+pub fn millis_init(tc2: arduino_hal::pac::TC2) {
     // Configure the timer for the above interval (in CTC mode)
     // and enable its interrupt.
-    tc0.tccr0a.write(|w| w.wgm0().ctc());
-    tc0.ocr0a.write(|w| unsafe { w.bits(TIMER_COUNTS as u8) });
-    tc0.tccr0b.write(|w| match PRESCALER {
-        8 => w.cs0().prescale_8(),
-        64 => w.cs0().prescale_64(),
-        256 => w.cs0().prescale_256(),
-        1024 => w.cs0().prescale_1024(),
+    tc2.tccr2a.write(|w| w.wgm2().ctc());
+    tc2.ocr2a.write(|w| unsafe { w.bits(TIMER_COUNTS as u8) });
+    tc2.tccr2b.write(|w| match PRESCALER {
+        8 => w.cs2().prescale_8(),
+        64 => w.cs2().prescale_64(),
+        256 => w.cs2().prescale_256(),
+        1024 => w.cs2().prescale_1024(),
         _ => panic!(),
     });
-    tc0.timsk0.write(|w| w.ocie0a().set_bit());
+    tc2.timsk2.write(|w| w.ocie2a().set_bit());
 
     // Reset the global millisecond counter
     avr_device::interrupt::free(|cs| {
         MILLIS_COUNTER.borrow(cs).set(0);
     });
+
+    // SAFETY: this runs once at startup, before interrupts are enabled and before any driver is used.
+    unsafe { smartcar::clock::set_source(millis) };
 }
 
 /// Function to increment the global millisecond counter on each timer interrupt.
-#[avr_device::interrupt(atmega328p)]
-fn TIMER0_COMPA() {
+#[cfg_attr(not(feature = "arduino-mega2560"), avr_device::interrupt(atmega328p))]
+#[cfg_attr(feature = "arduino-mega2560", avr_device::interrupt(atmega2560))]
+fn TIMER2_COMPA() {
     avr_device::interrupt::free(|cs| {
         let counter_cell = MILLIS_COUNTER.borrow(cs);
         let counter = counter_cell.get();
         counter_cell.set(counter + MILLIS_INCREMENT);
+
+        crate::servo_pulses::on_millis_tick(cs);
     })
 }
 
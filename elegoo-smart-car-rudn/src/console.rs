@@ -0,0 +1,143 @@
+//! The serial console, which receives commands and sends replies.
+//!
+//! Two protocols share the serial port. Text commands are parsed in `smartcar_protocol::command`,
+//! and replied to with `OK` or `ERR <code>`. Binary frames from the `smartcar_protocol` crate
+//! are used by host tools, mostly to stream sensor data.
+//!
+//! Text never contains a zero byte, and every binary frame starts and ends with one,
+//! so a zero byte switches the console to collecting a frame until that frame ends.
+
+use embedded_hal::serial::{Read, Write};
+use smartcar::grid_route::StepReport;
+use smartcar_protocol::command::{Command, CommandError, LineBuffer};
+use smartcar_protocol::frame::MAX_WIRE_FRAME_LENGTH;
+use smartcar_protocol::telemetry::{Channels, TelemetryRecord};
+use smartcar_protocol::{DistanceReading, Frame, FrameDecoder, ProtocolError};
+
+/// Something received by the console.
+pub enum ConsoleInput {
+    /// A line of text, parsed into a command.
+    Text(Result<Command, CommandError>),
+    /// A binary frame.
+    Frame(Result<Frame, ProtocolError>),
+}
+
+/// Collects commands from the serial port.
+pub struct Console {
+    lines: LineBuffer,
+    frames: FrameDecoder,
+    /// Whether a binary frame is being received.
+    in_frame: bool,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self {
+            lines: LineBuffer::new(),
+            frames: FrameDecoder::new(),
+            in_frame: false,
+        }
+    }
+
+    /// Read the bytes that have arrived so far, and return the first complete line or frame, if any.
+    ///
+    /// This never waits for more bytes, so it can be called on every pass of the main loop.
+    /// Errors in text commands are returned so that they can be replied to with [Console::reply_err].
+    pub fn poll<S: Read<u8>>(&mut self, serial: &mut S) -> Option<ConsoleInput> {
+        while let Ok(byte) = serial.read() {
+            if self.in_frame {
+                if let Some(result) = self.frames.push(byte) {
+                    self.in_frame = false;
+                    return Some(ConsoleInput::Frame(result));
+                }
+            } else if byte == smartcar_protocol::frame::DELIMITER {
+                self.in_frame = true;
+            } else if let Some(result) = self.lines.push(byte) {
+                return Some(ConsoleInput::Text(result));
+            }
+        }
+        None
+    }
+
+    /// Reply that the command was carried out.
+    pub fn reply_ok<W: ufmt::uWrite>(serial: &mut W) {
+        // A reply that could not be sent should not stop the robot.
+        ufmt::uwriteln!(serial, "OK\r").ok();
+    }
+
+    /// Reply that the command was wrong.
+    pub fn reply_err<W: ufmt::uWrite>(serial: &mut W, error: CommandError) {
+        ufmt::uwriteln!(serial, "ERR {}\r", error.code()).ok();
+    }
+
+    /// Reply to the `ping` command with a distance: `OK <mm>`, `OK inf` or `OK unknown`.
+    pub fn reply_distance<W: ufmt::uWrite>(serial: &mut W, reading: DistanceReading) {
+        Self::write_distance_reply(serial, reading).ok();
+    }
+
+    fn write_distance_reply<W: ufmt::uWrite>(serial: &mut W, reading: DistanceReading) -> Result<(), W::Error> {
+        serial.write_str("OK ")?;
+        Self::write_distance(serial, reading)?;
+        serial.write_str("\r\n")
+    }
+
+    /// Send a binary frame.
+    pub fn send_frame<S: Write<u8>>(serial: &mut S, frame: &Frame) {
+        let mut bytes = [0u8; MAX_WIRE_FRAME_LENGTH];
+        // Every message fits into the largest frame, so this cannot fail.
+        let len = frame.write(&mut bytes).unwrap_or(0);
+        for &byte in &bytes[..len] {
+            nb::block!(serial.write(byte)).ok();
+        }
+    }
+
+    /// Send the report of a finished step of a grid route, like `STEP 2 l: OK`.
+    pub fn send_step_report<W: ufmt::uWrite>(serial: &mut W, report: &StepReport) {
+        // Like the replies, a report that could not be sent should not stop the robot.
+        ufmt::uwriteln!(serial, "{}\r", report).ok();
+    }
+
+    /// Send the line that ends the reports of a grid route.
+    pub fn send_route_done<W: ufmt::uWrite>(serial: &mut W) {
+        ufmt::uwriteln!(serial, "ROUTE DONE\r").ok();
+    }
+
+    /// Send a telemetry record as a line of text, with only its selected channels.
+    ///
+    /// For example: `T 1500 motor 120 -120 servo 90 distance 300 line 010 timing 812 3`.
+    pub fn send_text_record<W: ufmt::uWrite>(serial: &mut W, record: &TelemetryRecord) {
+        // Like the replies, a record that could not be sent should not stop the robot.
+        Self::write_text_record(serial, record).ok();
+    }
+
+    fn write_text_record<W: ufmt::uWrite>(serial: &mut W, record: &TelemetryRecord) -> Result<(), W::Error> {
+        let channels = record.channels;
+        ufmt::uwrite!(serial, "T {}", record.at_ms)?;
+        if channels.contains(Channels::MOTOR) {
+            ufmt::uwrite!(serial, " motor {} {}", record.motor.0, record.motor.1)?;
+        }
+        if channels.contains(Channels::SERVO) {
+            ufmt::uwrite!(serial, " servo {}", record.servo_angle)?;
+        }
+        if channels.contains(Channels::DISTANCE) {
+            serial.write_str(" distance ")?;
+            Self::write_distance(serial, record.distance)?;
+        }
+        if channels.contains(Channels::LINE) {
+            let (left, center, right) = record.line;
+            ufmt::uwrite!(serial, " line {}{}{}", left as u8, center as u8, right as u8)?;
+        }
+        if channels.contains(Channels::TIMING) {
+            ufmt::uwrite!(serial, " timing {} {}", record.loop_count, record.max_loop_ms)?;
+        }
+        serial.write_str("\r\n")
+    }
+
+    fn write_distance<W: ufmt::uWrite>(serial: &mut W, reading: DistanceReading) -> Result<(), W::Error> {
+        match reading {
+            DistanceReading::Measured(mm) => ufmt::uwrite!(serial, "{}", mm),
+            DistanceReading::Infinity => serial.write_str("inf"),
+            DistanceReading::Unknown => serial.write_str("unknown"),
+        }
+    }
+}
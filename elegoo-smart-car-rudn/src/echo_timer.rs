@@ -0,0 +1,157 @@
+//! The timer that timestamps the echo of the HC-SR04, for the [smartcar::hc_sr04_distance_sensor].
+//!
+//! TC1 counts with a prescaler of 64, which is once per 4µs, and wraps around every 65536 * 4µs = 262.14ms.
+//! The edges of the Echo pin are timestamped with TC1 in the pin change interrupt.
+//! We cannot use TC1's input capture unit, because its pin (ICP1, PB0 on the ATmega328P) drives the motor driver,
+//! and is not on the headers of the Mega 2560.
+//!
+//! Where the Echo pin is depends on the board and the kit, see [crate::board::EchoPin],
+//! and so does its pin change interrupt, see the `pin_change` modules below.
+
+use core::cell;
+
+use arduino_hal::port::mode::Input;
+use arduino_hal::port::Pin;
+use smartcar::hal::EchoTimer;
+
+use crate::board::EchoPin;
+
+/// The edges recorded so far, shared with the pin change interrupt.
+#[derive(Clone, Copy)]
+struct Capture {
+    /// Whether the interrupt should record edges.
+    armed: bool,
+    rise_tick: Option<u16>,
+    fall_tick: Option<u16>,
+}
+
+static CAPTURE: avr_device::interrupt::Mutex<cell::Cell<Capture>> =
+    avr_device::interrupt::Mutex::new(cell::Cell::new(Capture { armed: false, rise_tick: None, fall_tick: None }));
+
+/// TC1, and the pin change interrupt of the Echo pin.
+pub struct Tc1EchoTimer {
+    tc1: arduino_hal::pac::TC1,
+    exint: arduino_hal::pac::EXINT,
+    echo_pin: Pin<Input, EchoPin>,
+}
+
+impl Tc1EchoTimer {
+    /// Start TC1 and enable the pin change interrupt for the Echo pin.
+    ///
+    /// Interrupts must be enabled globally for the edges to be recorded.
+    pub fn new(tc1: arduino_hal::pac::TC1, exint: arduino_hal::pac::EXINT, echo_pin: Pin<Input, EchoPin>) -> Self {
+        // Configure the timer for the smallest available interval (prescaling 64)
+        // which will count once per 4µs.
+        // The timer will overflow after 65535 * 4µs = 262.14ms, which is plenty enough for this task,
+        // since we only ever look at differences between ticks.
+        tc1.tccr1b.write(|w| w.cs1().prescale_64());
+
+        pin_change::enable(&exint);
+
+        Self { tc1, exint, echo_pin }
+    }
+}
+
+impl EchoTimer for Tc1EchoTimer {
+    fn now(&self) -> u16 {
+        // The 16-bit counter is read through the TEMP register of TC1, which the interrupts use too,
+        // so an interrupt between the reads of the two bytes would corrupt the high byte.
+        avr_device::interrupt::free(|_| self.tc1.tcnt1.read().bits())
+    }
+
+    fn start_capture(&mut self) {
+        avr_device::interrupt::free(|cs| {
+            CAPTURE.borrow(cs).set(Capture { armed: true, rise_tick: None, fall_tick: None })
+        });
+    }
+
+    fn edges(&self) -> (Option<u16>, Option<u16>) {
+        let capture = avr_device::interrupt::free(|cs| CAPTURE.borrow(cs).get());
+        (capture.rise_tick, capture.fall_tick)
+    }
+}
+
+/// The Echo pin of the V2 and V3 kits on the Uno or the Nano: A4 (PC4), which is PCINT12 in the PCINT1 group.
+#[cfg(all(any(feature = "kit-v2", feature = "kit-v3"), not(feature = "arduino-mega2560")))]
+mod pin_change {
+    /// The bit of the Echo pin in the PINC and PCMSK1 registers.
+    const PIN_BIT: u8 = 1 << 4;
+
+    /// Enable the pin change interrupt for the Echo pin.
+    pub(super) fn enable(exint: &arduino_hal::pac::EXINT) {
+        exint.pcmsk1.modify(|r, w| unsafe { w.bits(r.bits() | PIN_BIT) });
+        exint.pcicr.modify(|r, w| unsafe { w.bits(r.bits() | 1 << 1) });
+    }
+
+    #[avr_device::interrupt(atmega328p)]
+    fn PCINT1() {
+        // SAFETY: the input pins are only read here, which has no side effects.
+        let portc = unsafe { &*arduino_hal::pac::PORTC::ptr() };
+        super::on_echo_edge(portc.pinc.read().bits() & PIN_BIT != 0);
+    }
+}
+
+/// The Echo pin of the V3 kit on the Mega 2560: A8 (PK0), which is PCINT16 in the PCINT2 group.
+#[cfg(all(feature = "kit-v3", feature = "arduino-mega2560"))]
+mod pin_change {
+    /// The bit of the Echo pin in the PINK and PCMSK2 registers.
+    const PIN_BIT: u8 = 1 << 0;
+
+    /// Enable the pin change interrupt for the Echo pin.
+    pub(super) fn enable(exint: &arduino_hal::pac::EXINT) {
+        exint.pcmsk2.modify(|r, w| unsafe { w.bits(r.bits() | PIN_BIT) });
+        exint.pcicr.modify(|r, w| unsafe { w.bits(r.bits() | 1 << 2) });
+    }
+
+    #[avr_device::interrupt(atmega2560)]
+    fn PCINT2() {
+        // SAFETY: the input pins are only read here, which has no side effects.
+        let portk = unsafe { &*arduino_hal::pac::PORTK::ptr() };
+        super::on_echo_edge(portk.pink.read().bits() & PIN_BIT != 0);
+    }
+}
+
+/// The Echo pin of the V4 kit: D12 (PB4), which is PCINT4 in the PCINT0 group.
+#[cfg(feature = "kit-v4")]
+mod pin_change {
+    /// The bit of the Echo pin in the PINB and PCMSK0 registers.
+    const PIN_BIT: u8 = 1 << 4;
+
+    /// Enable the pin change interrupt for the Echo pin.
+    pub(super) fn enable(exint: &arduino_hal::pac::EXINT) {
+        exint.pcmsk0.modify(|r, w| unsafe { w.bits(r.bits() | PIN_BIT) });
+        exint.pcicr.modify(|r, w| unsafe { w.bits(r.bits() | 1 << 0) });
+    }
+
+    #[avr_device::interrupt(atmega328p)]
+    fn PCINT0() {
+        // SAFETY: the input pins are only read here, which has no side effects.
+        let portb = unsafe { &*arduino_hal::pac::PORTB::ptr() };
+        super::on_echo_edge(portb.pinb.read().bits() & PIN_BIT != 0);
+    }
+}
+
+/// Timestamp an edge of the Echo pin, from the pin change interrupt.
+fn on_echo_edge(echo_high: bool) {
+    // SAFETY: the timer counter is only read here, which has no side effects.
+    let tc1 = unsafe { &*arduino_hal::pac::TC1::ptr() };
+    let now = tc1.tcnt1.read().bits();
+
+    avr_device::interrupt::free(|cs| {
+        let capture_cell = CAPTURE.borrow(cs);
+        let mut capture = capture_cell.get();
+        if !capture.armed {
+            return;
+        }
+        match (capture.rise_tick, echo_high) {
+            (None, true) => capture.rise_tick = Some(now),
+            (Some(_), false) => {
+                // Only the first echo counts; later edges are reflections.
+                capture.fall_tick = Some(now);
+                capture.armed = false;
+            },
+            _ => {},
+        }
+        capture_cell.set(capture);
+    })
+}
@@ -0,0 +1,25 @@
+//! The firmware of the Elegoo smart robot car, on an Arduino Uno, Nano or Mega 2560.
+//!
+//! The hardware-independent drivers, and the behaviors that steer them, live in the [smartcar] crate,
+//! and are re-exported here. This crate fills in their hardware traits with the AVR timers and pins (see [board]),
+//! and holds the console that the host talks to, so that the firmware binary and the examples
+//! only have to wire them together.
+//!
+//! Linking this crate also installs the panic handler, which reports the panic over the serial port.
+
+#![no_std]
+#![feature(abi_avr_interrupt)]
+
+pub use smartcar::{differential_drive, hc_sr04_distance_sensor, l287n_motor_driver, line_tracker, servo, sound_speed};
+pub use smartcar::{filtered_rangefinder, line_events, motor_ramp, pid, telemetry};
+pub use smartcar::{avoid_mode, follow_mode, grid_mode, grid_route, line_following, obstacle_avoidance, range_scanner};
+
+pub mod board;
+pub mod clock;
+pub mod echo_timer;
+pub mod motor_pwm;
+pub mod servo_pulses;
+
+pub mod console;
+
+mod panic;
@@ -1,121 +1,246 @@
 #![no_std]
 #![no_main]
-#![feature(abi_avr_interrupt)]
-#![allow(dead_code)]
 
-#[allow(unused_imports)]
 use arduino_hal::prelude::*;
-#[allow(unused_imports)]
-use embedded_hal::serial::Read;
 
-
-mod l287n_motor_driver;
-#[allow(unused_imports)]
-use l287n_motor_driver::{MotorChassis, ChassisDirection};
-use servo::Servo;
-
-mod clock;
-
-mod hc_sr04_distance_sensor;
-mod servo;
-mod panic;
-mod line_tracker;
+use elegoo_smart_car_rudn::avoid_mode::AvoidMode;
+use elegoo_smart_car_rudn::board::Robot;
+use elegoo_smart_car_rudn::clock;
+use elegoo_smart_car_rudn::console::{Console, ConsoleInput};
+use elegoo_smart_car_rudn::differential_drive::Drive;
+use elegoo_smart_car_rudn::filtered_rangefinder::FilteredRangefinder;
+use elegoo_smart_car_rudn::follow_mode::{FollowMode, Steering};
+use elegoo_smart_car_rudn::grid_mode::GridMode;
+use elegoo_smart_car_rudn::grid_route::GridConfig;
+use elegoo_smart_car_rudn::hc_sr04_distance_sensor::DistanceMeasurement;
+use elegoo_smart_car_rudn::line_following::FollowConfig;
+use elegoo_smart_car_rudn::motor_ramp::RampedDrive;
+use elegoo_smart_car_rudn::obstacle_avoidance::AvoidConfig;
+use elegoo_smart_car_rudn::pid::PidGains;
+use elegoo_smart_car_rudn::range_scanner::{RangeScanner, ScanConfig};
+use elegoo_smart_car_rudn::robot;
+use elegoo_smart_car_rudn::telemetry::{TelemetryFormat, TelemetryScheduler};
+use smartcar_protocol::command::{Command, RobotMode};
+use smartcar_protocol::{Channels, DistanceReading, Frame, Message, MessageId};
+
+/// The number of pings combined into the distance that is reported when the host asks for it.
+const PING_SAMPLES: usize = 5;
+
+/// How far apart the pings may be for the reported distance to be trusted, in mm.
+const PING_MAX_SPREAD_MM: u64 = 30;
+
+/// How fast the wheel commands may change, per second: from stopped to full speed in a quarter of a second.
+const WHEEL_ACCELERATION: u16 = 1020;
+
+/// Who asked for the distance that is being measured.
+#[derive(Clone, Copy)]
+enum DistanceRequest {
+    /// The `ping` text command.
+    Text,
+    /// A binary request, answered with its sequence number.
+    Frame(u8),
+}
+
+/// Answer a request for the distance.
+fn send_distance<S>(serial: &mut S, request: DistanceRequest, measurement: DistanceMeasurement)
+where
+    S: ufmt::uWrite + embedded_hal::serial::Write<u8>,
+{
+    match request {
+        DistanceRequest::Text => Console::reply_distance(serial, DistanceReading::from(measurement)),
+        DistanceRequest::Frame(sequence) => {
+            let message = Message::Distance { at_ms: clock::millis() as u32, reading: DistanceReading::from(measurement) };
+            Console::send_frame(serial, &Frame::new(sequence, message));
+        },
+    }
+}
 
 #[arduino_hal::entry]
 fn main() -> ! {
     let dp = arduino_hal::Peripherals::take().unwrap();
     let pins = arduino_hal::pins!(dp);
 
-    /*
-     * For examples (and inspiration), head to
-     *
-     *     https://github.com/Rahix/avr-hal/tree/main/examples
-     *
-     * NOTE: Not all examples were ported to all boards!  There is a good chance though, that code
-     * for a different board can be adapted for yours.  The Arduino Uno currently has the most
-     * examples available.
-     */
-
-    let enable_a = pins.d5.into_output().downgrade();
-    let enable_b = pins.d6.into_output().downgrade();
-    let in1 = pins.d7.into_output().downgrade();
-    let in2 = pins.d8.into_output().downgrade();
-    let in3 = pins.d9.into_output().downgrade();
-    let in4 = pins.d11.into_output().downgrade();
-
-    let mut chassis = MotorChassis::new(
-        enable_a,
-        enable_b,
-        in1,
-        in2,
-        in3,
-        in4,
-    );
-
-    let mut led = pins.d13.into_output();
-
-
-    clock::millis_init(dp.TC0);
+    let Robot { chassis, servo, distance_sensor, mut line_tracker } = robot!(dp, pins);
+
+    clock::millis_init(dp.TC2);
 
     // Enable interrupts globally
     unsafe { avr_device::interrupt::enable() };
 
-    #[allow(unused_variables)]
     let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
 
-    chassis.set_enabled(true, true);
+    // Every wheel command goes through the ramp, so that the chassis does not jerk when it changes direction.
+    let mut chassis = RampedDrive::new(chassis, WHEEL_ACCELERATION);
 
-    /*
-    loop {
-        
-        ufmt::uwriteln!(&mut serial, "Moving\n").void_unwrap();
-        led.toggle();
-        chassis.set_direction(ChassisDirection::Forward);
-        arduino_hal::delay_ms(1000);
-        chassis.set_direction(ChassisDirection::Backward);
-        arduino_hal::delay_ms(1000);
-
-        ufmt::uwriteln!(&mut serial, "Turning\n").void_unwrap();
-        led.toggle();
-        chassis.set_direction(ChassisDirection::Left);
-        arduino_hal::delay_ms(1000);
-        chassis.set_direction(ChassisDirection::Right);
-        arduino_hal::delay_ms(1000);
-    }
-    */
-
-    let dist_trigger_pin = pins.a5.into_output().downgrade();
-    let dist_echo_pin = pins.a4.into_pull_up_input().downgrade().forget_imode();
-
-    let mut dist_sensor = hc_sr04_distance_sensor::HC_SR04::new(
-        dp.TC1,
-        dist_trigger_pin,
-        dist_echo_pin,
-    );
+    // Hold the wheels until the first command.
+    chassis.brake();
 
     ufmt::uwriteln!(&mut serial, "Running!").void_unwrap();
 
-    let mut servo = Servo::new(pins.d3.into_output());
-    
-    let mut line_tracker = line_tracker::LineTracker::new(
-        pins.d2.into_floating_input().forget_imode().downgrade(),
-        pins.d4.into_floating_input().forget_imode().downgrade(),
-        pins.d10.into_floating_input().forget_imode().downgrade()
-    );
+    let mut scanner = RangeScanner::new(servo, distance_sensor, ScanConfig::default()).unwrap();
+    let mut rangefinder = FilteredRangefinder::new(PING_SAMPLES, PING_MAX_SPREAD_MM);
+
+    let mut console = Console::new();
+    let mut mode = RobotMode::Idle;
+    let mut avoid_mode = AvoidMode::new(AvoidConfig::default());
+    let mut follow_mode = FollowMode::new(FollowConfig::default(), Steering::Pid);
+    let mut grid_mode = GridMode::new(GridConfig::default());
+    let mut telemetry = TelemetryScheduler::new();
+    let mut distance_request = None;
 
     loop {
-//        let dist = dist_sensor.get_distance();
-//        ufmt::uwriteln!(&mut serial, "Distance: {}", dist).void_unwrap();
-//        led.toggle();
-//        arduino_hal::delay_ms(1000);
-//        for i in [0, 90, 180, 90].iter() {
-//            servo.set_angle(*i);
-//            ufmt::uwriteln!(&mut serial, "Angle: {}", i).void_unwrap();
-//            arduino_hal::delay_ms(0);
-//        }
-
-        let line_pos = line_tracker.measure_full();
-        ufmt::uwriteln!(&mut serial, "Line: {:?}", line_pos).void_unwrap();
-        arduino_hal::delay_ms(1000);
+        telemetry.loop_tick(clock::millis());
+        chassis.update();
+
+        match console.poll(&mut serial) {
+            Some(ConsoleInput::Text(Ok(Command::Drive(left, right)))) => {
+                // Driving by hand takes over from any autonomous mode.
+                mode = RobotMode::Idle;
+                chassis.drive(left, right);
+                Console::reply_ok(&mut serial);
+            },
+            Some(ConsoleInput::Text(Ok(Command::Servo(angle)))) => {
+                scanner.servo().set_angle(angle);
+                Console::reply_ok(&mut serial);
+            },
+            Some(ConsoleInput::Text(Ok(Command::Ping))) => {
+                rangefinder.start();
+                distance_request = Some(DistanceRequest::Text);
+            },
+            Some(ConsoleInput::Text(Ok(Command::Line))) => {
+                let bias = follow_mode.follower().bias(&line_tracker.measure_full());
+                ufmt::uwriteln!(&mut serial, "OK {:?}\r", bias).void_unwrap();
+            },
+            Some(ConsoleInput::Text(Ok(Command::Stop))) => {
+                mode = RobotMode::Idle;
+                chassis.brake();
+                Console::reply_ok(&mut serial);
+            },
+            Some(ConsoleInput::Text(Ok(Command::Mode(new_mode)))) => {
+                if new_mode != mode {
+                    chassis.coast();
+                    match new_mode {
+                        RobotMode::Avoid => avoid_mode.restart(&mut scanner),
+                        RobotMode::FollowLine => follow_mode.restart(),
+                        // The grid mode waits for a `route` command.
+                        RobotMode::Grid => grid_mode.executor().abort(),
+                        RobotMode::Idle => {},
+                    }
+                    mode = new_mode;
+                }
+                Console::reply_ok(&mut serial);
+            },
+            Some(ConsoleInput::Text(Ok(Command::Telemetry(period_ms)))) => {
+                telemetry.configure(period_ms, TelemetryFormat::Text, clock::millis());
+                Console::reply_ok(&mut serial);
+            },
+            Some(ConsoleInput::Text(Ok(Command::Channel(channels, enabled)))) => {
+                telemetry.set_channel(channels, enabled);
+                Console::reply_ok(&mut serial);
+            },
+            Some(ConsoleInput::Text(Ok(Command::Pid(kp, ki, kd)))) => {
+                follow_mode.set_pid_gains(PidGains { kp: kp as i32, ki: ki as i32, kd: kd as i32 });
+                Console::reply_ok(&mut serial);
+            },
+            Some(ConsoleInput::Text(Ok(Command::LineColor(line_color)))) => {
+                follow_mode.set_line_color(line_color.into());
+                grid_mode.set_line_color(line_color.into());
+                Console::reply_ok(&mut serial);
+            },
+            Some(ConsoleInput::Text(Ok(Command::Route(route)))) => {
+                if mode != RobotMode::Grid {
+                    chassis.coast();
+                    mode = RobotMode::Grid;
+                }
+                grid_mode.start(route);
+                Console::reply_ok(&mut serial);
+            },
+            Some(ConsoleInput::Text(Err(error))) => Console::reply_err(&mut serial, error),
+            Some(ConsoleInput::Frame(Ok(frame))) => match frame.message {
+                Message::Motor { left, right } => {
+                    mode = RobotMode::Idle;
+                    chassis.drive(left, right);
+                },
+                Message::Servo { angle } => scanner.servo().set_angle(angle),
+                Message::Request { id: MessageId::Distance } => {
+                    rangefinder.start();
+                    distance_request = Some(DistanceRequest::Frame(frame.sequence));
+                },
+                Message::Request { id: MessageId::Line } => {
+                    let (left, center, right) = line_tracker.measure_full().dark_states();
+                    let message = Message::Line { at_ms: clock::millis() as u32, left, center, right };
+                    Console::send_frame(&mut serial, &Frame::new(frame.sequence, message));
+                },
+                Message::TelemetryConfig { period_ms, channels } => {
+                    telemetry.set_channels(channels);
+                    telemetry.configure(period_ms, TelemetryFormat::Binary, clock::millis());
+                },
+                // The car only sends samples, and cannot be asked for commands.
+                _ => {},
+            },
+            // A corrupted frame is dropped, and the host notices from the sequence numbers.
+            Some(ConsoleInput::Frame(Err(_))) => {},
+            None => {},
+        }
+
+        // The pings are taken from the main loop, so that the chassis and the modes keep running meanwhile.
+        // A mode that uses the sensor would lose its own pings to them, so it is answered with the mode's last one.
+        if let Some(request) = distance_request {
+            let measurement = if mode == RobotMode::Idle {
+                match rangefinder.poll(scanner.sensor()) {
+                    Ok(filtered) => Some(filtered.distance),
+                    Err(nb::Error::WouldBlock) => None,
+                    Err(nb::Error::Other(_)) => Some(DistanceMeasurement::Unknown),
+                }
+            } else {
+                Some(scanner.sensor().last_measurement().unwrap_or(DistanceMeasurement::Unknown))
+            };
+            if let Some(measurement) = measurement {
+                send_distance(&mut serial, request, measurement);
+                distance_request = None;
+            }
+        }
+
+        match mode {
+            RobotMode::Idle => {},
+            RobotMode::Avoid => avoid_mode.update(&mut chassis, &mut scanner),
+            RobotMode::FollowLine => {
+                follow_mode.update(&mut chassis, &mut line_tracker);
+            },
+            RobotMode::Grid => {
+                if let Some(report) = grid_mode.update(&mut chassis, &mut line_tracker) {
+                    Console::send_step_report(&mut serial, &report);
+                    if grid_mode.executor().is_stopped() {
+                        Console::send_route_done(&mut serial);
+                    }
+                }
+            },
+        }
+
+        if let Some(mut record) = telemetry.poll(clock::millis()) {
+            let channels = record.channels;
+            if channels.contains(Channels::MOTOR) {
+                record.motor = chassis.command();
+            }
+            if channels.contains(Channels::SERVO) {
+                record.servo_angle = scanner.servo().current_angle();
+            }
+            if channels.contains(Channels::DISTANCE) {
+                if let Some(measurement) = scanner.sensor().last_measurement() {
+                    record.distance = DistanceReading::from(measurement);
+                }
+            }
+            if channels.contains(Channels::LINE) {
+                record.line = line_tracker.measure_full().dark_states();
+            }
+
+            match telemetry.format() {
+                TelemetryFormat::Text => Console::send_text_record(&mut serial, &record),
+                TelemetryFormat::Binary => {
+                    let frame = Frame::new(telemetry.sequence(), Message::Telemetry(record));
+                    Console::send_frame(&mut serial, &frame);
+                },
+            }
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,162 @@
+//! The PWM outputs on the motor speed pins, for the [smartcar::l287n_motor_driver] and the [smartcar::tb6612_motor_driver].
+//!
+//! The speed pins (ENA and ENB on the V3 kit, PWMA and PWMB on the V4 kit) are wired to the OC0B and OC0A outputs
+//! of the TC0 timer, which are PD5 and PD6 on the ATmega328P, and PG5 and PB7 on the ATmega2560.
+//! We run TC0 in fast PWM mode, so the duty cycle on the speed pins sets the speed of each motor pair.
+//! With a prescaler of 64, the PWM frequency is 16MHz / 64 / 256 = 976Hz.
+//!
+//! On the V2 kit, ENB is wired to D11 (PB3), which is not an output of TC0, and D6 drives IN1 instead.
+//! The compare A unit of TC0 still times the pulses of ENB, but its interrupts switch the pin, see [interrupt_output].
+
+#[cfg(not(feature = "arduino-mega2560"))]
+use arduino_hal::hal::port::PD5 as Oc0b;
+#[cfg(not(any(feature = "arduino-mega2560", feature = "kit-v2")))]
+use arduino_hal::hal::port::PD6 as Oc0a;
+#[cfg(feature = "arduino-mega2560")]
+use arduino_hal::hal::port::{PB7 as Oc0a, PG5 as Oc0b};
+use arduino_hal::port::mode::Output;
+use arduino_hal::port::Pin;
+use embedded_hal::PwmPin;
+
+/// The pin whose pulses are timed by the compare A unit of TC0.
+#[cfg(not(feature = "kit-v2"))]
+type ChannelAPin = Oc0a;
+/// The pin whose pulses are timed by the compare A unit of TC0.
+#[cfg(feature = "kit-v2")]
+type ChannelAPin = arduino_hal::hal::port::PB3;
+
+/// Which output compare unit of TC0 drives the pin.
+#[derive(Clone, Copy)]
+enum Channel {
+    /// OC0A.
+    A,
+    /// OC0B.
+    B,
+}
+
+/// One of the two PWM outputs of TC0.
+pub struct MotorPwm {
+    channel: Channel,
+    pin: Pin<Output>,
+}
+
+impl MotorPwm {
+    /// Configure TC0 for fast PWM, and get the outputs on the OC0B and OC0A pins, in that order.
+    ///
+    /// Both outputs start out disabled.
+    pub fn split(
+        tc0: arduino_hal::pac::TC0,
+        pin_enable_a: Pin<Output, Oc0b>,
+        pin_enable_b: Pin<Output, ChannelAPin>,
+    ) -> (Self, Self) {
+        // Fast PWM mode, counting from 0 to 255, prescaling 64.
+        // The output compare pins are connected in `enable`.
+        tc0.tccr0a.write(|w| w.wgm0().pwm_fast());
+        tc0.tccr0b.write(|w| w.cs0().prescale_64());
+
+        let mut enable_a = Self { channel: Channel::B, pin: pin_enable_a.downgrade() };
+        let mut enable_b = Self { channel: Channel::A, pin: pin_enable_b.downgrade() };
+        enable_a.disable();
+        enable_b.disable();
+        (enable_a, enable_b)
+    }
+
+    fn tc0(&self) -> &arduino_hal::pac::tc0::RegisterBlock {
+        // SAFETY: TC0 was taken in `split`, and each output only touches its own channel.
+        // The shared TCCR0A register is only modified from the main program, never from an interrupt.
+        unsafe { &*arduino_hal::pac::TC0::ptr() }
+    }
+
+    /// Connect the timer to the pin, or disconnect it.
+    fn connect(&mut self, connected: bool) {
+        let channel = self.channel;
+        #[cfg(feature = "kit-v2")]
+        {
+            if let Channel::A = channel {
+                interrupt_output::connect(self.tc0(), connected);
+                return;
+            }
+        }
+        self.tc0().tccr0a.modify(|_, w| match (channel, connected) {
+            (Channel::A, false) => w.com0a().disconnected(),
+            (Channel::A, true) => w.com0a().match_clear(),
+            (Channel::B, false) => w.com0b().disconnected(),
+            (Channel::B, true) => w.com0b().match_clear(),
+        });
+    }
+}
+
+impl PwmPin for MotorPwm {
+    type Duty = u8;
+
+    /// Disconnect the timer from the pin, and hold it low.
+    ///
+    /// In fast PWM mode, a compare value of 0 still produces a short pulse on every cycle,
+    /// so this is the only way to fully stop a motor.
+    fn disable(&mut self) {
+        self.connect(false);
+        self.pin.set_low();
+    }
+
+    fn enable(&mut self) {
+        self.connect(true);
+    }
+
+    fn get_duty(&self) -> u8 {
+        match self.channel {
+            Channel::A => self.tc0().ocr0a.read().bits(),
+            Channel::B => self.tc0().ocr0b.read().bits(),
+        }
+    }
+
+    fn get_max_duty(&self) -> u8 {
+        u8::MAX
+    }
+
+    fn set_duty(&mut self, duty: u8) {
+        match self.channel {
+            Channel::A => self.tc0().ocr0a.write(|w| unsafe { w.bits(duty) }),
+            Channel::B => self.tc0().ocr0b.write(|w| unsafe { w.bits(duty) }),
+        }
+    }
+}
+
+/// Switching ENB on D11 (PB3) from the interrupts of TC0, on the V2 kit.
+///
+/// The overflow interrupt pulls the pin high at the start of every PWM cycle,
+/// and the compare A interrupt pulls it low when TC0 reaches OCR0A, like the OC0A output would.
+/// The pin is switched by writing its bit to PINB, which toggles it without touching the other pins of the port.
+#[cfg(feature = "kit-v2")]
+mod interrupt_output {
+    /// The bit of the ENB pin in the PORTB and PINB registers.
+    const PIN_BIT: u8 = 1 << 3;
+
+    /// Enable or disable the interrupts that switch the pin.
+    pub(super) fn connect(tc0: &arduino_hal::pac::tc0::RegisterBlock, connected: bool) {
+        tc0.timsk0.modify(|_, w| w.toie0().bit(connected).ocie0a().bit(connected));
+    }
+
+    /// Switch the pin to the given level, if it is not there already.
+    fn set(high: bool) {
+        // SAFETY: writing only the pin's bit to PINB toggles that pin, and leaves the rest of the port alone.
+        let portb = unsafe { &*arduino_hal::pac::PORTB::ptr() };
+        if (portb.portb.read().bits() & PIN_BIT != 0) != high {
+            portb.pinb.write(|w| unsafe { w.bits(PIN_BIT) });
+        }
+    }
+
+    #[avr_device::interrupt(atmega328p)]
+    fn TIMER0_OVF() {
+        // SAFETY: the compare value is only read here, which has no side effects.
+        let tc0 = unsafe { &*arduino_hal::pac::TC0::ptr() };
+        // Unlike OC0A, which sends a short pulse with a compare value of 0, the pin stays low then.
+        set(tc0.ocr0a.read().bits() != 0);
+    }
+
+    /// At a compare value of 255, this runs right before the overflow interrupt, which has a lower priority,
+    /// so the pin is only low for the few cycles between the two.
+    #[avr_device::interrupt(atmega328p)]
+    fn TIMER0_COMPA() {
+        set(false);
+    }
+}
@@ -0,0 +1,185 @@
+//! The pulse generator for the servo, for the [smartcar::servo] driver.
+//!
+//! The pulses are generated in the background by a hardware timer, from the millisecond interrupt in [crate::clock]:
+//! at the start of every 20ms frame, the servo pin is forced high,
+//! and an output compare unit is armed to pull it low at the exact tick when the pulse should end.
+//! Which timer that is depends on the pin that the servo is wired to, see [crate::board::ServoPin]:
+//!
+//! - On the V2 and V3 kits, the servo is on D3 (PD3) of the Uno, or on D9 (PH6) of the Mega 2560,
+//!   which is the OC2B output of TC2, the timer of the millisecond clock.
+//!   TC2 wraps around every millisecond, so we count the position in the frame,
+//!   and arm the compare unit in the millisecond period in which the pulse ends.
+//! - On the V4 kit, the servo is on D10 (PB2), which is the OC1B output of TC1, the free-running timer
+//!   of the [crate::echo_timer]. TC1 counts every 4µs for much longer than a pulse,
+//!   so the compare unit is armed right at the start of the pulse.
+
+use core::cell;
+
+use arduino_hal::port::mode::Output;
+use arduino_hal::port::Pin;
+use embedded_hal::PwmPin;
+use smartcar::servo::FRAME_US;
+
+use crate::board::ServoPin;
+
+/// The length of a tick of the timer that ends the pulses, in µs.
+const US_PER_TICK: u16 = 4;
+
+/// The number of millisecond interrupt periods in a 20ms servo frame.
+const PERIODS_PER_FRAME: u8 = 20;
+
+/// The length of the pulse to generate, in timer ticks, or 0 if the servo is detached.
+static PULSE_TICKS: avr_device::interrupt::Mutex<cell::Cell<u16>> =
+    avr_device::interrupt::Mutex::new(cell::Cell::new(0));
+
+/// The pulse length of the current frame, in timer ticks, latched from [PULSE_TICKS] at the start of the frame.
+///
+/// On the V2 and V3 kits, the end of the pulse is armed in a later millisecond period than its start,
+/// so a pulse length changed in between could move the end to a period that has already passed,
+/// and the pin would stay high for the whole frame.
+static FRAME_PULSE_TICKS: avr_device::interrupt::Mutex<cell::Cell<u16>> =
+    avr_device::interrupt::Mutex::new(cell::Cell::new(0));
+
+/// The position of the current millisecond interrupt period in the 20ms frame.
+static FRAME_POSITION: avr_device::interrupt::Mutex<cell::Cell<u8>> =
+    avr_device::interrupt::Mutex::new(cell::Cell::new(0));
+
+/// The servo pulses on the servo pin, as a PWM output whose duty cycle is the pulse width in µs.
+///
+/// The millisecond clock must be initialized with [crate::clock::millis_init] for the pulses to be sent.
+pub struct ServoPulses {
+    pin: Pin<Output, ServoPin>,
+    /// The pulse width in µs, kept while the pulses are disabled.
+    duty_us: u16,
+    enabled: bool,
+}
+
+impl ServoPulses {
+    /// Take the servo pin. No pulses are sent until the output is enabled.
+    pub fn new(pin: Pin<Output, ServoPin>) -> Self {
+        Self { pin, duty_us: 0, enabled: false }
+    }
+
+    /// Hand the pulse width over to the interrupt.
+    fn update_pulse(&self) {
+        let ticks = if self.enabled { self.duty_us / US_PER_TICK } else { 0 };
+        avr_device::interrupt::free(|cs| PULSE_TICKS.borrow(cs).set(ticks));
+    }
+}
+
+impl PwmPin for ServoPulses {
+    type Duty = u16;
+
+    /// Stop sending pulses, and hold the pin low.
+    fn disable(&mut self) {
+        self.enabled = false;
+        self.update_pulse();
+        self.pin.set_low();
+    }
+
+    /// Start sending pulses; the first one goes out at the start of the next 20ms frame.
+    fn enable(&mut self) {
+        self.enabled = true;
+        self.update_pulse();
+    }
+
+    fn get_duty(&self) -> u16 {
+        self.duty_us
+    }
+
+    fn get_max_duty(&self) -> u16 {
+        FRAME_US as u16
+    }
+
+    fn set_duty(&mut self, duty_us: u16) {
+        self.duty_us = duty_us;
+        self.update_pulse();
+    }
+}
+
+/// Advance the servo pulse generator by one millisecond interrupt period.
+///
+/// This is called from the TC2 compare interrupt in [crate::clock], right after the timer wrapped around to zero.
+pub(crate) fn on_millis_tick(cs: &avr_device::interrupt::CriticalSection) {
+    let position_cell = FRAME_POSITION.borrow(cs);
+    let position = position_cell.get();
+    position_cell.set((position + 1) % PERIODS_PER_FRAME);
+
+    // A new pulse length takes effect at the start of the next frame, but detaching the servo stops the pulses now.
+    let pulse_ticks = PULSE_TICKS.borrow(cs).get();
+    let frame_pulse_cell = FRAME_PULSE_TICKS.borrow(cs);
+    if position == 0 || pulse_ticks == 0 {
+        frame_pulse_cell.set(pulse_ticks);
+    }
+
+    output_compare::update(position, frame_pulse_cell.get());
+}
+
+/// Driving the servo pin from TC2's output compare B unit, on the V2 and V3 kits.
+#[cfg(any(feature = "kit-v2", feature = "kit-v3"))]
+mod output_compare {
+    /// The number of TC2 ticks in one millisecond interrupt period (the timer counts from 0 to 250 inclusive).
+    const TICKS_PER_PERIOD: u16 = 251;
+
+    /// The smallest compare value we can arm for the end of the pulse.
+    ///
+    /// When the interrupt runs, the timer has already counted a few ticks past zero,
+    /// so a compare value that is too small would be missed, and the pulse would last a whole millisecond longer.
+    const MIN_END_TICK: u16 = 8;
+
+    /// Start or end the pulse during the millisecond period at the given position in the frame.
+    ///
+    /// `pulse_ticks` must stay the same for the whole frame, see [super::FRAME_PULSE_TICKS].
+    pub(super) fn update(position: u8, pulse_ticks: u16) {
+        // SAFETY: TC2 is owned by the clock, which only configures it once at startup.
+        // The output compare B unit is only touched from this interrupt.
+        let tc2 = unsafe { &*arduino_hal::pac::TC2::ptr() };
+
+        if pulse_ticks == 0 {
+            tc2.tccr2a.modify(|_, w| w.com2b().disconnected());
+            return;
+        }
+
+        if position == 0 {
+            // Start of the frame: force a compare match with the output set to go high.
+            tc2.tccr2a.modify(|_, w| w.com2b().match_set());
+            tc2.tccr2b.modify(|_, w| w.foc2b().set_bit());
+        }
+
+        // Arm the compare unit to pull the pin low if the pulse ends during this period.
+        let end_period = pulse_ticks / TICKS_PER_PERIOD;
+        let end_tick = pulse_ticks % TICKS_PER_PERIOD;
+        if position as u16 == end_period {
+            let end_tick = end_tick.max(MIN_END_TICK);
+            tc2.ocr2b.write(|w| unsafe { w.bits(end_tick as u8) });
+            tc2.tccr2a.modify(|_, w| w.com2b().match_clear());
+        }
+    }
+}
+
+/// Driving the servo pin from TC1's output compare B unit, on the V4 kit.
+#[cfg(feature = "kit-v4")]
+mod output_compare {
+    /// Start the pulse at the start of the frame, and arm its end.
+    pub(super) fn update(position: u8, pulse_ticks: u16) {
+        // SAFETY: TC1 is owned by the echo timer, which only configures its counter once at startup.
+        // The output compare B unit is only touched from this interrupt.
+        let tc1 = unsafe { &*arduino_hal::pac::TC1::ptr() };
+
+        if pulse_ticks == 0 {
+            tc1.tccr1a.modify(|_, w| w.com1b().disconnected());
+            return;
+        }
+
+        if position == 0 {
+            // Force a compare match with the output set to go high,
+            // then arm the compare unit to pull the pin low once TC1 has counted the pulse length.
+            // The compare value is written before the output is switched over, so an old one cannot end the pulse early.
+            tc1.tccr1a.modify(|_, w| w.com1b().match_set());
+            tc1.tccr1c.write(|w| w.foc1b().set_bit());
+            let start_tick = tc1.tcnt1.read().bits();
+            tc1.ocr1b.write(|w| unsafe { w.bits(start_tick.wrapping_add(pulse_ticks)) });
+            tc1.tccr1a.modify(|_, w| w.com1b().match_clear());
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! The client side of the car's serial protocols.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use smartcar_protocol::command::{parse_reply, Command, CommandError, Reply, RobotMode};
+use smartcar_protocol::frame::MAX_WIRE_FRAME_LENGTH;
+use smartcar_protocol::{Channels, DistanceReading, Frame, Message, MessageId, TelemetryRecord};
+
+use crate::link::{Incoming, Receiver};
+
+/// The ways that talking to the car can fail.
+#[derive(Debug)]
+pub enum Error {
+    /// The port could not be read or written.
+    Io(io::Error),
+    /// The car replied with `ERR`.
+    Rejected(CommandError),
+    /// The car did not reply in time.
+    Timeout,
+    /// The car replied with something that does not make sense.
+    UnexpectedReply(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "serial port error: {}", error),
+            Error::Rejected(error) => write!(f, "the car rejected the command: {:?} (ERR {})", error, error.code()),
+            Error::Timeout => f.write_str("the car did not reply in time"),
+            Error::UnexpectedReply(reply) => write!(f, "unexpected reply from the car: {:?}", reply),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+/// A line tracker sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineSample {
+    /// The time on the car when the sample was taken, in ms.
+    pub at_ms: u32,
+    /// Whether each sensor sees a dark surface.
+    pub left: bool,
+    pub center: bool,
+    pub right: bool,
+}
+
+/// A distance sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DistanceSample {
+    /// The time on the car when the sample was taken, in ms.
+    pub at_ms: u32,
+    pub reading: DistanceReading,
+}
+
+/// A connection to the car, over anything that reads and writes bytes.
+///
+/// Reads may fail with [io::ErrorKind::TimedOut] or [io::ErrorKind::WouldBlock] when nothing has arrived;
+/// that is how serial ports report it.
+pub struct Car<P> {
+    port: P,
+    receiver: Receiver,
+    incoming: VecDeque<Incoming>,
+    /// The sequence number of the next binary request.
+    sequence: u8,
+    /// How long to wait for a reply.
+    timeout: Duration,
+}
+
+impl<P: Read + Write> Car<P> {
+    pub fn new(port: P) -> Self {
+        Self {
+            port,
+            receiver: Receiver::new(),
+            incoming: VecDeque::new(),
+            sequence: 0,
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Send a text command, and get the text that follows `OK` in the reply.
+    ///
+    /// Lines that are not replies (like step reports) and binary frames are skipped.
+    pub fn command(&mut self, command: Command) -> Result<String, Error> {
+        writeln!(self.port, "{}", command)?;
+        self.port.flush()?;
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            if let Incoming::Line(line) = self.next_incoming(deadline)? {
+                match parse_reply(&line) {
+                    Some(Reply::Ok(result)) => return Ok(result.to_string()),
+                    Some(Reply::Err(error)) => return Err(Error::Rejected(error)),
+                    None => {},
+                }
+            }
+        }
+    }
+
+    /// Send a binary request for a sample, and get the car's answer.
+    ///
+    /// Frames with other sequence numbers, corrupted frames and text are skipped.
+    pub fn request(&mut self, id: MessageId) -> Result<Message, Error> {
+        let sequence = self.sequence;
+        self.send(Message::Request { id })?;
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            if let Incoming::Frame(Ok(frame)) = self.next_incoming(deadline)? {
+                if frame.sequence == sequence && frame.message.id() == id {
+                    return Ok(frame.message);
+                }
+            }
+        }
+    }
+
+    /// Send a binary message that has no reply.
+    pub fn send(&mut self, message: Message) -> Result<(), Error> {
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+
+        let mut bytes = [0u8; MAX_WIRE_FRAME_LENGTH];
+        let len = Frame::new(sequence, message)
+            .write(&mut bytes)
+            .expect("every message fits into a frame");
+        self.port.write_all(&bytes[..len])?;
+        self.port.flush()?;
+        Ok(())
+    }
+
+    /// Ask the car to stream binary telemetry records with the given channels every `period_ms`.
+    ///
+    /// Read them with [Car::next_record].
+    pub fn start_telemetry(&mut self, period_ms: u16, channels: Channels) -> Result<(), Error> {
+        self.send(Message::TelemetryConfig { period_ms, channels })
+    }
+
+    /// Ask the car to stop streaming telemetry.
+    pub fn stop_telemetry(&mut self) -> Result<(), Error> {
+        self.send(Message::TelemetryConfig { period_ms: 0, channels: Channels::NONE })
+    }
+
+    /// Wait for the next telemetry record, skipping anything else.
+    ///
+    /// The timeout applies to each record, so it must be longer than the telemetry period.
+    pub fn next_record(&mut self) -> Result<TelemetryRecord, Error> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            if let Incoming::Frame(Ok(Frame { message: Message::Telemetry(record), .. })) = self.next_incoming(deadline)? {
+                return Ok(record);
+            }
+        }
+    }
+
+    /// Drive the wheels, taking over from any autonomous mode.
+    pub fn drive(&mut self, left: i16, right: i16) -> Result<(), Error> {
+        self.command(Command::Drive(left, right)).map(drop)
+    }
+
+    /// Turn the servo.
+    pub fn servo(&mut self, angle: u8) -> Result<(), Error> {
+        self.command(Command::Servo(angle)).map(drop)
+    }
+
+    /// Brake, and leave any autonomous mode.
+    pub fn stop(&mut self) -> Result<(), Error> {
+        self.command(Command::Stop).map(drop)
+    }
+
+    /// Switch to a different mode.
+    pub fn set_mode(&mut self, mode: RobotMode) -> Result<(), Error> {
+        self.command(Command::Mode(mode)).map(drop)
+    }
+
+    /// Measure the distance with the text `ping` command.
+    pub fn ping(&mut self) -> Result<DistanceReading, Error> {
+        let result = self.command(Command::Ping)?;
+        match result.as_str() {
+            "inf" => Ok(DistanceReading::Infinity),
+            "unknown" => Ok(DistanceReading::Unknown),
+            mm => mm.parse().map(DistanceReading::Measured).map_err(|_| Error::UnexpectedReply(result)),
+        }
+    }
+
+    /// Read the line tracker with the text `line` command, and get where the car sees the line.
+    pub fn line(&mut self) -> Result<String, Error> {
+        self.command(Command::Line)
+    }
+
+    /// Get a distance sample over the binary protocol.
+    pub fn distance_sample(&mut self) -> Result<DistanceSample, Error> {
+        match self.request(MessageId::Distance)? {
+            Message::Distance { at_ms, reading } => Ok(DistanceSample { at_ms, reading }),
+            message => Err(Error::UnexpectedReply(format!("{:?}", message))),
+        }
+    }
+
+    /// Get a line tracker sample over the binary protocol.
+    pub fn line_sample(&mut self) -> Result<LineSample, Error> {
+        match self.request(MessageId::Line)? {
+            Message::Line { at_ms, left, center, right } => Ok(LineSample { at_ms, left, center, right }),
+            message => Err(Error::UnexpectedReply(format!("{:?}", message))),
+        }
+    }
+
+    /// Sweep the servo over the given angles, and measure the distance at each one.
+    ///
+    /// After each turn of the servo, it waits for `settle` before pinging.
+    pub fn scan<I>(&mut self, angles: I, settle: Duration) -> Result<Vec<(u8, DistanceReading)>, Error>
+    where
+        I: IntoIterator<Item = u8>,
+    {
+        let mut points = Vec::new();
+        for angle in angles {
+            self.servo(angle)?;
+            thread::sleep(settle);
+            points.push((angle, self.distance_sample()?.reading));
+        }
+        Ok(points)
+    }
+
+    /// Wait for the next line or frame from the car.
+    fn next_incoming(&mut self, deadline: Instant) -> Result<Incoming, Error> {
+        let mut buffer = [0u8; 64];
+        loop {
+            if let Some(incoming) = self.incoming.pop_front() {
+                return Ok(incoming);
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::Timeout);
+            }
+
+            let len = match self.port.read(&mut buffer) {
+                Ok(0) => return Err(Error::Io(io::ErrorKind::UnexpectedEof.into())),
+                Ok(len) => len,
+                Err(error) if is_no_data(&error) => continue,
+                Err(error) => return Err(error.into()),
+            };
+            for &byte in &buffer[..len] {
+                if let Some(incoming) = self.receiver.push(byte) {
+                    self.incoming.push_back(incoming);
+                }
+            }
+        }
+    }
+}
+
+/// Whether a read error only means that nothing has arrived yet.
+fn is_no_data(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+    )
+}
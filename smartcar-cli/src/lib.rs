@@ -0,0 +1,14 @@
+//! Talking to the smart car from a host computer.
+//!
+//! The [Car] client speaks both of the car's protocols from the `smartcar_protocol` crate:
+//! text commands for control, and binary frames for sensor samples.
+//! It works over anything that reads and writes bytes, usually a serial port.
+//!
+//! The [sim] module has a simulated car that serves the same protocols over a pseudo-terminal,
+//! so that the CLI can be used and tested without hardware.
+
+pub mod car;
+pub mod link;
+pub mod sim;
+
+pub use car::{Car, DistanceSample, Error, LineSample};
@@ -0,0 +1,91 @@
+//! Splitting the bytes from the car into text lines and binary frames.
+//!
+//! This mirrors the console on the car: text never contains a zero byte,
+//! and every binary frame starts and ends with one.
+
+use smartcar_protocol::frame::DELIMITER;
+use smartcar_protocol::{Frame, FrameDecoder, ProtocolError};
+
+/// Something received from the car.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Incoming {
+    /// A line of text, without the line ending.
+    Line(String),
+    /// A binary frame.
+    Frame(Result<Frame, ProtocolError>),
+}
+
+/// Collects received bytes into lines and frames.
+#[derive(Default)]
+pub struct Receiver {
+    line: Vec<u8>,
+    frames: FrameDecoder,
+    in_frame: bool,
+}
+
+impl Receiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a received byte, and get the line or frame that it completes, if any.
+    ///
+    /// Empty lines are ignored.
+    pub fn push(&mut self, byte: u8) -> Option<Incoming> {
+        if self.in_frame {
+            let result = self.frames.push(byte)?;
+            self.in_frame = false;
+            return Some(Incoming::Frame(result));
+        }
+
+        match byte {
+            DELIMITER => {
+                self.in_frame = true;
+                None
+            },
+            b'\n' => {
+                let line = String::from_utf8_lossy(&self.line).trim_end_matches('\r').to_string();
+                self.line.clear();
+                if line.is_empty() {
+                    None
+                } else {
+                    Some(Incoming::Line(line))
+                }
+            },
+            _ => {
+                self.line.push(byte);
+                None
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smartcar_protocol::frame::MAX_WIRE_FRAME_LENGTH;
+    use smartcar_protocol::Message;
+
+    #[test]
+    fn separates_lines_and_frames() {
+        // The frame has a newline byte in it, which must not end a line.
+        let frame = Frame::new(b'\n', Message::Servo { angle: b'\n' });
+        let mut wire = [0u8; MAX_WIRE_FRAME_LENGTH];
+        let len = frame.write(&mut wire).unwrap();
+
+        let mut bytes = b"Running!\r\n".to_vec();
+        bytes.extend_from_slice(&wire[..len]);
+        bytes.extend_from_slice(b"OK\r\n");
+
+        let mut receiver = Receiver::new();
+        let incoming: Vec<_> = bytes.iter().filter_map(|&byte| receiver.push(byte)).collect();
+        assert_eq!(
+            incoming,
+            vec![
+                Incoming::Line("Running!".to_string()),
+                Incoming::Frame(Ok(frame)),
+                Incoming::Line("OK".to_string()),
+            ]
+        );
+    }
+}
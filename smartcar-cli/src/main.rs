@@ -0,0 +1,242 @@
+use std::error::Error;
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand};
+use serialport::TTYPort;
+use smartcar_cli::sim::{Pty, SimulatedCar};
+use smartcar_cli::Car;
+use smartcar_protocol::command::RobotMode;
+use smartcar_protocol::{Channels, DistanceReading, TelemetryRecord};
+
+/// Talk to the smart car over its serial port.
+#[derive(Parser)]
+#[command(name = "smartcar-cli", version)]
+struct Cli {
+    /// The serial port of the car, or the pty of a simulated car.
+    #[arg(short, long, env = "SMARTCAR_PORT")]
+    port: Option<String>,
+    #[arg(short, long, default_value_t = 57600)]
+    baud: u32,
+    /// How long to wait after opening the port, since opening it resets the Arduino.
+    #[arg(long, default_value_t = 2000)]
+    boot_wait_ms: u64,
+    #[command(subcommand)]
+    command: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    /// Drive the wheels with signed commands from -255 to 255, taking over from any autonomous mode.
+    Drive {
+        #[arg(allow_negative_numbers = true)]
+        left: i16,
+        #[arg(allow_negative_numbers = true)]
+        right: i16,
+    },
+    /// Brake, and leave any autonomous mode.
+    Stop,
+    /// Turn the servo to an angle from 0 (right) to 180 (left).
+    Servo { angle: u8 },
+    /// Measure the distance once.
+    Ping,
+    /// Read the line tracker once.
+    Line,
+    /// Switch the car to a mode: idle, avoid, follow-line or grid.
+    Mode {
+        #[arg(value_parser = parse_mode)]
+        mode: RobotMode,
+    },
+    /// Sweep the servo and measure the distance in every direction.
+    Scan {
+        #[arg(long, default_value_t = 0)]
+        from: u8,
+        #[arg(long, default_value_t = 180)]
+        to: u8,
+        #[arg(long, default_value_t = 15)]
+        step: u8,
+        /// How long to let the servo settle before each ping.
+        #[arg(long, default_value_t = 150)]
+        settle_ms: u64,
+        /// Print CSV instead of a table.
+        #[arg(long)]
+        csv: bool,
+    },
+    /// Stream telemetry records from the car until interrupted.
+    Telemetry {
+        /// Print CSV instead of text.
+        #[arg(long)]
+        csv: bool,
+        #[arg(long, default_value_t = 200, value_parser = clap::value_parser!(u16).range(1..))]
+        interval_ms: u16,
+        /// The channels to stream: motor, servo, distance, line, timing or all.
+        #[arg(long, value_delimiter = ',', default_value = "all", value_parser = parse_channels)]
+        channels: Vec<Channels>,
+        /// Stop after this many records.
+        #[arg(long)]
+        count: Option<u64>,
+    },
+    /// Run a simulated car on a new pseudo-terminal, and print its path.
+    Simulate,
+}
+
+fn parse_mode(name: &str) -> Result<RobotMode, String> {
+    RobotMode::from_name(name).ok_or_else(|| format!("unknown mode {:?}, expected idle, avoid, follow-line or grid", name))
+}
+
+fn parse_channels(name: &str) -> Result<Channels, String> {
+    Channels::from_name(name)
+        .ok_or_else(|| format!("unknown channel {:?}, expected motor, servo, distance, line, timing or all", name))
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(error) = run(cli) {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    if let Action::Simulate = cli.command {
+        let pty = Pty::open()?;
+        println!("{}", pty.path.display());
+        io::stdout().flush()?;
+        return Ok(SimulatedCar::new().serve(pty.master)?);
+    }
+
+    let path = cli.port.ok_or("no port given; use --port or set SMARTCAR_PORT")?;
+    let port: TTYPort = serialport::new(&path, cli.baud)
+        .timeout(Duration::from_millis(50))
+        .open_native()?;
+    thread::sleep(Duration::from_millis(cli.boot_wait_ms));
+    let mut car = Car::new(port);
+
+    match cli.command {
+        Action::Drive { left, right } => car.drive(left, right)?,
+        Action::Stop => car.stop()?,
+        Action::Servo { angle } => car.servo(angle)?,
+        Action::Ping => println!("{}", format_distance(car.ping()?)),
+        Action::Line => println!("{}", car.line()?),
+        Action::Mode { mode } => car.set_mode(mode)?,
+        Action::Scan { from, to, step, settle_ms, csv } => {
+            let angles = (from..=to).step_by(step.max(1) as usize);
+            let points = car.scan(angles, Duration::from_millis(settle_ms))?;
+            if csv {
+                println!("angle,distance_mm");
+            }
+            for (angle, reading) in points {
+                if csv {
+                    println!("{},{}", angle, csv_distance(reading));
+                } else {
+                    println!("{:>3} deg  {}", angle, format_distance(reading));
+                }
+            }
+        },
+        Action::Telemetry { csv, interval_ms, channels, count } => {
+            let channels = channels
+                .into_iter()
+                .fold(Channels::NONE, |all, channels| all.with(channels, true));
+            if csv {
+                println!("{}", csv_header(channels));
+            }
+            car.set_timeout(Duration::from_millis(interval_ms as u64) + Duration::from_secs(1));
+            car.start_telemetry(interval_ms, channels)?;
+            let mut printed = 0;
+            while count.is_none_or(|count| printed < count) {
+                let record = car.next_record()?;
+                if csv {
+                    println!("{}", csv_record(&record));
+                } else {
+                    println!("{}", text_record(&record));
+                }
+                io::stdout().flush()?;
+                printed += 1;
+            }
+            car.stop_telemetry()?;
+        },
+        Action::Simulate => unreachable!(),
+    }
+    Ok(())
+}
+
+fn format_distance(reading: DistanceReading) -> String {
+    match reading {
+        DistanceReading::Measured(mm) => format!("{} mm", mm),
+        DistanceReading::Infinity => "nothing in range".to_string(),
+        DistanceReading::Unknown => "no echo".to_string(),
+    }
+}
+
+/// A distance for a CSV column: a number of mm, `inf`, or empty if it is not known.
+fn csv_distance(reading: DistanceReading) -> String {
+    match reading {
+        DistanceReading::Measured(mm) => mm.to_string(),
+        DistanceReading::Infinity => "inf".to_string(),
+        DistanceReading::Unknown => String::new(),
+    }
+}
+
+/// The CSV columns of the given channels, in the order of [csv_record].
+fn csv_header(channels: Channels) -> String {
+    let columns: [(Channels, &str); 5] = [
+        (Channels::MOTOR, ",motor_left,motor_right"),
+        (Channels::SERVO, ",servo_deg"),
+        (Channels::DISTANCE, ",distance_mm"),
+        (Channels::LINE, ",line_left,line_center,line_right"),
+        (Channels::TIMING, ",loop_count,max_loop_ms"),
+    ];
+    let mut header = "time_ms".to_string();
+    for &(channel, names) in &columns {
+        if channels.contains(channel) {
+            header += names;
+        }
+    }
+    header
+}
+
+fn csv_record(record: &TelemetryRecord) -> String {
+    let channels = record.channels;
+    let mut row = record.at_ms.to_string();
+    if channels.contains(Channels::MOTOR) {
+        row += &format!(",{},{}", record.motor.0, record.motor.1);
+    }
+    if channels.contains(Channels::SERVO) {
+        row += &format!(",{}", record.servo_angle);
+    }
+    if channels.contains(Channels::DISTANCE) {
+        row += &format!(",{}", csv_distance(record.distance));
+    }
+    if channels.contains(Channels::LINE) {
+        let (left, center, right) = record.line;
+        row += &format!(",{},{},{}", left as u8, center as u8, right as u8);
+    }
+    if channels.contains(Channels::TIMING) {
+        row += &format!(",{},{}", record.loop_count, record.max_loop_ms);
+    }
+    row
+}
+
+fn text_record(record: &TelemetryRecord) -> String {
+    let channels = record.channels;
+    let sensor = |dark: bool| if dark { '#' } else { '.' };
+    let mut text = format!("{:>8} ms", record.at_ms);
+    if channels.contains(Channels::MOTOR) {
+        text += &format!("  motor {:>4} {:>4}", record.motor.0, record.motor.1);
+    }
+    if channels.contains(Channels::SERVO) {
+        text += &format!("  servo {:>3} deg", record.servo_angle);
+    }
+    if channels.contains(Channels::DISTANCE) {
+        text += &format!("  distance {}", format_distance(record.distance));
+    }
+    if channels.contains(Channels::LINE) {
+        let (left, center, right) = record.line;
+        text += &format!("  line {}{}{}", sensor(left), sensor(center), sensor(right));
+    }
+    if channels.contains(Channels::TIMING) {
+        text += &format!("  loop {} passes, max {} ms", record.loop_count, record.max_loop_ms);
+    }
+    text
+}
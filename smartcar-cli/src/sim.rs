@@ -0,0 +1,360 @@
+//! A simulated car, which answers the same protocols as the firmware over a pseudo-terminal.
+//!
+//! It does not model any physics: the wheels and the servo go wherever they are told,
+//! the distance sensor sees a fixed room with a box straight ahead, and the line is always under the center sensor.
+//! The main loop is pretended to take 1 ms, for the timing telemetry channel.
+//! That is enough to try out the CLI, and to test it, without a car.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsFd, OwnedFd};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::pty::openpty;
+use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg};
+use nix::unistd::ttyname;
+use smartcar_protocol::command::{Command, CommandError, LineBuffer, RobotMode};
+use smartcar_protocol::frame::{DELIMITER, MAX_WIRE_FRAME_LENGTH};
+use smartcar_protocol::{Channels, DistanceReading, Frame, FrameDecoder, Message, MessageId, TelemetryRecord};
+
+/// The distance to the walls of the simulated room, in mm.
+const WALL_DISTANCE_MM: u16 = 1500;
+/// The distance to the box straight ahead, in mm.
+const BOX_DISTANCE_MM: u16 = 300;
+/// The servo angles at which the box is seen.
+const BOX_ANGLES: (u8, u8) = (75, 105);
+/// Up to this angle, the sensor looks out of an open door on the right, and sees nothing.
+const DOOR_ANGLE: u8 = 15;
+
+/// The state of the simulated car.
+pub struct SimulatedCar {
+    started: Instant,
+    lines: LineBuffer,
+    frames: FrameDecoder,
+    in_frame: bool,
+    wheels: (i16, i16),
+    servo_angle: u8,
+    mode: RobotMode,
+    /// The time between telemetry records, in ms, or 0 if telemetry is off.
+    telemetry_period_ms: u16,
+    /// Whether telemetry was turned on with a binary message, rather than from the text console.
+    telemetry_binary: bool,
+    channels: Channels,
+    /// The time when the next telemetry record is due.
+    next_record: Instant,
+    record_sequence: u8,
+    /// The number of times that telemetry was polled since the last record, which are the passes of the car's loop.
+    loop_passes: u16,
+}
+
+impl Default for SimulatedCar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulatedCar {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            lines: LineBuffer::new(),
+            frames: FrameDecoder::new(),
+            in_frame: false,
+            wheels: (0, 0),
+            servo_angle: 90,
+            mode: RobotMode::Idle,
+            telemetry_period_ms: 0,
+            telemetry_binary: false,
+            channels: Channels::ALL,
+            next_record: Instant::now(),
+            record_sequence: 0,
+            loop_passes: 0,
+        }
+    }
+
+    /// The last wheel commands.
+    pub fn wheels(&self) -> (i16, i16) {
+        self.wheels
+    }
+
+    pub fn servo_angle(&self) -> u8 {
+        self.servo_angle
+    }
+
+    pub fn mode(&self) -> RobotMode {
+        self.mode
+    }
+
+    /// The selected telemetry channels.
+    pub fn channels(&self) -> Channels {
+        self.channels
+    }
+
+    /// What the distance sensor sees at the given servo angle.
+    pub fn distance_at(angle: u8) -> DistanceReading {
+        if angle <= DOOR_ANGLE {
+            DistanceReading::Infinity
+        } else if angle >= BOX_ANGLES.0 && angle <= BOX_ANGLES.1 {
+            DistanceReading::Measured(BOX_DISTANCE_MM)
+        } else {
+            DistanceReading::Measured(WALL_DISTANCE_MM)
+        }
+    }
+
+    /// Handle a byte received from the host, and append any reply to `out`.
+    pub fn handle_byte(&mut self, byte: u8, out: &mut Vec<u8>) {
+        if self.in_frame {
+            if let Some(result) = self.frames.push(byte) {
+                self.in_frame = false;
+                // Like the firmware, corrupted frames are dropped without a reply.
+                if let Ok(frame) = result {
+                    self.handle_frame(frame, out);
+                }
+            }
+        } else if byte == DELIMITER {
+            self.in_frame = true;
+        } else if let Some(result) = self.lines.push(byte) {
+            self.handle_command(result, out);
+        }
+    }
+
+    fn handle_command(&mut self, result: Result<Command, CommandError>, out: &mut Vec<u8>) {
+        let reply = match result {
+            Ok(Command::Drive(left, right)) => {
+                self.mode = RobotMode::Idle;
+                self.wheels = (left, right);
+                "OK".to_string()
+            },
+            Ok(Command::Servo(angle)) => {
+                self.servo_angle = angle;
+                "OK".to_string()
+            },
+            Ok(Command::Ping) => match Self::distance_at(self.servo_angle) {
+                DistanceReading::Measured(mm) => format!("OK {}", mm),
+                DistanceReading::Infinity => "OK inf".to_string(),
+                DistanceReading::Unknown => "OK unknown".to_string(),
+            },
+            Ok(Command::Line) => "OK Center".to_string(),
+            Ok(Command::Stop) => {
+                self.mode = RobotMode::Idle;
+                self.wheels = (0, 0);
+                "OK".to_string()
+            },
+            Ok(Command::Mode(mode)) => {
+                self.mode = mode;
+                "OK".to_string()
+            },
+            Ok(Command::Telemetry(period_ms)) => {
+                self.configure_telemetry(period_ms, false);
+                "OK".to_string()
+            },
+            Ok(Command::Channel(channels, enabled)) => {
+                self.channels = self.channels.with(channels, enabled);
+                "OK".to_string()
+            },
+            // The simulated car does not follow lines, so there is nothing to tune.
+            Ok(Command::Pid(..)) | Ok(Command::LineColor(_)) => "OK".to_string(),
+            // There are no crossings to count, so the route is taken but never driven.
+            Ok(Command::Route(_)) => {
+                self.mode = RobotMode::Grid;
+                "OK".to_string()
+            },
+            Err(error) => format!("ERR {}", error.code()),
+        };
+        out.extend_from_slice(reply.as_bytes());
+        out.extend_from_slice(b"\r\n");
+    }
+
+    fn handle_frame(&mut self, frame: Frame, out: &mut Vec<u8>) {
+        let at_ms = self.started.elapsed().as_millis() as u32;
+        let reply = match frame.message {
+            Message::Motor { left, right } => {
+                self.mode = RobotMode::Idle;
+                self.wheels = (left, right);
+                return;
+            },
+            Message::Servo { angle } => {
+                self.servo_angle = angle;
+                return;
+            },
+            Message::Request { id: MessageId::Distance } => Message::Distance {
+                at_ms,
+                reading: Self::distance_at(self.servo_angle),
+            },
+            Message::Request { id: MessageId::Line } => Message::Line {
+                at_ms,
+                left: false,
+                center: true,
+                right: false,
+            },
+            Message::TelemetryConfig { period_ms, channels } => {
+                self.channels = channels;
+                self.configure_telemetry(period_ms, true);
+                return;
+            },
+            _ => return,
+        };
+        write_frame(Frame::new(frame.sequence, reply), out);
+    }
+
+    fn configure_telemetry(&mut self, period_ms: u16, binary: bool) {
+        self.telemetry_period_ms = period_ms;
+        self.telemetry_binary = binary;
+        self.next_record = Instant::now();
+        self.loop_passes = 0;
+    }
+
+    /// Append a telemetry record to `out` if one is due, and get how long until the next one,
+    /// or `None` if telemetry is off.
+    pub fn poll_telemetry(&mut self, out: &mut Vec<u8>) -> Option<Duration> {
+        if self.telemetry_period_ms == 0 {
+            return None;
+        }
+        self.loop_passes = self.loop_passes.saturating_add(1);
+
+        let now = Instant::now();
+        if now >= self.next_record {
+            let period = Duration::from_millis(self.telemetry_period_ms as u64);
+            self.next_record += period;
+            if self.next_record <= now {
+                self.next_record = now + period;
+            }
+            self.record_sequence = self.record_sequence.wrapping_add(1);
+
+            let record = self.record();
+            self.loop_passes = 0;
+            if self.telemetry_binary {
+                write_frame(Frame::new(self.record_sequence, Message::Telemetry(record)), out);
+            } else {
+                out.extend_from_slice(text_record(&record).as_bytes());
+            }
+        }
+        Some(self.next_record.saturating_duration_since(now))
+    }
+
+    /// A telemetry record of the selected channels, taken now.
+    fn record(&self) -> TelemetryRecord {
+        let mut record = TelemetryRecord::new(self.started.elapsed().as_millis() as u32);
+        record.channels = self.channels;
+        if self.channels.contains(Channels::MOTOR) {
+            record.motor = self.wheels;
+        }
+        if self.channels.contains(Channels::SERVO) {
+            record.servo_angle = self.servo_angle;
+        }
+        if self.channels.contains(Channels::DISTANCE) {
+            record.distance = Self::distance_at(self.servo_angle);
+        }
+        if self.channels.contains(Channels::LINE) {
+            record.line = (false, true, false);
+        }
+        if self.channels.contains(Channels::TIMING) {
+            record.loop_count = self.loop_passes;
+            // The simulated car only waits between its passes, so they take no time to speak of.
+            record.max_loop_ms = 0;
+        }
+        record
+    }
+
+    /// Answer everything that arrives on `port`, and send telemetry while it is on, until the port is closed.
+    pub fn serve<P: Read + Write + AsFd>(&mut self, mut port: P) -> io::Result<()> {
+        let mut buffer = [0u8; 64];
+        let mut out = Vec::new();
+        loop {
+            let wait = self.poll_telemetry(&mut out);
+            if !out.is_empty() {
+                port.write_all(&out)?;
+                port.flush()?;
+                out.clear();
+            }
+
+            // Wait for input, but no longer than until the next telemetry record is due.
+            let timeout = match wait {
+                Some(wait) => PollTimeout::from(wait.as_millis().min(u16::MAX as u128) as u16),
+                None => PollTimeout::NONE,
+            };
+            let mut fds = [PollFd::new(port.as_fd(), PollFlags::POLLIN)];
+            match poll(&mut fds, timeout) {
+                Ok(0) => continue,
+                Ok(_) => {},
+                Err(nix::errno::Errno::EINTR) => continue,
+                Err(error) => return Err(error.into()),
+            }
+
+            let len = match port.read(&mut buffer) {
+                Ok(0) => return Ok(()),
+                Ok(len) => len,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            };
+            for &byte in &buffer[..len] {
+                self.handle_byte(byte, &mut out);
+            }
+            port.write_all(&out)?;
+            port.flush()?;
+            out.clear();
+        }
+    }
+}
+
+fn write_frame(frame: Frame, out: &mut Vec<u8>) {
+    let mut bytes = [0u8; MAX_WIRE_FRAME_LENGTH];
+    let len = frame.write(&mut bytes).expect("every message fits into a frame");
+    out.extend_from_slice(&bytes[..len]);
+}
+
+/// A telemetry record as the firmware prints it on the text console.
+fn text_record(record: &TelemetryRecord) -> String {
+    let channels = record.channels;
+    let mut text = format!("T {}", record.at_ms);
+    if channels.contains(Channels::MOTOR) {
+        text += &format!(" motor {} {}", record.motor.0, record.motor.1);
+    }
+    if channels.contains(Channels::SERVO) {
+        text += &format!(" servo {}", record.servo_angle);
+    }
+    if channels.contains(Channels::DISTANCE) {
+        match record.distance {
+            DistanceReading::Measured(mm) => text += &format!(" distance {}", mm),
+            DistanceReading::Infinity => text += " distance inf",
+            DistanceReading::Unknown => text += " distance unknown",
+        }
+    }
+    if channels.contains(Channels::LINE) {
+        let (left, center, right) = record.line;
+        text += &format!(" line {}{}{}", left as u8, center as u8, right as u8);
+    }
+    if channels.contains(Channels::TIMING) {
+        text += &format!(" timing {} {}", record.loop_count, record.max_loop_ms);
+    }
+    text + "\r\n"
+}
+
+/// A pseudo-terminal for a simulated car to serve.
+pub struct Pty {
+    /// The side that the simulated car reads and writes.
+    pub master: File,
+    /// The side that the CLI opens, like a serial port.
+    pub path: PathBuf,
+    /// The slave side is kept open, so that reading the master does not fail while no one has the path open.
+    _slave: OwnedFd,
+}
+
+impl Pty {
+    /// Open a new pseudo-terminal in raw mode, so that the bytes go through unchanged.
+    pub fn open() -> io::Result<Self> {
+        let pty = openpty(None, None)?;
+        let mut termios = tcgetattr(&pty.slave)?;
+        cfmakeraw(&mut termios);
+        tcsetattr(&pty.slave, SetArg::TCSANOW, &termios)?;
+        let path = ttyname(&pty.slave)?;
+
+        Ok(Self {
+            master: File::from(pty.master),
+            path,
+            _slave: pty.slave,
+        })
+    }
+}
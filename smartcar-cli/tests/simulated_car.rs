@@ -0,0 +1,154 @@
+//! Runs the client and the CLI against the simulated car over a pseudo-terminal.
+
+use std::path::PathBuf;
+use std::process::Command as Process;
+use std::thread;
+use std::time::Duration;
+
+use serialport::TTYPort;
+use smartcar_cli::sim::{Pty, SimulatedCar};
+use smartcar_cli::{Car, Error};
+use smartcar_protocol::command::{Command, CommandError, RobotMode};
+use smartcar_protocol::{Channels, DistanceReading};
+
+/// Start a simulated car in the background, and get the path to talk to it.
+fn start_simulated_car() -> PathBuf {
+    let pty = Pty::open().unwrap();
+    let path = pty.path.clone();
+    thread::spawn(move || {
+        SimulatedCar::new().serve(pty.master).unwrap();
+    });
+    path
+}
+
+fn connect() -> Car<TTYPort> {
+    let path = start_simulated_car();
+    let port = serialport::new(path.to_str().unwrap(), 57600)
+        .timeout(Duration::from_millis(50))
+        .open_native()
+        .unwrap();
+    Car::new(port)
+}
+
+#[test]
+fn text_commands() {
+    let mut car = connect();
+    car.drive(-100, 100).unwrap();
+    car.set_mode(RobotMode::FollowLine).unwrap();
+    car.stop().unwrap();
+    assert_eq!(car.line().unwrap(), "Center");
+
+    car.servo(90).unwrap();
+    assert_eq!(car.ping().unwrap(), DistanceReading::Measured(300));
+    car.servo(0).unwrap();
+    assert_eq!(car.ping().unwrap(), DistanceReading::Infinity);
+
+    match car.command(Command::Servo(200)) {
+        Err(Error::Rejected(CommandError::BadArgument)) => {},
+        other => panic!("expected a rejection, got {:?}", other),
+    }
+}
+
+#[test]
+fn binary_samples() {
+    let mut car = connect();
+    let line = car.line_sample().unwrap();
+    assert!(line.center && !line.left && !line.right);
+
+    let first = car.distance_sample().unwrap();
+    thread::sleep(Duration::from_millis(20));
+    let second = car.distance_sample().unwrap();
+    assert_eq!(second.reading, DistanceReading::Measured(300));
+    assert!(second.at_ms >= first.at_ms + 20);
+}
+
+#[test]
+fn scan_sweeps_the_servo() {
+    let mut car = connect();
+    let points = car.scan(vec![0, 45, 90, 135, 180], Duration::from_millis(0)).unwrap();
+    assert_eq!(
+        points,
+        vec![
+            (0, DistanceReading::Infinity),
+            (45, DistanceReading::Measured(1500)),
+            (90, DistanceReading::Measured(300)),
+            (135, DistanceReading::Measured(1500)),
+            (180, DistanceReading::Measured(1500)),
+        ]
+    );
+}
+
+/// Run the CLI against a new simulated car, and get what it printed.
+fn run_cli(args: &[&str]) -> String {
+    let path = start_simulated_car();
+    let output = Process::new(env!("CARGO_BIN_EXE_smartcar-cli"))
+        .arg("--port")
+        .arg(&path)
+        .args(["--boot-wait-ms", "0"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn cli_subcommands() {
+    assert_eq!(run_cli(&["drive", "-120", "120"]), "");
+    assert_eq!(run_cli(&["mode", "follow-line"]), "");
+    assert_eq!(run_cli(&["ping"]), "300 mm\n");
+    assert_eq!(
+        run_cli(&["scan", "--csv", "--step", "90", "--settle-ms", "0"]),
+        "angle,distance_mm\n0,inf\n90,300\n180,1500\n"
+    );
+
+    let telemetry = run_cli(&["telemetry", "--csv", "--count", "3", "--interval-ms", "10", "--channels", "distance,line"]);
+    let lines: Vec<_> = telemetry.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "time_ms,distance_mm,line_left,line_center,line_right");
+    for line in &lines[1..] {
+        assert!(line.ends_with(",300,0,1,0"), "{}", line);
+    }
+
+    let telemetry = run_cli(&["telemetry", "--csv", "--count", "1", "--interval-ms", "10"]);
+    assert!(telemetry.starts_with(
+        "time_ms,motor_left,motor_right,servo_deg,distance_mm,line_left,line_center,line_right,loop_count,max_loop_ms\n"
+    ));
+    let values: Vec<_> = telemetry.lines().nth(1).unwrap().split(',').collect();
+    assert_eq!(values[1..8], ["0", "0", "90", "300", "0", "1", "0"]);
+    // The simulated car took the record on one of its passes.
+    assert!(values[8].parse::<u16>().unwrap() >= 1, "{}", telemetry);
+}
+
+#[test]
+fn telemetry_streams_the_selected_channels() {
+    let mut car = connect();
+    car.drive(-100, 120).unwrap();
+    car.start_telemetry(20, Channels::MOTOR.with(Channels::TIMING, true)).unwrap();
+
+    let first = car.next_record().unwrap();
+    let second = car.next_record().unwrap();
+    assert_eq!(second.channels, Channels::MOTOR.with(Channels::TIMING, true));
+    assert_eq!(second.motor, (-100, 120));
+    assert_eq!(second.distance, DistanceReading::Unknown);
+    assert!(second.at_ms >= first.at_ms + 15, "{} then {}", first.at_ms, second.at_ms);
+
+    // Commands are still answered while the records stream in.
+    car.servo(0).unwrap();
+    assert_eq!(car.ping().unwrap(), DistanceReading::Infinity);
+
+    car.stop_telemetry().unwrap();
+    car.set_timeout(Duration::from_millis(100));
+    // One record may have been on its way before telemetry stopped.
+    let _ = car.next_record();
+    assert!(matches!(car.next_record(), Err(Error::Timeout)));
+}
+
+#[test]
+fn cli_rejects_unknown_mode() {
+    let output = Process::new(env!("CARGO_BIN_EXE_smartcar-cli"))
+        .args(["--port", "/nonexistent", "mode", "dance"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
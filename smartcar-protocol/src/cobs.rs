@@ -0,0 +1,131 @@
+//! Consistent Overhead Byte Stuffing.
+//!
+//! COBS removes every zero byte from a block of data, at the cost of one extra byte per 254 bytes,
+//! so that a zero byte can mark where a frame ends. After a lost or corrupted byte,
+//! the receiver only has to wait for the next zero to be back in sync.
+
+use crate::ProtocolError;
+
+/// The largest encoded size of `len` bytes of data, not counting the zero delimiter.
+pub const fn max_encoded_len(len: usize) -> usize {
+    len + len / 254 + 1
+}
+
+/// Encode `data` into `out`, and return the length of the encoded data.
+///
+/// The output never contains a zero byte, and does not include the delimiter.
+pub fn encode(data: &[u8], out: &mut [u8]) -> Result<usize, ProtocolError> {
+    if out.len() < max_encoded_len(data.len()) {
+        return Err(ProtocolError::BufferTooSmall);
+    }
+
+    // Where the code byte of the current block goes, and how far the block has got.
+    let mut code_index = 0;
+    let mut code = 1u8;
+    let mut out_index = 1;
+
+    for &byte in data {
+        if byte != 0 {
+            out[out_index] = byte;
+            out_index += 1;
+            code += 1;
+        }
+        if byte == 0 || code == 0xFF {
+            out[code_index] = code;
+            code_index = out_index;
+            out_index += 1;
+            code = 1;
+        }
+    }
+    out[code_index] = code;
+
+    Ok(out_index)
+}
+
+/// Decode `data` (without the delimiter) into `out`, and return the length of the decoded data.
+pub fn decode(data: &[u8], out: &mut [u8]) -> Result<usize, ProtocolError> {
+    let mut in_index = 0;
+    let mut out_index = 0;
+
+    while in_index < data.len() {
+        let code = data[in_index];
+        if code == 0 {
+            return Err(ProtocolError::BadEncoding);
+        }
+        in_index += 1;
+
+        let block_end = in_index + code as usize - 1;
+        if block_end > data.len() {
+            return Err(ProtocolError::BadEncoding);
+        }
+        for &byte in &data[in_index..block_end] {
+            if byte == 0 {
+                return Err(ProtocolError::BadEncoding);
+            }
+            *out.get_mut(out_index).ok_or(ProtocolError::BufferTooSmall)? = byte;
+            out_index += 1;
+        }
+        in_index = block_end;
+
+        // A block that is not full ends with a zero, except for the last one.
+        if code != 0xFF && in_index < data.len() {
+            *out.get_mut(out_index).ok_or(ProtocolError::BufferTooSmall)? = 0;
+            out_index += 1;
+        }
+    }
+
+    Ok(out_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8]) {
+        let mut encoded = [0u8; 600];
+        let len = encode(data, &mut encoded).unwrap();
+        assert!(len <= max_encoded_len(data.len()));
+        assert!(!encoded[..len].contains(&0));
+
+        let mut decoded = [0u8; 600];
+        let decoded_len = decode(&encoded[..len], &mut decoded).unwrap();
+        assert_eq!(&decoded[..decoded_len], data);
+    }
+
+    #[test]
+    fn encodes_known_examples() {
+        let mut out = [0u8; 16];
+        assert_eq!(encode(&[], &mut out), Ok(1));
+        assert_eq!(out[..1], [0x01]);
+        assert_eq!(encode(&[0x00], &mut out), Ok(2));
+        assert_eq!(out[..2], [0x01, 0x01]);
+        assert_eq!(encode(&[0x11, 0x22, 0x00, 0x33], &mut out), Ok(5));
+        assert_eq!(out[..5], [0x03, 0x11, 0x22, 0x02, 0x33]);
+        assert_eq!(encode(&[0x11, 0x00, 0x00, 0x00], &mut out), Ok(5));
+        assert_eq!(out[..5], [0x02, 0x11, 0x01, 0x01, 0x01]);
+    }
+
+    #[test]
+    fn round_trips() {
+        round_trip(&[]);
+        round_trip(&[0]);
+        round_trip(&[0, 0, 1, 0]);
+        round_trip(&[1, 2, 3]);
+
+        let mut long = [0u8; 520];
+        for (i, byte) in long.iter_mut().enumerate() {
+            *byte = (i % 256) as u8;
+        }
+        round_trip(&long);
+        round_trip(&[0xAA; 254]);
+        round_trip(&[0xAA; 255]);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let mut out = [0u8; 16];
+        assert_eq!(decode(&[0x05, 0x11], &mut out), Err(ProtocolError::BadEncoding));
+        assert_eq!(decode(&[0x02, 0x00], &mut out), Err(ProtocolError::BadEncoding));
+        assert_eq!(encode(&[1, 2, 3], &mut out[..3]), Err(ProtocolError::BufferTooSmall));
+    }
+}
@@ -0,0 +1,544 @@
+//! The text commands that are sent to the robot over the serial port.
+//!
+//! Every command is one line of ASCII text: a command name, followed by its arguments, separated by spaces.
+//! The robot replies to every line with `OK` (possibly followed by some data) or `ERR <code>`,
+//! where the code is one of the [CommandError] codes.
+//!
+//! | Command           | Meaning                                                        |
+//! |-------------------|----------------------------------------------------------------|
+//! | `drive <l> <r>`   | Drive the wheels with signed commands from -255 to 255         |
+//! | `servo <deg>`     | Turn the servo to the given angle                              |
+//! | `ping`            | Measure the distance with the ultrasonic sensor                |
+//! | `line`            | Read the line tracker                                          |
+//! | `stop`            | Brake the motors and leave any autonomous mode                 |
+//! | `mode <name>`     | Switch to the `idle`, `avoid`, `follow-line` or `grid` mode    |
+//! | `telemetry <ms>`  | Send a text telemetry record every `<ms>`, or stop if it is 0  |
+//! | `channel <name> <on\|off>` | Turn a telemetry channel (or `all`) on or off     |
+//! | `pid <kp> <ki> <kd>` | Set the gains of the line follower, in thousandths          |
+//! | `line-color <dark\|light>` | Set the color of the line to follow                |
+//! | `route <moves>`   | Drive a route on a grid of lines, see [crate::route]           |
+//!
+//! While a route is driven, the robot reports the result of every step on a line of its own,
+//! such as `STEP 2 l: OK` or `STEP 3 f2: FAIL line lost`, and `ROUTE DONE` once the route has stopped.
+//!
+//! On the robot, the bytes are collected in a fixed-size [LineBuffer], so nothing is allocated.
+//! Host tools write commands with the [Display](fmt::Display) implementation of [Command],
+//! and read the answers with [parse_reply].
+
+use core::fmt;
+
+use crate::route::{GridMove, Route};
+use crate::telemetry::Channels;
+
+/// The longest line that can be received, not counting the line ending.
+pub const MAX_LINE_LENGTH: usize = 32;
+
+/// The operating modes of the robot that can be selected with the `mode` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RobotMode {
+    /// The robot only does what it is told over the serial port.
+    Idle,
+    /// The robot drives around avoiding obstacles.
+    Avoid,
+    /// The robot follows a line.
+    FollowLine,
+    /// The robot drives the route given with the `route` command, on a grid of lines.
+    Grid,
+}
+
+impl RobotMode {
+    /// Parse a mode from its name in the `mode` command.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("idle") {
+            Some(RobotMode::Idle)
+        } else if name.eq_ignore_ascii_case("avoid") {
+            Some(RobotMode::Avoid)
+        } else if name.eq_ignore_ascii_case("follow-line") {
+            Some(RobotMode::FollowLine)
+        } else if name.eq_ignore_ascii_case("grid") {
+            Some(RobotMode::Grid)
+        } else {
+            None
+        }
+    }
+
+    /// The name of the mode in the `mode` command.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RobotMode::Idle => "idle",
+            RobotMode::Avoid => "avoid",
+            RobotMode::FollowLine => "follow-line",
+            RobotMode::Grid => "grid",
+        }
+    }
+}
+
+impl fmt::Display for RobotMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The colors of line that can be selected with the `line-color` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineColor {
+    /// A dark line on a light background.
+    Dark,
+    /// A light line on a dark background.
+    Light,
+}
+
+impl LineColor {
+    /// Parse a color from its name in the `line-color` command.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("dark") {
+            Some(LineColor::Dark)
+        } else if name.eq_ignore_ascii_case("light") {
+            Some(LineColor::Light)
+        } else {
+            None
+        }
+    }
+
+    /// The name of the color in the `line-color` command.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineColor::Dark => "dark",
+            LineColor::Light => "light",
+        }
+    }
+}
+
+impl fmt::Display for LineColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A command received over the serial port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Drive(i16, i16),
+    Servo(u8),
+    Ping,
+    Line,
+    Stop,
+    Mode(RobotMode),
+    Telemetry(u16),
+    Channel(Channels, bool),
+    /// The proportional, integral and derivative gains of the line follower, in thousandths.
+    Pid(u16, u16, u16),
+    LineColor(LineColor),
+    /// Drive the route, switching to the grid mode.
+    Route(Route),
+}
+
+/// Writes the command as it is typed into the console, without the line ending.
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Drive(left, right) => write!(f, "drive {} {}", left, right),
+            Command::Servo(angle) => write!(f, "servo {}", angle),
+            Command::Ping => f.write_str("ping"),
+            Command::Line => f.write_str("line"),
+            Command::Stop => f.write_str("stop"),
+            Command::Mode(mode) => write!(f, "mode {}", mode),
+            Command::Telemetry(period_ms) => write!(f, "telemetry {}", period_ms),
+            Command::Channel(channels, enabled) => {
+                let name = Channels::NAMES
+                    .iter()
+                    .find(|&&(_, named)| named == *channels)
+                    .map_or("?", |&(name, _)| name);
+                write!(f, "channel {} {}", name, if *enabled { "on" } else { "off" })
+            },
+            Command::Pid(kp, ki, kd) => write!(f, "pid {} {} {}", kp, ki, kd),
+            Command::LineColor(color) => write!(f, "line-color {}", color),
+            Command::Route(route) => write!(f, "route {}", route),
+        }
+    }
+}
+
+/// The ways that a command can be wrong, each with its own code in the `ERR` reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandError {
+    /// The line was empty.
+    Empty,
+    /// The command name is not known.
+    UnknownCommand,
+    /// The command needs more arguments.
+    MissingArgument,
+    /// An argument is not a number, or is out of range.
+    BadArgument,
+    /// The command got more arguments than it takes.
+    TooManyArguments,
+    /// The line did not fit into the buffer.
+    LineTooLong,
+    /// The line had bytes that are not ASCII text.
+    NotText,
+}
+
+impl CommandError {
+    /// The code of the error, as sent in the `ERR` reply.
+    pub fn code(&self) -> u8 {
+        match self {
+            CommandError::Empty => 1,
+            CommandError::UnknownCommand => 2,
+            CommandError::MissingArgument => 3,
+            CommandError::BadArgument => 4,
+            CommandError::TooManyArguments => 5,
+            CommandError::LineTooLong => 6,
+            CommandError::NotText => 7,
+        }
+    }
+
+    /// The error with the given code, if there is one.
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(CommandError::Empty),
+            2 => Some(CommandError::UnknownCommand),
+            3 => Some(CommandError::MissingArgument),
+            4 => Some(CommandError::BadArgument),
+            5 => Some(CommandError::TooManyArguments),
+            6 => Some(CommandError::LineTooLong),
+            7 => Some(CommandError::NotText),
+            _ => None,
+        }
+    }
+}
+
+/// The robot's reply to a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reply<'a> {
+    /// The command was carried out; the text after `OK` is its result, if it has one.
+    Ok(&'a str),
+    /// The command was wrong.
+    Err(CommandError),
+}
+
+/// Parse a line (without the line ending) sent by the robot, if it is a reply to a command.
+pub fn parse_reply(line: &str) -> Option<Reply<'_>> {
+    let line = line.trim();
+    let mut words = line.splitn(2, ' ');
+    match words.next()? {
+        "OK" => Some(Reply::Ok(words.next().unwrap_or("").trim())),
+        "ERR" => {
+            let code = words.next()?.trim().parse().ok()?;
+            CommandError::from_code(code).map(Reply::Err)
+        },
+        _ => None,
+    }
+}
+
+/// Parse a single line (without the line ending) into a [Command].
+pub fn parse_command(line: &str) -> Result<Command, CommandError> {
+    let mut words = line.split_ascii_whitespace();
+    let name = words.next().ok_or(CommandError::Empty)?;
+
+    let command = if name.eq_ignore_ascii_case("drive") {
+        let left = parse_argument(words.next())?;
+        let right = parse_argument(words.next())?;
+        if !(-255..=255).contains(&left) || !(-255..=255).contains(&right) {
+            return Err(CommandError::BadArgument);
+        }
+        Command::Drive(left, right)
+    } else if name.eq_ignore_ascii_case("servo") {
+        let angle: u8 = parse_argument(words.next())?;
+        if angle > 180 {
+            return Err(CommandError::BadArgument);
+        }
+        Command::Servo(angle)
+    } else if name.eq_ignore_ascii_case("ping") {
+        Command::Ping
+    } else if name.eq_ignore_ascii_case("line") {
+        Command::Line
+    } else if name.eq_ignore_ascii_case("stop") {
+        Command::Stop
+    } else if name.eq_ignore_ascii_case("mode") {
+        let mode_name = words.next().ok_or(CommandError::MissingArgument)?;
+        Command::Mode(RobotMode::from_name(mode_name).ok_or(CommandError::BadArgument)?)
+    } else if name.eq_ignore_ascii_case("telemetry") {
+        Command::Telemetry(parse_argument(words.next())?)
+    } else if name.eq_ignore_ascii_case("channel") {
+        let channel_name = words.next().ok_or(CommandError::MissingArgument)?;
+        let channels = Channels::from_name(channel_name).ok_or(CommandError::BadArgument)?;
+        let state = words.next().ok_or(CommandError::MissingArgument)?;
+        let enabled = if state.eq_ignore_ascii_case("on") {
+            true
+        } else if state.eq_ignore_ascii_case("off") {
+            false
+        } else {
+            return Err(CommandError::BadArgument);
+        };
+        Command::Channel(channels, enabled)
+    } else if name.eq_ignore_ascii_case("pid") {
+        let kp = parse_argument(words.next())?;
+        let ki = parse_argument(words.next())?;
+        let kd = parse_argument(words.next())?;
+        Command::Pid(kp, ki, kd)
+    } else if name.eq_ignore_ascii_case("line-color") {
+        let color_name = words.next().ok_or(CommandError::MissingArgument)?;
+        Command::LineColor(LineColor::from_name(color_name).ok_or(CommandError::BadArgument)?)
+    } else if name.eq_ignore_ascii_case("route") {
+        let mut route = Route::new();
+        for move_name in &mut words {
+            let grid_move = GridMove::from_name(move_name).ok_or(CommandError::BadArgument)?;
+            if !route.push(grid_move) {
+                return Err(CommandError::TooManyArguments);
+            }
+        }
+        if route.moves().is_empty() {
+            return Err(CommandError::MissingArgument);
+        }
+        Command::Route(route)
+    } else {
+        return Err(CommandError::UnknownCommand);
+    };
+
+    if words.next().is_some() {
+        return Err(CommandError::TooManyArguments);
+    }
+    Ok(command)
+}
+
+/// Parse a numeric argument, if there is one.
+fn parse_argument<T: core::str::FromStr>(word: Option<&str>) -> Result<T, CommandError> {
+    word.ok_or(CommandError::MissingArgument)?
+        .parse()
+        .map_err(|_| CommandError::BadArgument)
+}
+
+/// Collects the bytes received over the serial port into lines.
+pub struct LineBuffer {
+    bytes: [u8; MAX_LINE_LENGTH],
+    len: usize,
+    /// Whether the current line did not fit, so the rest of it is being thrown away.
+    overflowed: bool,
+}
+
+impl LineBuffer {
+    pub fn new() -> Self {
+        Self {
+            bytes: [0; MAX_LINE_LENGTH],
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Add a received byte, and if it ends a line, parse that line.
+    ///
+    /// Lines can end with `\n` or `\r\n`; empty lines are ignored, so that `\r\n` does not count twice.
+    pub fn push(&mut self, byte: u8) -> Option<Result<Command, CommandError>> {
+        match byte {
+            b'\r' => None,
+            b'\n' => {
+                let result = if self.overflowed {
+                    Some(Err(CommandError::LineTooLong))
+                } else if self.len == 0 {
+                    None
+                } else {
+                    Some(match core::str::from_utf8(&self.bytes[..self.len]) {
+                        Ok(line) => parse_command(line),
+                        Err(_) => Err(CommandError::NotText),
+                    })
+                };
+                self.len = 0;
+                self.overflowed = false;
+                result
+            },
+            _ => {
+                if self.len == MAX_LINE_LENGTH {
+                    self.overflowed = true;
+                } else {
+                    self.bytes[self.len] = byte;
+                    self.len += 1;
+                }
+                None
+            },
+        }
+    }
+}
+
+impl Default for LineBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes formatted text into a fixed buffer, since there is no `String` without `std`.
+    struct Cursor<'a> {
+        buffer: &'a mut [u8],
+        len: usize,
+    }
+
+    impl fmt::Write for Cursor<'_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.len + s.len();
+            self.buffer.get_mut(self.len..end).ok_or(fmt::Error)?.copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+
+    /// A route with the same move, again and again.
+    fn repeated(grid_move: GridMove, count: usize) -> Route {
+        let mut route = Route::new();
+        for _ in 0..count {
+            route.push(grid_move);
+        }
+        route
+    }
+
+    /// Feed a whole string into a new buffer, and collect the results.
+    fn feed(input: &str) -> [Option<Result<Command, CommandError>>; 4] {
+        let mut buffer = LineBuffer::new();
+        let mut results = [None; 4];
+        let mut count = 0;
+        for byte in input.bytes() {
+            if let Some(result) = buffer.push(byte) {
+                results[count] = Some(result);
+                count += 1;
+            }
+        }
+        results
+    }
+
+    #[test]
+    fn parses_every_command() {
+        assert_eq!(parse_command("drive 100 -100"), Ok(Command::Drive(100, -100)));
+        assert_eq!(parse_command("servo 45"), Ok(Command::Servo(45)));
+        assert_eq!(parse_command("ping"), Ok(Command::Ping));
+        assert_eq!(parse_command("line"), Ok(Command::Line));
+        assert_eq!(parse_command("stop"), Ok(Command::Stop));
+        assert_eq!(parse_command("mode follow-line"), Ok(Command::Mode(RobotMode::FollowLine)));
+        assert_eq!(parse_command("mode avoid"), Ok(Command::Mode(RobotMode::Avoid)));
+        assert_eq!(parse_command("mode idle"), Ok(Command::Mode(RobotMode::Idle)));
+        assert_eq!(parse_command("telemetry 100"), Ok(Command::Telemetry(100)));
+        assert_eq!(parse_command("channel line off"), Ok(Command::Channel(Channels::LINE, false)));
+        assert_eq!(parse_command("channel all on"), Ok(Command::Channel(Channels::ALL, true)));
+        assert_eq!(parse_command("pid 100 20 10"), Ok(Command::Pid(100, 20, 10)));
+        assert_eq!(parse_command("line-color light"), Ok(Command::LineColor(LineColor::Light)));
+        assert_eq!(parse_command("mode grid"), Ok(Command::Mode(RobotMode::Grid)));
+
+        let mut route = Route::new();
+        route.push(GridMove::Forward(3));
+        route.push(GridMove::TurnLeft);
+        route.push(GridMove::Forward(12));
+        route.push(GridMove::TurnRight);
+        assert_eq!(parse_command("route f3 l F12 R"), Ok(Command::Route(route)));
+    }
+
+    #[test]
+    fn ignores_case_and_extra_spaces() {
+        assert_eq!(parse_command("  DRIVE   5\t-5 "), Ok(Command::Drive(5, -5)));
+        assert_eq!(parse_command("Mode Avoid"), Ok(Command::Mode(RobotMode::Avoid)));
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert_eq!(parse_command(""), Err(CommandError::Empty));
+        assert_eq!(parse_command("fly"), Err(CommandError::UnknownCommand));
+        assert_eq!(parse_command("drive 100"), Err(CommandError::MissingArgument));
+        assert_eq!(parse_command("drive 100 fast"), Err(CommandError::BadArgument));
+        assert_eq!(parse_command("drive 300 0"), Err(CommandError::BadArgument));
+        assert_eq!(parse_command("servo 181"), Err(CommandError::BadArgument));
+        assert_eq!(parse_command("servo -1"), Err(CommandError::BadArgument));
+        assert_eq!(parse_command("mode dance"), Err(CommandError::BadArgument));
+        assert_eq!(parse_command("ping 1"), Err(CommandError::TooManyArguments));
+        assert_eq!(parse_command("telemetry 70000"), Err(CommandError::BadArgument));
+        assert_eq!(parse_command("channel line"), Err(CommandError::MissingArgument));
+        assert_eq!(parse_command("channel line maybe"), Err(CommandError::BadArgument));
+        assert_eq!(parse_command("channel speed on"), Err(CommandError::BadArgument));
+        assert_eq!(parse_command("pid 100 20"), Err(CommandError::MissingArgument));
+        assert_eq!(parse_command("pid 100 -20 10"), Err(CommandError::BadArgument));
+        assert_eq!(parse_command("line-color red"), Err(CommandError::BadArgument));
+        assert_eq!(parse_command("route"), Err(CommandError::MissingArgument));
+        assert_eq!(parse_command("route f3 u"), Err(CommandError::BadArgument));
+        assert_eq!(parse_command("route f0"), Err(CommandError::BadArgument));
+        assert_eq!(parse_command("route f"), Err(CommandError::BadArgument));
+        assert_eq!(parse_command("route l l l l l l l l l l l l l"), Ok(Command::Route(repeated(GridMove::TurnLeft, 13))));
+        assert_eq!(parse_command("route f1 f1 f1 f1 f1 f1 f1 f1"), Ok(Command::Route(repeated(GridMove::Forward(1), 8))));
+        // Longer routes do not fit into a line, but they are rejected even without the limit.
+        assert_eq!(parse_command("route l l l l l l l l l l l l l l l l l"), Err(CommandError::TooManyArguments));
+    }
+
+    #[test]
+    fn commands_are_written_as_typed() {
+        let commands = [
+            Command::Drive(-255, 17),
+            Command::Servo(180),
+            Command::Ping,
+            Command::Line,
+            Command::Stop,
+            Command::Mode(RobotMode::FollowLine),
+            Command::Telemetry(250),
+            Command::Channel(Channels::DISTANCE, true),
+            Command::Channel(Channels::ALL, false),
+            Command::Pid(1500, 0, 65535),
+            Command::LineColor(LineColor::Dark),
+            Command::Route(repeated(GridMove::TurnRight, 2)),
+        ];
+        for &command in &commands {
+            let mut buffer = [0u8; MAX_LINE_LENGTH];
+            let mut cursor = Cursor { buffer: &mut buffer, len: 0 };
+            fmt::write(&mut cursor, format_args!("{}", command)).unwrap();
+            let len = cursor.len;
+            assert_eq!(parse_command(core::str::from_utf8(&buffer[..len]).unwrap()), Ok(command));
+        }
+    }
+
+    #[test]
+    fn parses_replies() {
+        assert_eq!(parse_reply("OK"), Some(Reply::Ok("")));
+        assert_eq!(parse_reply("OK 123\r"), Some(Reply::Ok("123")));
+        assert_eq!(parse_reply("OK Center"), Some(Reply::Ok("Center")));
+        assert_eq!(parse_reply("ERR 4"), Some(Reply::Err(CommandError::BadArgument)));
+        assert_eq!(parse_reply("ERR 99"), None);
+        assert_eq!(parse_reply("Running!"), None);
+    }
+
+    #[test]
+    fn error_codes_are_distinct() {
+        let errors = [
+            CommandError::Empty,
+            CommandError::UnknownCommand,
+            CommandError::MissingArgument,
+            CommandError::BadArgument,
+            CommandError::TooManyArguments,
+            CommandError::LineTooLong,
+            CommandError::NotText,
+        ];
+        for (i, a) in errors.iter().enumerate() {
+            assert_eq!(CommandError::from_code(a.code()), Some(*a));
+            for b in &errors[i + 1..] {
+                assert_ne!(a.code(), b.code());
+            }
+        }
+    }
+
+    #[test]
+    fn buffer_splits_lines() {
+        let results = feed("ping\r\nstop\n\nservo 90\n");
+        assert_eq!(results[0], Some(Ok(Command::Ping)));
+        assert_eq!(results[1], Some(Ok(Command::Stop)));
+        assert_eq!(results[2], Some(Ok(Command::Servo(90))));
+        assert_eq!(results[3], None);
+    }
+
+    #[test]
+    fn buffer_rejects_long_lines_and_recovers() {
+        let results = feed("drive 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1\nping\n");
+        assert_eq!(results[0], Some(Err(CommandError::LineTooLong)));
+        assert_eq!(results[1], Some(Ok(Command::Ping)));
+    }
+
+    #[test]
+    fn buffer_rejects_binary() {
+        let mut buffer = LineBuffer::new();
+        assert_eq!(buffer.push(0xff), None);
+        assert_eq!(buffer.push(b'\n'), Some(Err(CommandError::NotText)));
+    }
+}
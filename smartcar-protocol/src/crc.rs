@@ -0,0 +1,35 @@
+//! The CRC-16 that protects every frame.
+//!
+//! This is CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xFFFF, no reflection and no final XOR.
+//! It is computed bit by bit, because a lookup table would take 512 bytes of the AVR's flash.
+
+const POLYNOMIAL: u16 = 0x1021;
+const INITIAL: u16 = 0xFFFF;
+
+/// Compute the CRC of some bytes.
+pub fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = INITIAL;
+    for &byte in bytes {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ POLYNOMIAL } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_check_value() {
+        // The standard check value of CRC-16/CCITT-FALSE.
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn empty_input_gives_initial_value() {
+        assert_eq!(crc16(&[]), 0xFFFF);
+    }
+}
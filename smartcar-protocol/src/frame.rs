@@ -0,0 +1,216 @@
+//! Framing of messages on the serial port.
+//!
+//! A frame holds one [Message], with a sequence number and a CRC:
+//!
+//! ```text
+//! sequence (1) | message ID (1) | payload (0..18) | CRC-16 of everything before it (2, little-endian)
+//! ```
+//!
+//! This is COBS-encoded, so it has no zero bytes, and a zero byte is sent both before and after it.
+//! The zero before the frame flushes anything else that the receiver may have collected,
+//! such as text from the console or the tail of a corrupted frame, so the frame itself is always received whole.
+
+use crate::cobs;
+use crate::crc::crc16;
+use crate::message::{Message, MAX_PAYLOAD_LENGTH};
+use crate::ProtocolError;
+
+/// The byte that separates frames.
+pub const DELIMITER: u8 = 0;
+
+/// The largest frame before COBS encoding.
+pub const MAX_RAW_FRAME_LENGTH: usize = 1 + 1 + MAX_PAYLOAD_LENGTH + 2;
+
+/// The largest COBS-encoded frame, not counting the delimiters.
+pub const MAX_ENCODED_FRAME_LENGTH: usize = cobs::max_encoded_len(MAX_RAW_FRAME_LENGTH);
+
+/// The largest frame on the wire, with both delimiters.
+pub const MAX_WIRE_FRAME_LENGTH: usize = MAX_ENCODED_FRAME_LENGTH + 2;
+
+/// A message with its sequence number.
+///
+/// The sequence number lets the receiver notice lost frames, and match a reply to its request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    pub sequence: u8,
+    pub message: Message,
+}
+
+impl Frame {
+    pub fn new(sequence: u8, message: Message) -> Self {
+        Self { sequence, message }
+    }
+
+    /// Write the frame as it goes on the wire, with both delimiters, and return its length.
+    pub fn write(&self, out: &mut [u8]) -> Result<usize, ProtocolError> {
+        let mut raw = [0u8; MAX_RAW_FRAME_LENGTH];
+        raw[0] = self.sequence;
+        let len = 1 + self.message.write(&mut raw[1..])?;
+        let crc = crc16(&raw[..len]);
+        raw[len..len + 2].copy_from_slice(&crc.to_le_bytes());
+        let raw = &raw[..len + 2];
+
+        if out.len() < cobs::max_encoded_len(raw.len()) + 2 {
+            return Err(ProtocolError::BufferTooSmall);
+        }
+        out[0] = DELIMITER;
+        let encoded_len = cobs::encode(raw, &mut out[1..])?;
+        out[1 + encoded_len] = DELIMITER;
+        Ok(encoded_len + 2)
+    }
+
+    /// Read a frame from its COBS-encoded bytes, without the delimiters.
+    pub fn read(encoded: &[u8]) -> Result<Self, ProtocolError> {
+        let mut raw = [0u8; MAX_RAW_FRAME_LENGTH];
+        let len = cobs::decode(encoded, &mut raw).map_err(|error| match error {
+            ProtocolError::BufferTooSmall => ProtocolError::FrameTooLong,
+            error => error,
+        })?;
+        if len < 4 {
+            return Err(ProtocolError::BadLength);
+        }
+
+        let (body, crc) = raw[..len].split_at(len - 2);
+        if crc16(body) != u16::from_le_bytes([crc[0], crc[1]]) {
+            return Err(ProtocolError::BadCrc);
+        }
+
+        Ok(Self {
+            sequence: body[0],
+            message: Message::read(&body[1..])?,
+        })
+    }
+}
+
+/// Collects received bytes into frames.
+pub struct FrameDecoder {
+    bytes: [u8; MAX_ENCODED_FRAME_LENGTH],
+    len: usize,
+    /// Whether the current frame did not fit, so the rest of it is being thrown away.
+    overflowed: bool,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self {
+            bytes: [0; MAX_ENCODED_FRAME_LENGTH],
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Whether some bytes of a frame have been received, but not its final delimiter.
+    pub fn in_frame(&self) -> bool {
+        self.len > 0 || self.overflowed
+    }
+
+    /// Add a received byte, and if it ends a frame, read that frame.
+    ///
+    /// Empty frames, such as between the two delimiters of back-to-back frames, are ignored.
+    pub fn push(&mut self, byte: u8) -> Option<Result<Frame, ProtocolError>> {
+        if byte == DELIMITER {
+            let result = if self.overflowed {
+                Some(Err(ProtocolError::FrameTooLong))
+            } else if self.len == 0 {
+                None
+            } else {
+                Some(Frame::read(&self.bytes[..self.len]))
+            };
+            self.len = 0;
+            self.overflowed = false;
+            return result;
+        }
+
+        if self.len == MAX_ENCODED_FRAME_LENGTH {
+            self.overflowed = true;
+        } else {
+            self.bytes[self.len] = byte;
+            self.len += 1;
+        }
+        None
+    }
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{DistanceReading, MessageId};
+
+    fn wire(frame: Frame) -> ([u8; MAX_WIRE_FRAME_LENGTH], usize) {
+        let mut out = [0u8; MAX_WIRE_FRAME_LENGTH];
+        let len = frame.write(&mut out).unwrap();
+        (out, len)
+    }
+
+    fn decode_all(decoder: &mut FrameDecoder, bytes: &[u8]) -> Option<Result<Frame, ProtocolError>> {
+        let mut last = None;
+        for &byte in bytes {
+            if let Some(result) = decoder.push(byte) {
+                last = Some(result);
+            }
+        }
+        last
+    }
+
+    #[test]
+    fn frames_round_trip_through_the_decoder() {
+        let frames = [
+            Frame::new(0, Message::Motor { left: 0, right: 0 }),
+            Frame::new(1, Message::Servo { angle: 0 }),
+            Frame::new(255, Message::Distance { at_ms: 1000, reading: DistanceReading::Measured(256) }),
+            Frame::new(7, Message::Line { at_ms: 0, left: false, center: true, right: false }),
+            Frame::new(8, Message::Request { id: MessageId::Distance }),
+        ];
+
+        let mut decoder = FrameDecoder::new();
+        for &frame in &frames {
+            let (bytes, len) = wire(frame);
+            assert_eq!(bytes[0], DELIMITER);
+            assert_eq!(bytes[len - 1], DELIMITER);
+            assert!(!bytes[1..len - 1].contains(&DELIMITER));
+            assert_eq!(decode_all(&mut decoder, &bytes[..len]), Some(Ok(frame)));
+        }
+    }
+
+    #[test]
+    fn leading_delimiter_flushes_garbage() {
+        let mut decoder = FrameDecoder::new();
+        let frame = Frame::new(3, Message::Servo { angle: 90 });
+        let (bytes, len) = wire(frame);
+
+        // Some console text arrives first, and is rejected when the frame starts.
+        assert_eq!(decode_all(&mut decoder, b"OK\r\n"), None);
+        assert!(decoder.in_frame());
+        assert_eq!(decoder.push(bytes[0]), Some(Err(ProtocolError::BadEncoding)));
+        assert_eq!(decode_all(&mut decoder, &bytes[1..len]), Some(Ok(frame)));
+    }
+
+    #[test]
+    fn corrupted_frame_fails_crc() {
+        let (mut bytes, len) = wire(Frame::new(9, Message::Motor { left: 100, right: -100 }));
+        bytes[3] ^= 0x40;
+        assert_eq!(Frame::read(&bytes[1..len - 1]), Err(ProtocolError::BadCrc));
+    }
+
+    #[test]
+    fn overlong_frame_is_rejected_and_decoder_recovers() {
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(decode_all(&mut decoder, &[0x11; 40]), None);
+        assert_eq!(decoder.push(DELIMITER), Some(Err(ProtocolError::FrameTooLong)));
+
+        let frame = Frame::new(1, Message::Servo { angle: 45 });
+        let (bytes, len) = wire(frame);
+        assert_eq!(decode_all(&mut decoder, &bytes[..len]), Some(Ok(frame)));
+    }
+
+    #[test]
+    fn short_frame_is_rejected() {
+        assert_eq!(Frame::read(&[0x03, 0x01, 0x01]), Err(ProtocolError::BadLength));
+    }
+}
@@ -0,0 +1,42 @@
+//! The protocols spoken over the serial port between the smart car and a host computer.
+//!
+//! There are two of them on the same port. The text [command]s are easy to type into a terminal,
+//! but too slow for streaming sensor data at 57600 baud. For that, the binary protocol sends compact [Message]s,
+//! each in a [Frame] with a sequence number and a CRC-16, COBS-encoded so that frames can be told apart
+//! and the receiver can recover from lost bytes.
+//!
+//! The same definitions are used by the firmware and by host tools, so this crate is `no_std`
+//! and does not depend on anything; everything is written into caller-provided buffers.
+
+#![no_std]
+
+pub mod cobs;
+pub mod command;
+pub mod crc;
+pub mod frame;
+pub mod message;
+pub mod route;
+pub mod telemetry;
+
+pub use frame::{Frame, FrameDecoder};
+pub use message::{DistanceReading, Message, MessageId};
+pub use telemetry::{Channels, TelemetryRecord};
+
+/// The ways that encoding or decoding can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolError {
+    /// The output buffer is too small.
+    BufferTooSmall,
+    /// The bytes are not valid COBS.
+    BadEncoding,
+    /// The frame is longer than any valid frame.
+    FrameTooLong,
+    /// The CRC does not match, so the frame was corrupted.
+    BadCrc,
+    /// The frame or the payload has the wrong length for its message.
+    BadLength,
+    /// The payload has a value that does not mean anything.
+    BadPayload,
+    /// The message ID is not known.
+    UnknownMessage(u8),
+}
@@ -0,0 +1,280 @@
+//! The messages that can be sent in a frame.
+//!
+//! Every message starts with a one-byte [MessageId], followed by a fixed-size payload.
+//! Multi-byte numbers are little-endian, like on the AVR.
+
+use crate::telemetry::{Channels, TelemetryRecord};
+use crate::ProtocolError;
+
+/// The largest payload of any message.
+pub const MAX_PAYLOAD_LENGTH: usize = 18;
+
+/// The type of a message, as sent in its first byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageId {
+    Motor = 0x01,
+    Servo = 0x02,
+    Distance = 0x03,
+    Line = 0x04,
+    Telemetry = 0x05,
+    Request = 0x10,
+    TelemetryConfig = 0x11,
+}
+
+impl MessageId {
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0x01 => Some(MessageId::Motor),
+            0x02 => Some(MessageId::Servo),
+            0x03 => Some(MessageId::Distance),
+            0x04 => Some(MessageId::Line),
+            0x05 => Some(MessageId::Telemetry),
+            0x10 => Some(MessageId::Request),
+            0x11 => Some(MessageId::TelemetryConfig),
+            _ => None,
+        }
+    }
+
+    /// The length of the payload of this type of message.
+    pub fn payload_len(&self) -> usize {
+        match self {
+            MessageId::Motor => 4,
+            MessageId::Servo => 1,
+            MessageId::Distance => 7,
+            MessageId::Line => 5,
+            MessageId::Telemetry => 18,
+            MessageId::Request => 1,
+            MessageId::TelemetryConfig => 3,
+        }
+    }
+}
+
+/// The result of a single ping of the distance sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistanceReading {
+    /// An obstacle at the given distance, in mm.
+    Measured(u16),
+    /// Nothing in range.
+    Infinity,
+    /// The sensor did not answer.
+    Unknown,
+}
+
+/// A message, with its payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    /// Host to car: drive the wheels with signed commands from -255 to 255.
+    Motor { left: i16, right: i16 },
+    /// Host to car: turn the servo to the given angle, in degrees.
+    Servo { angle: u8 },
+    /// Car to host: a distance sample, taken at the given time.
+    Distance { at_ms: u32, reading: DistanceReading },
+    /// Car to host: the line tracker reading, taken at the given time.
+    /// Each sensor is `true` when it sees a dark surface.
+    Line { at_ms: u32, left: bool, center: bool, right: bool },
+    /// Car to host: a telemetry record.
+    Telemetry(TelemetryRecord),
+    /// Host to car: ask for a sample of the given type, which is sent back with the same sequence number.
+    Request { id: MessageId },
+    /// Host to car: send binary telemetry records with the given channels every `period_ms`, or stop if it is 0.
+    TelemetryConfig { period_ms: u16, channels: Channels },
+}
+
+impl Message {
+    pub fn id(&self) -> MessageId {
+        match self {
+            Message::Motor { .. } => MessageId::Motor,
+            Message::Servo { .. } => MessageId::Servo,
+            Message::Distance { .. } => MessageId::Distance,
+            Message::Line { .. } => MessageId::Line,
+            Message::Telemetry(_) => MessageId::Telemetry,
+            Message::Request { .. } => MessageId::Request,
+            Message::TelemetryConfig { .. } => MessageId::TelemetryConfig,
+        }
+    }
+
+    /// Write the ID and the payload into `out`, and return the length written.
+    pub fn write(&self, out: &mut [u8]) -> Result<usize, ProtocolError> {
+        let id = self.id();
+        let len = 1 + id.payload_len();
+        if out.len() < len {
+            return Err(ProtocolError::BufferTooSmall);
+        }
+
+        out[0] = id as u8;
+        let payload = &mut out[1..len];
+        match *self {
+            Message::Motor { left, right } => {
+                payload[0..2].copy_from_slice(&left.to_le_bytes());
+                payload[2..4].copy_from_slice(&right.to_le_bytes());
+            },
+            Message::Servo { angle } => payload[0] = angle,
+            Message::Distance { at_ms, reading } => {
+                payload[0..4].copy_from_slice(&at_ms.to_le_bytes());
+                write_distance(reading, &mut payload[4..7]);
+            },
+            Message::Line { at_ms, left, center, right } => {
+                payload[0..4].copy_from_slice(&at_ms.to_le_bytes());
+                payload[4] = line_bits(left, center, right);
+            },
+            Message::Telemetry(record) => {
+                payload[0..4].copy_from_slice(&record.at_ms.to_le_bytes());
+                payload[4] = record.channels.bits();
+                payload[5..7].copy_from_slice(&record.motor.0.to_le_bytes());
+                payload[7..9].copy_from_slice(&record.motor.1.to_le_bytes());
+                payload[9] = record.servo_angle;
+                write_distance(record.distance, &mut payload[10..13]);
+                payload[13] = line_bits(record.line.0, record.line.1, record.line.2);
+                payload[14..16].copy_from_slice(&record.loop_count.to_le_bytes());
+                payload[16..18].copy_from_slice(&record.max_loop_ms.to_le_bytes());
+            },
+            Message::Request { id } => payload[0] = id as u8,
+            Message::TelemetryConfig { period_ms, channels } => {
+                payload[0..2].copy_from_slice(&period_ms.to_le_bytes());
+                payload[2] = channels.bits();
+            },
+        }
+
+        Ok(len)
+    }
+
+    /// Read a message from its ID and payload.
+    pub fn read(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let (&id_byte, payload) = bytes.split_first().ok_or(ProtocolError::BadLength)?;
+        let id = MessageId::from_byte(id_byte).ok_or(ProtocolError::UnknownMessage(id_byte))?;
+        if payload.len() != id.payload_len() {
+            return Err(ProtocolError::BadLength);
+        }
+
+        let u16_at = |i: usize| u16::from_le_bytes([payload[i], payload[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes([payload[i], payload[i + 1], payload[i + 2], payload[i + 3]]);
+
+        Ok(match id {
+            MessageId::Motor => Message::Motor {
+                left: u16_at(0) as i16,
+                right: u16_at(2) as i16,
+            },
+            MessageId::Servo => Message::Servo { angle: payload[0] },
+            MessageId::Distance => Message::Distance {
+                at_ms: u32_at(0),
+                reading: read_distance(&payload[4..7])?,
+            },
+            MessageId::Line => {
+                let (left, center, right) = read_line_bits(payload[4]);
+                Message::Line { at_ms: u32_at(0), left, center, right }
+            },
+            MessageId::Telemetry => Message::Telemetry(TelemetryRecord {
+                at_ms: u32_at(0),
+                channels: Channels::from_bits(payload[4]),
+                motor: (u16_at(5) as i16, u16_at(7) as i16),
+                servo_angle: payload[9],
+                distance: read_distance(&payload[10..13])?,
+                line: read_line_bits(payload[13]),
+                loop_count: u16_at(14),
+                max_loop_ms: u16_at(16),
+            }),
+            MessageId::Request => Message::Request {
+                id: MessageId::from_byte(payload[0]).ok_or(ProtocolError::BadPayload)?,
+            },
+            MessageId::TelemetryConfig => Message::TelemetryConfig {
+                period_ms: u16_at(0),
+                channels: Channels::from_bits(payload[2]),
+            },
+        })
+    }
+}
+
+/// Write a distance reading as a kind byte and a little-endian distance in mm.
+fn write_distance(reading: DistanceReading, out: &mut [u8]) {
+    let (kind, mm) = match reading {
+        DistanceReading::Measured(mm) => (0, mm),
+        DistanceReading::Infinity => (1, 0),
+        DistanceReading::Unknown => (2, 0),
+    };
+    out[0] = kind;
+    out[1..3].copy_from_slice(&mm.to_le_bytes());
+}
+
+fn read_distance(bytes: &[u8]) -> Result<DistanceReading, ProtocolError> {
+    match bytes[0] {
+        0 => Ok(DistanceReading::Measured(u16::from_le_bytes([bytes[1], bytes[2]]))),
+        1 => Ok(DistanceReading::Infinity),
+        2 => Ok(DistanceReading::Unknown),
+        _ => Err(ProtocolError::BadPayload),
+    }
+}
+
+/// Pack the three line trackers into the low bits of a byte: left, center, right.
+fn line_bits(left: bool, center: bool, right: bool) -> u8 {
+    left as u8 | (center as u8) << 1 | (right as u8) << 2
+}
+
+fn read_line_bits(bits: u8) -> (bool, bool, bool) {
+    (bits & 1 != 0, bits & 2 != 0, bits & 4 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(message: Message) {
+        let mut buffer = [0u8; 1 + MAX_PAYLOAD_LENGTH];
+        let len = message.write(&mut buffer).unwrap();
+        assert_eq!(len, 1 + message.id().payload_len());
+        assert_eq!(Message::read(&buffer[..len]), Ok(message));
+    }
+
+    #[test]
+    fn every_message_round_trips() {
+        round_trip(Message::Motor { left: -255, right: 200 });
+        round_trip(Message::Servo { angle: 180 });
+        round_trip(Message::Distance { at_ms: 123_456, reading: DistanceReading::Measured(1500) });
+        round_trip(Message::Distance { at_ms: 0, reading: DistanceReading::Infinity });
+        round_trip(Message::Distance { at_ms: u32::MAX, reading: DistanceReading::Unknown });
+        round_trip(Message::Line { at_ms: 42, left: true, center: false, right: true });
+        round_trip(Message::Request { id: MessageId::Line });
+        round_trip(Message::TelemetryConfig { period_ms: 100, channels: Channels::MOTOR.with(Channels::LINE, true) });
+        round_trip(Message::Telemetry(TelemetryRecord {
+            at_ms: 99_999,
+            channels: Channels::ALL,
+            motor: (-255, 128),
+            servo_angle: 45,
+            distance: DistanceReading::Measured(1234),
+            line: (false, true, true),
+            loop_count: 500,
+            max_loop_ms: 12,
+        }));
+    }
+
+    #[test]
+    fn payloads_fit_the_limit() {
+        let ids = [
+            MessageId::Motor,
+            MessageId::Servo,
+            MessageId::Distance,
+            MessageId::Line,
+            MessageId::Telemetry,
+            MessageId::Request,
+            MessageId::TelemetryConfig,
+        ];
+        for &id in &ids {
+            assert!(id.payload_len() <= MAX_PAYLOAD_LENGTH);
+            assert_eq!(MessageId::from_byte(id as u8), Some(id));
+        }
+    }
+
+    #[test]
+    fn motor_layout_is_little_endian() {
+        let mut buffer = [0u8; 5];
+        Message::Motor { left: -1, right: 0x0102 }.write(&mut buffer).unwrap();
+        assert_eq!(buffer, [0x01, 0xFF, 0xFF, 0x02, 0x01]);
+    }
+
+    #[test]
+    fn rejects_bad_messages() {
+        assert_eq!(Message::read(&[]), Err(ProtocolError::BadLength));
+        assert_eq!(Message::read(&[0x7F]), Err(ProtocolError::UnknownMessage(0x7F)));
+        assert_eq!(Message::read(&[0x02, 90, 0]), Err(ProtocolError::BadLength));
+        assert_eq!(Message::read(&[0x10, 0x7F]), Err(ProtocolError::BadPayload));
+    }
+}
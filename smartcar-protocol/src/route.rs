@@ -0,0 +1,116 @@
+//! Routes on a grid of lines, which the car drives by counting crossings.
+//!
+//! A [Route] is sent with the `route` text command, as a list of [GridMove]s separated by spaces:
+//! `f<n>` follows the line forward over `n` crossings, and `l` and `r` turn left and right onto the next line.
+//! For example, `route f3 l f2` goes three crossings forward, turns left, and goes two more.
+
+use core::fmt;
+
+/// The most moves that a single route can have.
+pub const MAX_ROUTE_MOVES: usize = 16;
+
+/// A single move on the grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridMove {
+    /// Follow the line forward until the given number of crossings have been passed,
+    /// and stop with the wheels on the last one.
+    Forward(u8),
+    /// Turn left onto the next line.
+    TurnLeft,
+    /// Turn right onto the next line.
+    TurnRight,
+}
+
+impl GridMove {
+    /// Parse a move from its name in the `route` command.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("l") {
+            Some(GridMove::TurnLeft)
+        } else if name.eq_ignore_ascii_case("r") {
+            Some(GridMove::TurnRight)
+        } else if name.starts_with('f') || name.starts_with('F') {
+            match name[1..].parse() {
+                Ok(crossings) if crossings > 0 => Some(GridMove::Forward(crossings)),
+                _ => None,
+            }
+        } else {
+            None
+        }
+    }
+}
+
+/// Writes the move as it is typed into the `route` command.
+impl fmt::Display for GridMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridMove::Forward(crossings) => write!(f, "f{}", crossings),
+            GridMove::TurnLeft => f.write_str("l"),
+            GridMove::TurnRight => f.write_str("r"),
+        }
+    }
+}
+
+/// A list of moves on the grid.
+#[derive(Clone, Copy)]
+pub struct Route {
+    moves: [GridMove; MAX_ROUTE_MOVES],
+    len: usize,
+}
+
+impl Route {
+    /// Creates an empty route.
+    pub fn new() -> Self {
+        Self {
+            moves: [GridMove::Forward(0); MAX_ROUTE_MOVES],
+            len: 0,
+        }
+    }
+
+    /// Add a move to the end of the route. Returns `false` if the route is full.
+    pub fn push(&mut self, grid_move: GridMove) -> bool {
+        if self.len == MAX_ROUTE_MOVES {
+            return false;
+        }
+        self.moves[self.len] = grid_move;
+        self.len += 1;
+        true
+    }
+
+    pub fn moves(&self) -> &[GridMove] {
+        &self.moves[..self.len]
+    }
+}
+
+impl Default for Route {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Two routes are the same if they have the same moves, whatever is left in the unused part of the list.
+impl PartialEq for Route {
+    fn eq(&self, other: &Self) -> bool {
+        self.moves() == other.moves()
+    }
+}
+
+impl Eq for Route {}
+
+impl fmt::Debug for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.moves()).finish()
+    }
+}
+
+/// Writes the moves as they are typed into the `route` command, separated by spaces.
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, grid_move) in self.moves().iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}", grid_move)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+//! Telemetry records, which the car sends periodically while telemetry is turned on.
+//!
+//! A record holds a sample of every channel, taken at the same time.
+//! The host selects the [Channels] it wants; the others are left out of text records,
+//! and are zero in binary ones.
+
+use crate::message::DistanceReading;
+
+/// A set of telemetry channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Channels(u8);
+
+impl Channels {
+    /// The signed wheel commands.
+    pub const MOTOR: Channels = Channels(1 << 0);
+    /// The servo angle.
+    pub const SERVO: Channels = Channels(1 << 1);
+    /// The last distance measurement.
+    pub const DISTANCE: Channels = Channels(1 << 2);
+    /// The line tracker reading.
+    pub const LINE: Channels = Channels(1 << 3);
+    /// How often, and how slowly, the main loop ran.
+    pub const TIMING: Channels = Channels(1 << 4);
+
+    pub const NONE: Channels = Channels(0);
+    pub const ALL: Channels = Channels(0x1F);
+
+    /// The names of the channels in the text commands.
+    pub const NAMES: [(&'static str, Channels); 6] = [
+        ("motor", Self::MOTOR),
+        ("servo", Self::SERVO),
+        ("distance", Self::DISTANCE),
+        ("line", Self::LINE),
+        ("timing", Self::TIMING),
+        ("all", Self::ALL),
+    ];
+
+    /// The channels from their bits, as sent in binary messages. Unknown bits are dropped.
+    pub fn from_bits(bits: u8) -> Self {
+        Channels(bits & Self::ALL.0)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// A channel (or `all`) from its name in the text commands.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(channel_name, _)| name.eq_ignore_ascii_case(channel_name))
+            .map(|&(_, channels)| channels)
+    }
+
+    /// Whether all of the `other` channels are in this set.
+    pub fn contains(&self, other: Channels) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// This set with the `other` channels turned on or off.
+    pub fn with(self, other: Channels, enabled: bool) -> Self {
+        if enabled {
+            Channels(self.0 | other.0)
+        } else {
+            Channels(self.0 & !other.0)
+        }
+    }
+}
+
+/// A telemetry record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TelemetryRecord {
+    /// The time on the car when the record was taken, in ms.
+    pub at_ms: u32,
+    /// The channels that are filled in.
+    pub channels: Channels,
+    /// The signed wheel commands, from -255 to 255.
+    pub motor: (i16, i16),
+    /// The servo angle, in degrees.
+    pub servo_angle: u8,
+    /// The last distance measurement, which may be older than the record.
+    pub distance: DistanceReading,
+    /// Whether the left, center and right line trackers see a dark surface.
+    pub line: (bool, bool, bool),
+    /// The number of passes of the main loop since the previous record.
+    pub loop_count: u16,
+    /// The longest pass of the main loop since the previous record, in ms.
+    pub max_loop_ms: u16,
+}
+
+impl TelemetryRecord {
+    /// An empty record at the given time, with no channels filled in.
+    pub fn new(at_ms: u32) -> Self {
+        Self {
+            at_ms,
+            channels: Channels::NONE,
+            motor: (0, 0),
+            servo_angle: 0,
+            distance: DistanceReading::Unknown,
+            line: (false, false, false),
+            loop_count: 0,
+            max_loop_ms: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels_turn_on_and_off() {
+        let channels = Channels::NONE.with(Channels::MOTOR, true).with(Channels::LINE, true);
+        assert!(channels.contains(Channels::MOTOR));
+        assert!(channels.contains(Channels::LINE));
+        assert!(!channels.contains(Channels::SERVO));
+        assert!(!channels.contains(Channels::ALL));
+
+        let channels = channels.with(Channels::MOTOR, false);
+        assert_eq!(channels, Channels::LINE);
+        assert_eq!(Channels::ALL.with(Channels::ALL, false), Channels::NONE);
+    }
+
+    #[test]
+    fn channels_by_name() {
+        assert_eq!(Channels::from_name("distance"), Some(Channels::DISTANCE));
+        assert_eq!(Channels::from_name("ALL"), Some(Channels::ALL));
+        assert_eq!(Channels::from_name("speed"), None);
+        assert_eq!(Channels::from_bits(0xFF), Channels::ALL);
+    }
+}
@@ -0,0 +1,150 @@
+//! The physics of the car: how the wheels respond to the motor driver, and how the car moves on the floor.
+//!
+//! The car is a differential drive: the wheels on each side turn together, and the car turns
+//! by driving the sides at different speeds. The wheels do not slip, except against a wall.
+
+use smartcar::servo::ServoCalibration;
+
+use crate::geometry::{Point, Pose};
+use crate::world::World;
+
+/// How close the car's body has to be to a wall to touch it, in mm.
+const CONTACT_MM: f64 = 1.0;
+
+/// The dimensions and the performance of the car.
+///
+/// The [Default] is the V3 kit: its size, the positions of its sensors, and roughly how fast it goes.
+#[derive(Debug, Clone, Copy)]
+pub struct CarModel {
+    /// The distance between the left and the right wheels, in mm.
+    pub track_width: f64,
+    /// The speed of the wheels at a 100% duty cycle, in mm/s. Below that, the speed is proportional to the duty cycle.
+    pub max_wheel_speed: f64,
+    /// How quickly the wheels reach the speed they are driven at: the time constant of the motors, in ms.
+    pub drive_time_constant_ms: f64,
+    /// How quickly the wheels stop when the motors are short-braked, in ms.
+    pub brake_time_constant_ms: f64,
+    /// How quickly the wheels spin down when the motors are disabled, in ms.
+    pub coast_time_constant_ms: f64,
+    /// The radius of the circle around the pose that the car takes up, in mm. Walls cannot get inside of it.
+    pub body_radius: f64,
+    /// How far in front of the pose the line sensors are, in mm.
+    pub line_sensor_ahead: f64,
+    /// The distance between the center line sensor and each of the outer ones, in mm.
+    pub line_sensor_spacing: f64,
+    /// How far in front of the pose the servo with the distance sensor is, in mm.
+    pub mast_ahead: f64,
+    /// The pulse widths that the servo turns to every angle at.
+    pub servo_calibration: ServoCalibration,
+    /// How fast the servo turns, in degrees per second.
+    pub servo_speed: f64,
+    /// The angle between the middle and the edge of the distance sensor's beam, in degrees.
+    pub beam_half_angle: f64,
+    /// The distance beyond which the distance sensor does not hear echoes, in mm.
+    pub max_range: f64,
+}
+
+impl Default for CarModel {
+    fn default() -> Self {
+        Self {
+            track_width: 130.0,
+            max_wheel_speed: 500.0,
+            drive_time_constant_ms: 60.0,
+            brake_time_constant_ms: 15.0,
+            coast_time_constant_ms: 200.0,
+            body_radius: 120.0,
+            line_sensor_ahead: 90.0,
+            line_sensor_spacing: 15.0,
+            mast_ahead: 70.0,
+            servo_calibration: ServoCalibration::SG90,
+            servo_speed: 600.0,
+            beam_half_angle: 7.5,
+            max_range: 4000.0,
+        }
+    }
+}
+
+/// What the motor driver does with the motors on one side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MotorInput {
+    /// Drive the motors at a fraction of the full speed, from -1 (full speed backward) to 1 (full speed forward).
+    Drive(f64),
+    /// Short-brake the motors.
+    Brake,
+    /// Disconnect the motors, and let them spin down.
+    Coast,
+}
+
+/// The moving parts of the car.
+#[derive(Debug, Clone, Copy)]
+pub struct Body {
+    pub pose: Pose,
+    /// The speeds of the left and the right wheels, in mm/s.
+    pub wheel_speeds: (f64, f64),
+    /// The angle of the servo, in degrees.
+    pub mast_angle: f64,
+    /// Whether the car's body is touching a wall.
+    pub touching: bool,
+}
+
+impl Body {
+    /// A car standing still at the given pose, with the servo looking straight ahead.
+    pub fn new(pose: Pose) -> Self {
+        Self {
+            pose,
+            wheel_speeds: (0.0, 0.0),
+            mast_angle: 90.0,
+            touching: false,
+        }
+    }
+
+    /// Move the car along for a short time, with the motors driven as given and the servo sent to an angle.
+    ///
+    /// Returns the distance that the car moved, in mm.
+    pub fn step(&mut self, model: &CarModel, world: &World, motors: (MotorInput, MotorInput), servo_target: Option<f64>, dt_ms: f64) -> f64 {
+        self.wheel_speeds = (
+            wheel_response(model, self.wheel_speeds.0, motors.0, dt_ms),
+            wheel_response(model, self.wheel_speeds.1, motors.1, dt_ms),
+        );
+
+        if let Some(target) = servo_target {
+            let reach = model.servo_speed * dt_ms / 1000.0;
+            self.mast_angle += (target - self.mast_angle).clamp(-reach, reach);
+        }
+
+        let (left, right) = self.wheel_speeds;
+        let dt = dt_ms / 1000.0;
+        let speed = (left + right) / 2.0;
+        let turn_rate = (right - left) / model.track_width;
+
+        // Move along the heading halfway through the step, which follows a curve closely.
+        let heading = self.pose.heading + turn_rate * dt / 2.0;
+        let position = self.pose.position + Point::from_angle(heading) * (speed * dt);
+        self.pose.heading += turn_rate * dt;
+
+        // A wall stops the car from moving into it, but it can still turn, or back away from the wall.
+        let clearance = world.distance_to_wall(position);
+        let old_clearance = world.distance_to_wall(self.pose.position);
+        let moved = if clearance < model.body_radius && clearance < old_clearance {
+            0.0
+        } else {
+            let moved = position.distance(self.pose.position);
+            self.pose.position = position;
+            moved
+        };
+
+        self.touching = world.distance_to_wall(self.pose.position) < model.body_radius + CONTACT_MM;
+        moved
+    }
+}
+
+/// The speed of a wheel after a short time, as its motor pulls it toward the speed it is driven at.
+fn wheel_response(model: &CarModel, speed: f64, input: MotorInput, dt_ms: f64) -> f64 {
+    let (target, time_constant_ms) = match input {
+        MotorInput::Drive(fraction) => (fraction.clamp(-1.0, 1.0) * model.max_wheel_speed, model.drive_time_constant_ms),
+        MotorInput::Brake => (0.0, model.brake_time_constant_ms),
+        MotorInput::Coast => (0.0, model.coast_time_constant_ms),
+    };
+
+    speed + (target - speed) * (1.0 - (-dt_ms / time_constant_ms).exp())
+}
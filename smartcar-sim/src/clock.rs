@@ -0,0 +1,41 @@
+//! The simulated time, which [smartcar::clock::millis] reads.
+//!
+//! The time is kept in µs, because the echo timer counts in 4µs ticks.
+//! Every thread has its own time, so that the tests, which cargo runs on separate threads,
+//! can each simulate their own car.
+
+use std::cell::Cell;
+use std::sync::Once;
+
+use smartcar::clock;
+
+thread_local! {
+    /// The simulated time of the calling thread, in µs.
+    static NOW_US: Cell<u64> = const { Cell::new(0) };
+}
+
+fn thread_millis() -> u64 {
+    micros() / 1000
+}
+
+/// Make [clock::millis] read the simulated time of the calling thread.
+pub fn install() {
+    static INSTALL: Once = Once::new();
+    // SAFETY: the `Once` makes every thread wait until the source is installed, before any of them reads it.
+    INSTALL.call_once(|| unsafe { clock::set_source(thread_millis) });
+}
+
+/// The simulated time of the calling thread, in µs.
+pub fn micros() -> u64 {
+    NOW_US.with(Cell::get)
+}
+
+/// Move the simulated time of the calling thread forward.
+pub(crate) fn advance_micros(us: u64) {
+    NOW_US.with(|now| now.set(now.get() + us));
+}
+
+/// Start the simulated time of the calling thread over from 0.
+pub(crate) fn reset() {
+    NOW_US.with(|now| now.set(0));
+}
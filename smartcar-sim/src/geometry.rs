@@ -0,0 +1,130 @@
+//! Points, segments and rays on the floor, in mm.
+//!
+//! The floor is a plane with x to the east and y to the north,
+//! and angles are in radians, counterclockwise from the x axis.
+
+use std::ops::{Add, Mul, Sub};
+
+/// A point on the floor, or a vector between two points, in mm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub const fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    /// The unit vector pointing in the given direction.
+    pub fn from_angle(angle: f64) -> Self {
+        Self::new(angle.cos(), angle.sin())
+    }
+
+    pub fn dot(self, other: Point) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// The z component of the cross product, which is positive when `other` is counterclockwise from `self`.
+    pub fn cross(self, other: Point) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn distance(self, other: Point) -> f64 {
+        (other - self).length()
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+
+    fn add(self, other: Point) -> Point {
+        Point::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f64> for Point {
+    type Output = Point;
+
+    fn mul(self, factor: f64) -> Point {
+        Point::new(self.x * factor, self.y * factor)
+    }
+}
+
+/// A straight piece between two points, like a piece of a line or a wall.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    pub start: Point,
+    pub end: Point,
+}
+
+impl Segment {
+    pub const fn new(start: Point, end: Point) -> Self {
+        Self { start, end }
+    }
+
+    /// The distance from a point to the nearest point of the segment.
+    pub fn distance_to(&self, point: Point) -> f64 {
+        let along = self.end - self.start;
+        let length_squared = along.dot(along);
+        if length_squared == 0.0 {
+            return point.distance(self.start);
+        }
+
+        let t = ((point - self.start).dot(along) / length_squared).clamp(0.0, 1.0);
+        point.distance(self.start + along * t)
+    }
+
+    /// How far a ray from `origin` in the direction of the unit vector `direction` goes before it hits the segment.
+    pub fn ray_distance(&self, origin: Point, direction: Point) -> Option<f64> {
+        let along = self.end - self.start;
+        let denominator = direction.cross(along);
+        if denominator.abs() < f64::EPSILON {
+            // The ray is parallel to the segment, so it only grazes it.
+            return None;
+        }
+
+        let to_start = self.start - origin;
+        let distance = to_start.cross(along) / denominator;
+        let t = to_start.cross(direction) / denominator;
+        if distance >= 0.0 && (0.0..=1.0).contains(&t) {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+}
+
+/// The position of the car on the floor: the point between its wheels that it turns around, and the direction it faces.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    pub position: Point,
+    /// The direction that the front of the car faces, in radians.
+    pub heading: f64,
+}
+
+impl Pose {
+    pub const fn new(x: f64, y: f64, heading: f64) -> Self {
+        Self { position: Point::new(x, y), heading }
+    }
+
+    /// The point at the given offset from the car, `ahead` mm to the front and `left` mm to the left.
+    pub fn to_world(&self, ahead: f64, left: f64) -> Point {
+        let forward = Point::from_angle(self.heading);
+        let leftward = Point::new(-forward.y, forward.x);
+        self.position + forward * ahead + leftward * left
+    }
+}
@@ -0,0 +1,205 @@
+//! The simulated implementations of the hardware traits that the drivers are generic over.
+//!
+//! Like the firmware's, the outputs are plain pins and PWM outputs, which the [Sim](crate::Sim) reads
+//! to find out what the motors and the servo are told to do. The inputs look at the simulated world:
+//! the line sensors check whether they are over a line, and the echo timer times echoes off the walls.
+
+use std::cell::{Cell, RefCell};
+use std::convert::Infallible;
+use std::rc::Rc;
+
+use smartcar::hal::{EchoTimer, InputPin, OutputPin, PwmPin};
+use smartcar::hc_sr04_distance_sensor::TICK_US;
+use smartcar::line_tracker::LineTrackerDirection;
+
+use crate::clock;
+use crate::geometry::Point;
+use crate::Shared;
+
+/// How long after the trigger the HC-SR04 raises the Echo pin, while it sends its pulses, in µs.
+const ECHO_DELAY_US: u64 = 500;
+
+/// How long the HC-SR04 holds the Echo pin high when no echo comes back, in µs.
+const NO_ECHO_US: u64 = 130_000;
+
+/// The number of rays that are cast across the beam of the distance sensor.
+const BEAM_RAYS: usize = 5;
+
+/// A digital output pin.
+#[derive(Clone, Default)]
+pub struct SimPin {
+    high: Rc<Cell<bool>>,
+}
+
+impl SimPin {
+    pub fn is_set_high(&self) -> bool {
+        self.high.get()
+    }
+}
+
+impl OutputPin for SimPin {
+    type Error = Infallible;
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.high.set(true);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.high.set(false);
+        Ok(())
+    }
+}
+
+/// A PWM output, with its duty cycle in any unit up to a fixed maximum.
+#[derive(Clone)]
+pub struct SimPwm<D> {
+    enabled: Rc<Cell<bool>>,
+    duty: Rc<Cell<D>>,
+    max_duty: D,
+}
+
+impl<D: Copy + Default> SimPwm<D> {
+    /// A disabled output with a duty cycle of zero.
+    pub fn new(max_duty: D) -> Self {
+        Self {
+            enabled: Rc::new(Cell::new(false)),
+            duty: Rc::new(Cell::new(D::default())),
+            max_duty,
+        }
+    }
+
+    /// The duty cycle, or `None` while the output is disabled.
+    pub fn output(&self) -> Option<D> {
+        if self.enabled.get() {
+            Some(self.duty.get())
+        } else {
+            None
+        }
+    }
+}
+
+impl<D: Copy> PwmPin for SimPwm<D> {
+    type Duty = D;
+
+    fn disable(&mut self) {
+        self.enabled.set(false);
+    }
+
+    fn enable(&mut self) {
+        self.enabled.set(true);
+    }
+
+    fn get_duty(&self) -> D {
+        self.duty.get()
+    }
+
+    fn get_max_duty(&self) -> D {
+        self.max_duty
+    }
+
+    fn set_duty(&mut self, duty: D) {
+        self.duty.set(duty);
+    }
+}
+
+/// The output of one of the V3 kit's line sensors, which is low over a line.
+pub struct LineSensorPin {
+    shared: Rc<RefCell<Shared>>,
+    direction: LineTrackerDirection,
+}
+
+impl LineSensorPin {
+    pub(crate) fn new(shared: Rc<RefCell<Shared>>, direction: LineTrackerDirection) -> Self {
+        Self { shared, direction }
+    }
+}
+
+impl InputPin for LineSensorPin {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Infallible> {
+        self.is_low().map(|low| !low)
+    }
+
+    fn is_low(&self) -> Result<bool, Infallible> {
+        let shared = self.shared.borrow();
+        Ok(shared.world.is_dark(shared.line_sensor_position(self.direction)))
+    }
+}
+
+/// The timer that timestamps the echoes of the distance sensor, off the walls that its beam hits.
+///
+/// Reading the time takes one tick, like on the robot, where the driver spins on it while it pulses the Trig pin.
+pub struct SimEchoTimer {
+    shared: Rc<RefCell<Shared>>,
+    /// The times of the rising and the falling edge of the Echo pin after the last capture was started, in µs.
+    echo_us: Option<(u64, u64)>,
+}
+
+impl SimEchoTimer {
+    pub(crate) fn new(shared: Rc<RefCell<Shared>>) -> Self {
+        Self { shared, echo_us: None }
+    }
+}
+
+impl EchoTimer for SimEchoTimer {
+    fn now(&self) -> u16 {
+        let now = ticks(clock::micros());
+        clock::advance_micros(TICK_US);
+        now
+    }
+
+    fn start_capture(&mut self) {
+        let shared = self.shared.borrow();
+        let rise_us = clock::micros() + ECHO_DELAY_US;
+        let high_us = match shared.echo_distance() {
+            // The sound goes there and back.
+            Some(distance) => (2.0 * distance * 1_000_000.0 / shared.world.sound_speed.mm_per_sec() as f64) as u64,
+            None => NO_ECHO_US,
+        };
+        self.echo_us = Some((rise_us, rise_us + high_us));
+    }
+
+    fn edges(&self) -> (Option<u16>, Option<u16>) {
+        let now = clock::micros();
+        match self.echo_us {
+            Some((rise_us, fall_us)) if now >= fall_us => (Some(ticks(rise_us)), Some(ticks(fall_us))),
+            Some((rise_us, _)) if now >= rise_us => (Some(ticks(rise_us)), None),
+            _ => (None, None),
+        }
+    }
+}
+
+impl Shared {
+    /// Where a line sensor is on the floor.
+    pub(crate) fn line_sensor_position(&self, direction: LineTrackerDirection) -> Point {
+        let left = match direction {
+            LineTrackerDirection::Left => self.model.line_sensor_spacing,
+            LineTrackerDirection::Center => 0.0,
+            LineTrackerDirection::Right => -self.model.line_sensor_spacing,
+        };
+        self.body.pose.to_world(self.model.line_sensor_ahead, left)
+    }
+
+    /// The distance to the nearest wall in the beam of the distance sensor, if it is in range.
+    ///
+    /// The servo is mounted so that 90 degrees looks straight ahead and 180 degrees to the left.
+    fn echo_distance(&self) -> Option<f64> {
+        let pose = self.body.pose;
+        let origin = pose.to_world(self.model.mast_ahead, 0.0);
+        let bearing = pose.heading + (self.body.mast_angle - 90.0).to_radians();
+        let half_beam = self.model.beam_half_angle.to_radians();
+
+        (0..BEAM_RAYS)
+            .map(|ray| bearing - half_beam + 2.0 * half_beam * ray as f64 / (BEAM_RAYS - 1) as f64)
+            .filter_map(|angle| self.world.cast_ray(origin, Point::from_angle(angle)))
+            .filter(|distance| *distance <= self.model.max_range)
+            .fold(None, |nearest: Option<f64>, distance| Some(nearest.map_or(distance, |nearest| nearest.min(distance))))
+    }
+}
+
+/// The count of the echo timer at a time in µs.
+fn ticks(us: u64) -> u16 {
+    (us / TICK_US) as u16
+}
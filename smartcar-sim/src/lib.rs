@@ -0,0 +1,228 @@
+//! A physics simulation of the smart car, for running its drivers and behaviors on the host.
+//!
+//! The [Sim] holds a [World] of lines on the floor and walls, and a car that moves around in it.
+//! It hands out the car's drivers in a [Robot], wired to simulated pins (see [hardware]) instead of the AVR's,
+//! so the behaviors from the `smartcar` crate run on it unmodified:
+//!
+//! - The motor driver's pins set how the wheels on each side are driven, and the car moves as a differential drive,
+//!   see [car].
+//! - The line sensors are low over the lines, which are polylines with a width.
+//! - The echo timer times the echoes of rays cast from the servo-mounted distance sensor to the walls.
+//! - [smartcar::clock::millis] reads the simulated time, which only moves when [Sim::advance] moves it
+//!   (and by a tick whenever the echo timer is read, like on the robot).
+//!
+//! The simulated car is the V3 kit, with the L298N motor driver and the digital line tracker.
+//! A test builds a world, runs a behavior's `update` and [Sim::advance] in a loop, and then looks at where the car went.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use smartcar::hc_sr04_distance_sensor::HC_SR04;
+use smartcar::l287n_motor_driver::MotorChassis;
+use smartcar::line_tracker::{LineTracker, LineTrackerDirection};
+use smartcar::servo::{Servo, FRAME_US};
+
+pub mod car;
+pub mod clock;
+pub mod geometry;
+pub mod hardware;
+pub mod world;
+
+use car::{Body, CarModel, MotorInput};
+use geometry::{Point, Pose};
+use hardware::{LineSensorPin, SimEchoTimer, SimPin, SimPwm};
+use world::World;
+
+pub type Chassis = MotorChassis<SimPwm<u8>, SimPin>;
+pub type DistanceSensor = HC_SR04<SimPin, SimEchoTimer>;
+pub type MastServo = Servo<SimPwm<u16>>;
+pub type Tracker = LineTracker<LineSensorPin>;
+
+/// Every driver of the simulated car.
+pub struct Robot {
+    pub chassis: Chassis,
+    pub servo: MastServo,
+    pub distance_sensor: DistanceSensor,
+    pub line_tracker: Tracker,
+}
+
+/// The longest time that the car is moved in one go, in µs, so that its path follows curves closely.
+const MAX_STEP_US: u64 = 1000;
+
+/// The part of the simulation that the input pins look at.
+pub(crate) struct Shared {
+    world: World,
+    model: CarModel,
+    body: Body,
+}
+
+/// The motor driver's pins, as the L298N sees them.
+#[derive(Clone)]
+struct MotorPins {
+    enable_a: SimPwm<u8>,
+    enable_b: SimPwm<u8>,
+    a1: SimPin,
+    a2: SimPin,
+    b1: SimPin,
+    b2: SimPin,
+}
+
+impl MotorPins {
+    fn new() -> Self {
+        Self {
+            // Like the firmware's timer outputs, the duty cycle goes up to 255.
+            enable_a: SimPwm::new(u8::MAX),
+            enable_b: SimPwm::new(u8::MAX),
+            a1: SimPin::default(),
+            a2: SimPin::default(),
+            b1: SimPin::default(),
+            b2: SimPin::default(),
+        }
+    }
+}
+
+/// The simulated world and car.
+pub struct Sim {
+    shared: Rc<RefCell<Shared>>,
+    motor_pins: MotorPins,
+    servo_output: SimPwm<u16>,
+    /// The simulated time up to which the car has been moved, in µs.
+    stepped_us: u64,
+    /// The distance that the car has moved, in mm.
+    odometer: f64,
+    /// The number of times the car ran into a wall.
+    bumps: u32,
+}
+
+impl Sim {
+    /// Put a car in a world at the given pose, standing still, and start the simulated time over from 0.
+    ///
+    /// This also makes [smartcar::clock::millis] read the simulated time of the calling thread.
+    pub fn new(world: World, model: CarModel, pose: Pose) -> Self {
+        clock::install();
+        clock::reset();
+
+        Self {
+            shared: Rc::new(RefCell::new(Shared { world, model, body: Body::new(pose) })),
+            motor_pins: MotorPins::new(),
+            // The servo's PWM output counts in µs, so its duty cycle is the pulse width.
+            servo_output: SimPwm::new(FRAME_US as u16),
+            stepped_us: 0,
+            odometer: 0.0,
+            bumps: 0,
+        }
+    }
+
+    /// Create the drivers of the car, wired to the simulated pins.
+    ///
+    /// Every call wires a new set of drivers to the same pins, so only call this once.
+    pub fn robot(&self) -> Robot {
+        let pins = self.motor_pins.clone();
+        let line_sensor = |direction| LineSensorPin::new(self.shared.clone(), direction);
+        let calibration = self.shared.borrow().model.servo_calibration;
+
+        Robot {
+            chassis: MotorChassis::new(pins.enable_a, pins.enable_b, pins.a1, pins.a2, pins.b1, pins.b2),
+            servo: Servo::new(self.servo_output.clone(), calibration),
+            distance_sensor: HC_SR04::new(SimPin::default(), SimEchoTimer::new(self.shared.clone())),
+            line_tracker: LineTracker::new(
+                line_sensor(LineTrackerDirection::Left),
+                line_sensor(LineTrackerDirection::Center),
+                line_sensor(LineTrackerDirection::Right),
+            ),
+        }
+    }
+
+    /// Move the simulated time forward, and move the car along with it.
+    pub fn advance(&mut self, ms: u64) {
+        clock::advance_micros(ms * 1000);
+        let now = clock::micros();
+
+        let motors = self.motor_inputs();
+        let servo_target = self.servo_target();
+        let mut shared = self.shared.borrow_mut();
+        let Shared { world, model, body } = &mut *shared;
+
+        while self.stepped_us < now {
+            let step_us = (now - self.stepped_us).min(MAX_STEP_US);
+            let was_touching = body.touching;
+            self.odometer += body.step(model, world, motors, servo_target, step_us as f64 / 1000.0);
+            if body.touching && !was_touching {
+                self.bumps += 1;
+            }
+            self.stepped_us += step_us;
+        }
+    }
+
+    /// The simulated time, in ms.
+    pub fn millis(&self) -> u64 {
+        clock::micros() / 1000
+    }
+
+    pub fn pose(&self) -> Pose {
+        self.shared.borrow().body.pose
+    }
+
+    /// The speeds of the left and the right wheels, in mm/s.
+    pub fn wheel_speeds(&self) -> (f64, f64) {
+        self.shared.borrow().body.wheel_speeds
+    }
+
+    /// The angle that the servo is turned to, in degrees.
+    pub fn mast_angle(&self) -> f64 {
+        self.shared.borrow().body.mast_angle
+    }
+
+    /// Where a line sensor is on the floor.
+    pub fn line_sensor_position(&self, direction: LineTrackerDirection) -> Point {
+        self.shared.borrow().line_sensor_position(direction)
+    }
+
+    /// The distance from the center line sensor to the middle of the nearest line, in mm.
+    pub fn line_offset(&self) -> f64 {
+        let shared = self.shared.borrow();
+        shared.world.distance_to_line(shared.line_sensor_position(LineTrackerDirection::Center))
+    }
+
+    /// The distance that the car has moved, in mm.
+    pub fn odometer(&self) -> f64 {
+        self.odometer
+    }
+
+    /// The number of times that the car ran into a wall.
+    pub fn bumps(&self) -> u32 {
+        self.bumps
+    }
+
+    /// How the L298N drives the motors on each side, from the state of its pins.
+    ///
+    /// The A pair is on the left, and goes forward when A1 is high; the B pair goes forward when B2 is high.
+    /// A pair is braked when it is enabled and both of its direction pins are at the same level.
+    fn motor_inputs(&self) -> (MotorInput, MotorInput) {
+        let pins = &self.motor_pins;
+        (
+            motor_input(pins.enable_a.output(), pins.a1.is_set_high(), pins.a2.is_set_high()),
+            motor_input(pins.enable_b.output(), pins.b2.is_set_high(), pins.b1.is_set_high()),
+        )
+    }
+
+    /// The angle that the servo is sent to by its pulses, or `None` if it gets no pulses.
+    fn servo_target(&self) -> Option<f64> {
+        let calibration = self.shared.borrow().model.servo_calibration;
+        let pulse_us = self.servo_output.output()? as f64;
+        let span = calibration.max_us as f64 - calibration.min_us as f64;
+        Some(((pulse_us - calibration.min_us as f64) * 180.0 / span).clamp(0.0, 180.0))
+    }
+}
+
+/// How a motor pair is driven, from its enable output and its direction pins.
+fn motor_input(enable: Option<u8>, forward: bool, backward: bool) -> MotorInput {
+    match enable {
+        None | Some(0) => MotorInput::Coast,
+        Some(_) if forward == backward => MotorInput::Brake,
+        Some(duty) => {
+            let fraction = duty as f64 / u8::MAX as f64;
+            MotorInput::Drive(if forward { fraction } else { -fraction })
+        },
+    }
+}
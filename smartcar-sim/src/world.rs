@@ -0,0 +1,122 @@
+//! What the car drives around in: lines drawn on the floor, and walls standing on it.
+
+use smartcar::sound_speed::SoundSpeed;
+
+use crate::geometry::{Point, Segment};
+
+/// The width of the black tape that the kit's line tracking course is made of, in mm.
+pub const TAPE_WIDTH_MM: f64 = 18.0;
+
+/// A line drawn on the floor, along a polyline.
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub points: Vec<Point>,
+    /// The width of the line, in mm.
+    pub width: f64,
+}
+
+impl Line {
+    /// A line of tape through the given points.
+    pub fn new(points: Vec<Point>) -> Self {
+        Self { points, width: TAPE_WIDTH_MM }
+    }
+
+    /// A line of tape through the given points, and back from the last point to the first.
+    pub fn closed(mut points: Vec<Point>) -> Self {
+        if let Some(&first) = points.first() {
+            points.push(first);
+        }
+        Self::new(points)
+    }
+
+    /// The straight pieces of the line.
+    pub fn segments(&self) -> impl Iterator<Item = Segment> + '_ {
+        self.points.windows(2).map(|pair| Segment::new(pair[0], pair[1]))
+    }
+
+    /// The distance from a point to the middle of the line, in mm.
+    pub fn distance_to(&self, point: Point) -> f64 {
+        match self.points.as_slice() {
+            [] => f64::INFINITY,
+            [only] => only.distance(point),
+            _ => self.segments().map(|segment| segment.distance_to(point)).fold(f64::INFINITY, f64::min),
+        }
+    }
+
+    /// Whether a point on the floor is on the line.
+    pub fn covers(&self, point: Point) -> bool {
+        self.distance_to(point) <= self.width / 2.0
+    }
+}
+
+/// The lines and walls around the car.
+///
+/// The floor is light everywhere except on the lines, which are dark.
+#[derive(Debug, Clone)]
+pub struct World {
+    pub lines: Vec<Line>,
+    pub walls: Vec<Segment>,
+    /// The speed of sound in the air, which sets how long the echoes take.
+    pub sound_speed: SoundSpeed,
+}
+
+impl Default for World {
+    /// An empty floor, at room temperature.
+    fn default() -> Self {
+        Self {
+            lines: Vec::new(),
+            walls: Vec::new(),
+            sound_speed: SoundSpeed::default(),
+        }
+    }
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_line(&mut self, line: Line) {
+        self.lines.push(line);
+    }
+
+    pub fn add_wall(&mut self, start: Point, end: Point) {
+        self.walls.push(Segment::new(start, end));
+    }
+
+    /// Add the four walls of a rectangular room or box, between two opposite corners.
+    pub fn add_box(&mut self, corner: Point, opposite: Point) {
+        let corners = [
+            corner,
+            Point::new(opposite.x, corner.y),
+            opposite,
+            Point::new(corner.x, opposite.y),
+        ];
+        for i in 0..corners.len() {
+            self.add_wall(corners[i], corners[(i + 1) % corners.len()]);
+        }
+    }
+
+    /// Whether a point on the floor is on any line.
+    pub fn is_dark(&self, point: Point) -> bool {
+        self.lines.iter().any(|line| line.covers(point))
+    }
+
+    /// The distance from a point to the middle of the nearest line, in mm.
+    pub fn distance_to_line(&self, point: Point) -> f64 {
+        self.lines.iter().map(|line| line.distance_to(point)).fold(f64::INFINITY, f64::min)
+    }
+
+    /// The distance from a point to the nearest wall, in mm.
+    pub fn distance_to_wall(&self, point: Point) -> f64 {
+        self.walls.iter().map(|wall| wall.distance_to(point)).fold(f64::INFINITY, f64::min)
+    }
+
+    /// How far a ray from `origin` in the direction of the unit vector `direction` goes before it hits a wall.
+    pub fn cast_ray(&self, origin: Point, direction: Point) -> Option<f64> {
+        self.walls
+            .iter()
+            .filter_map(|wall| wall.ray_distance(origin, direction))
+            .fold(None, |nearest: Option<f64>, distance| Some(nearest.map_or(distance, |nearest| nearest.min(distance))))
+    }
+}
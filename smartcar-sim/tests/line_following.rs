@@ -0,0 +1,102 @@
+//! Runs the line following mode on a simulated car, around a course of tape on the floor.
+
+use std::f64::consts::PI;
+
+use smartcar::follow_mode::{FollowMode, Steering};
+use smartcar::line_following::FollowConfig;
+use smartcar_sim::car::CarModel;
+use smartcar_sim::geometry::{Point, Pose};
+use smartcar_sim::world::{Line, World};
+use smartcar_sim::Sim;
+
+/// The half-length of the straights of the course, in mm.
+const STRAIGHT_MM: f64 = 500.0;
+/// The radius of the bends of the course, in mm.
+const BEND_RADIUS_MM: f64 = 300.0;
+
+/// A stadium-shaped loop around the origin: two straights along the x axis, joined by two half circles.
+fn stadium() -> World {
+    let mut points = Vec::new();
+    for (center_x, start_angle) in [(STRAIGHT_MM, -PI / 2.0), (-STRAIGHT_MM, PI / 2.0)].iter() {
+        for i in 0..=36 {
+            let angle = start_angle + PI * i as f64 / 36.0;
+            points.push(Point::new(center_x + BEND_RADIUS_MM * angle.cos(), BEND_RADIUS_MM * angle.sin()));
+        }
+    }
+
+    let mut world = World::new();
+    world.add_line(Line::closed(points));
+    world
+}
+
+/// The result of following the line for a while.
+struct Run {
+    /// The number of times the car went around the origin, counterclockwise.
+    laps: f64,
+    /// The largest distance between the center line sensor and the middle of the line, in mm.
+    ///
+    /// Past 24mm, an outer sensor is further than half the tape's width from the line, and the car has lost it.
+    worst_offset: f64,
+}
+
+/// Follow the stadium counterclockwise for the given time, starting on the lower straight.
+fn follow(steering: Steering, duration_ms: u64) -> Run {
+    let mut sim = Sim::new(stadium(), CarModel::default(), Pose::new(-300.0, -BEND_RADIUS_MM, 0.0));
+    let mut robot = sim.robot();
+    let mut follow_mode = FollowMode::new(FollowConfig::default(), steering);
+
+    let angle_around = |pose: Pose| pose.position.y.atan2(pose.position.x);
+    let mut last_angle = angle_around(sim.pose());
+    let mut turned = 0.0;
+    let mut worst_offset: f64 = 0.0;
+
+    while sim.millis() < duration_ms {
+        follow_mode.update(&mut robot.chassis, &mut robot.line_tracker);
+        sim.advance(1);
+
+        let angle = angle_around(sim.pose());
+        let mut delta = angle - last_angle;
+        if delta > PI {
+            delta -= 2.0 * PI;
+        } else if delta < -PI {
+            delta += 2.0 * PI;
+        }
+        turned += delta;
+        last_angle = angle;
+        worst_offset = worst_offset.max(sim.line_offset());
+    }
+
+    Run { laps: turned / (2.0 * PI), worst_offset }
+}
+
+#[test]
+fn pid_steering_goes_around_the_loop() {
+    let run = follow(Steering::Pid, 20_000);
+    assert!(run.laps > 1.0, "only went {:.2} laps", run.laps);
+    assert!(run.worst_offset < 25.0, "strayed {:.1}mm from the line", run.worst_offset);
+}
+
+#[test]
+fn discrete_steering_goes_around_the_loop() {
+    let run = follow(Steering::Discrete, 20_000);
+    assert!(run.laps > 1.0, "only went {:.2} laps", run.laps);
+    assert!(run.worst_offset < 25.0, "strayed {:.1}mm from the line", run.worst_offset);
+}
+
+#[test]
+fn searches_for_a_line_that_is_lost() {
+    // Without a line on the floor, the car spins in place looking for it.
+    let mut sim = Sim::new(World::new(), CarModel::default(), Pose::new(0.0, 0.0, 0.0));
+    let mut robot = sim.robot();
+    let mut follow_mode = FollowMode::new(FollowConfig::default(), Steering::Discrete);
+
+    while sim.millis() < 2000 {
+        follow_mode.update(&mut robot.chassis, &mut robot.line_tracker);
+        sim.advance(1);
+    }
+
+    let pose = sim.pose();
+    assert!(pose.position.distance(Point::new(0.0, 0.0)) < 1.0);
+    // Searching to the left turns counterclockwise.
+    assert!(pose.heading > PI);
+}
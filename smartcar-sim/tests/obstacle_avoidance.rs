@@ -0,0 +1,95 @@
+//! Runs the obstacle avoidance mode on a simulated car, among walls.
+
+use smartcar::avoid_mode::AvoidMode;
+use smartcar::obstacle_avoidance::{AvoidConfig, AvoidState, Side};
+use smartcar::range_scanner::{RangeScanner, ScanConfig};
+use smartcar_sim::car::CarModel;
+use smartcar_sim::geometry::{Point, Pose};
+use smartcar_sim::world::World;
+use smartcar_sim::Sim;
+
+/// A square room of 3m by 3m around the origin.
+fn room() -> World {
+    let mut world = World::new();
+    world.add_box(Point::new(-1500.0, -1500.0), Point::new(1500.0, 1500.0));
+    world
+}
+
+/// Run the avoid mode for the given time, and call `watch` with the state of the behavior after every step.
+fn avoid(sim: &mut Sim, duration_ms: u64, mut watch: impl FnMut(&Sim, AvoidState)) {
+    let robot = sim.robot();
+    let mut chassis = robot.chassis;
+    let mut scanner = RangeScanner::new(robot.servo, robot.distance_sensor, ScanConfig::default()).unwrap();
+    let mut avoid_mode = AvoidMode::new(AvoidConfig::default());
+    avoid_mode.restart(&mut scanner);
+
+    while sim.millis() < duration_ms {
+        avoid_mode.update(&mut chassis, &mut scanner);
+        sim.advance(1);
+        watch(sim, avoid_mode.behavior().state());
+    }
+}
+
+#[test]
+fn stops_in_front_of_a_wall_and_turns_toward_the_open_side() {
+    // A wall straight ahead, and another along the right side up to it, so only the left is open.
+    let mut world = room();
+    world.add_wall(Point::new(500.0, -1500.0), Point::new(500.0, 1500.0));
+    world.add_wall(Point::new(-1500.0, -300.0), Point::new(500.0, -300.0));
+    let mut sim = Sim::new(world, CarModel::default(), Pose::new(-500.0, 0.0, 0.0));
+
+    let mut turned_left = false;
+    let mut closest_mm = f64::INFINITY;
+    avoid(&mut sim, 8000, |sim, state| {
+        if let AvoidState::Turning { side, .. } = state {
+            turned_left |= side == Side::Left;
+        }
+        closest_mm = closest_mm.min(500.0 - sim.pose().position.x);
+    });
+
+    assert!(turned_left);
+    assert_eq!(sim.bumps(), 0);
+    // The car stops about the obstacle distance away from the wall, measured from the sensor.
+    assert!(closest_mm > 250.0, "got within {:.0}mm of the wall", closest_mm);
+    // Then it heads away from the wall, to the left.
+    assert!(sim.pose().position.y > 0.0);
+}
+
+#[test]
+fn backs_out_of_a_dead_end() {
+    // A corridor 500mm wide, which ends 600mm in front of the car.
+    let mut world = room();
+    world.add_wall(Point::new(-1500.0, 250.0), Point::new(0.0, 250.0));
+    world.add_wall(Point::new(-1500.0, -250.0), Point::new(0.0, -250.0));
+    world.add_wall(Point::new(0.0, -250.0), Point::new(0.0, 250.0));
+    let mut sim = Sim::new(world, CarModel::default(), Pose::new(-600.0, 0.0, 0.0));
+
+    let mut backed_up = false;
+    avoid(&mut sim, 8000, |_, state| {
+        backed_up |= matches!(state, AvoidState::BackingUp { .. });
+    });
+
+    assert!(backed_up);
+    assert_eq!(sim.bumps(), 0);
+}
+
+#[test]
+fn wanders_around_a_room_without_hitting_anything() {
+    let mut world = room();
+    // A box in the middle of the room.
+    world.add_box(Point::new(-200.0, -200.0), Point::new(200.0, 200.0));
+    let mut sim = Sim::new(world, CarModel::default(), Pose::new(-900.0, -900.0, 0.3));
+
+    let mut scans = 0;
+    let mut last_state = AvoidState::Cruising;
+    avoid(&mut sim, 60_000, |_, state| {
+        if state == AvoidState::Scanning && last_state != AvoidState::Scanning {
+            scans += 1;
+        }
+        last_state = state;
+    });
+
+    assert!(scans >= 3, "only scanned {} times", scans);
+    assert_eq!(sim.bumps(), 0);
+    assert!(sim.odometer() > 5000.0, "only drove {:.0}mm", sim.odometer());
+}
@@ -0,0 +1,136 @@
+//! Drives the simulated car by hand, to check that it moves like the real one.
+
+use std::f64::consts::PI;
+
+use smartcar::hc_sr04_distance_sensor::DistanceMeasurement;
+use smartcar::line_tracker::LineBiasDirection;
+use smartcar_sim::car::CarModel;
+use smartcar_sim::geometry::{Point, Pose};
+use smartcar_sim::world::{Line, World};
+use smartcar_sim::{DistanceSensor, Sim};
+
+#[test]
+fn drives_straight_at_a_speed_proportional_to_the_duty_cycle() {
+    let mut sim = Sim::new(World::new(), CarModel::default(), Pose::new(0.0, 0.0, 0.0));
+    let mut robot = sim.robot();
+
+    robot.chassis.drive(255, 255);
+    sim.advance(2000);
+    // 500mm/s, minus a little while the motors spin up.
+    let x = sim.pose().position.x;
+    assert!(x > 900.0 && x < 1000.0, "drove to {:.0}mm", x);
+    assert_eq!(sim.pose().position.y, 0.0);
+
+    robot.chassis.drive(-128, -128);
+    sim.advance(1000);
+    let (left, right) = sim.wheel_speeds();
+    assert!((left + 251.0).abs() < 1.0 && (right + 251.0).abs() < 1.0);
+}
+
+#[test]
+fn braking_stops_sooner_than_coasting() {
+    let stopping_distance = |brake: bool| {
+        let mut sim = Sim::new(World::new(), CarModel::default(), Pose::new(0.0, 0.0, 0.0));
+        let mut robot = sim.robot();
+        robot.chassis.drive(255, 255);
+        sim.advance(1000);
+        let start = sim.odometer();
+        if brake {
+            robot.chassis.brake();
+        } else {
+            robot.chassis.coast();
+        }
+        sim.advance(1000);
+        sim.odometer() - start
+    };
+
+    assert!(stopping_distance(true) < 10.0);
+    assert!(stopping_distance(false) > 90.0);
+}
+
+#[test]
+fn spins_in_place_with_opposite_wheels() {
+    let mut sim = Sim::new(World::new(), CarModel::default(), Pose::new(0.0, 0.0, 0.0));
+    let mut robot = sim.robot();
+
+    // The wheels on each side at 250mm/s turn the car around at 500 / 130 radians per second.
+    robot.chassis.drive(-128, 128);
+    sim.advance(1000);
+    let pose = sim.pose();
+    assert!(pose.position.distance(Point::new(0.0, 0.0)) < 0.001);
+    assert!(pose.heading > 3.0 && pose.heading < 500.0 / 130.0, "turned to {:.2}rad", pose.heading);
+}
+
+#[test]
+fn line_sensors_see_the_tape_under_them() {
+    let mut world = World::new();
+    world.add_line(Line::new(vec![Point::new(0.0, -500.0), Point::new(0.0, 500.0)]));
+
+    // The sensors are 90mm in front of the pose, so they are over the line when the car faces it from 90mm away.
+    let mut sim = Sim::new(world.clone(), CarModel::default(), Pose::new(-90.0, 0.0, 0.0));
+    let position = sim.robot().line_tracker.measure_full();
+    assert_eq!(position.get_bias_direction_dark(), LineBiasDirection::OnPerpendicularLine);
+
+    // Along the line, only the center sensor is over it.
+    sim = Sim::new(world.clone(), CarModel::default(), Pose::new(0.0, -200.0, PI / 2.0));
+    let position = sim.robot().line_tracker.measure_full();
+    assert_eq!(position.get_bias_direction_dark(), LineBiasDirection::Center);
+
+    // With the line 15mm to the right of the center sensor, only the right sensor is over it.
+    sim = Sim::new(world, CarModel::default(), Pose::new(-15.0, -200.0, PI / 2.0));
+    let position = sim.robot().line_tracker.measure_full();
+    assert_eq!(position.get_bias_direction_dark(), LineBiasDirection::VeryRight);
+}
+
+/// Ping with the distance sensor, polling it every millisecond like the behaviors do.
+fn ping(sim: &mut Sim, sensor: &mut DistanceSensor) -> DistanceMeasurement {
+    sensor.start_measurement();
+    loop {
+        if let Ok(measurement) = sensor.poll() {
+            return measurement;
+        }
+        sim.advance(1);
+    }
+}
+
+#[test]
+fn distance_sensor_hears_the_walls_in_its_beam() {
+    let mut world = World::new();
+    world.add_wall(Point::new(1070.0, -500.0), Point::new(1070.0, 500.0));
+    let mut sim = Sim::new(world, CarModel::default(), Pose::new(0.0, 0.0, 0.0));
+    let mut robot = sim.robot();
+
+    // The sensor is 70mm in front of the pose, so the wall is 1m away.
+    match ping(&mut sim, &mut robot.distance_sensor) {
+        DistanceMeasurement::Measured(distance) => {
+            assert!((distance.to_mm() as i64 - 1000).abs() <= 2, "measured {}", distance);
+        },
+        other => panic!("expected a distance, got {}", other),
+    }
+
+    // Looking to the left, there is nothing to hear.
+    robot.servo.set_angle(180);
+    sim.advance(500);
+    assert!(matches!(ping(&mut sim, &mut robot.distance_sensor), DistanceMeasurement::Infinity));
+}
+
+#[test]
+fn walls_stop_the_car() {
+    let mut world = World::new();
+    world.add_wall(Point::new(500.0, -500.0), Point::new(500.0, 500.0));
+    let mut sim = Sim::new(world, CarModel::default(), Pose::new(0.0, 0.0, 0.0));
+    let mut robot = sim.robot();
+
+    robot.chassis.drive(255, 255);
+    sim.advance(3000);
+    let x = sim.pose().position.x;
+    // The car's body is 120mm around the pose.
+    assert!(x > 370.0 && x <= 380.0, "stopped at {:.0}mm", x);
+    assert_eq!(sim.bumps(), 1);
+
+    // It can still back away.
+    robot.chassis.drive(-255, -255);
+    sim.advance(1000);
+    assert!(sim.pose().position.x < 300.0);
+    assert_eq!(sim.bumps(), 1);
+}
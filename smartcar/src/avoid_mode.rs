@@ -0,0 +1,153 @@
+//! Running the obstacle avoidance behavior on the robot.
+//!
+//! This connects the state machine from [crate::obstacle_avoidance] to the motors, the servo and the distance sensor.
+//! While cruising, the sensor looks straight ahead and pings as often as it can;
+//! when the behavior asks for a scan, the [RangeScanner] sweeps it from side to side.
+//!
+//! The servo is assumed to be mounted so that 180 degrees looks to the left and 0 degrees to the right.
+
+use core::convert::Infallible;
+
+use crate::clock;
+use crate::differential_drive::Drive;
+use crate::hal::{EchoTimer, OutputPin, PwmPin};
+use crate::hc_sr04_distance_sensor::{DistanceMeasurement, MIN_CYCLE_MS};
+use crate::obstacle_avoidance::{AvoidAction, AvoidBehavior, AvoidConfig, AvoidEvent};
+use crate::range_scanner::RangeScanner;
+
+/// The servo angle that looks straight ahead.
+const FORWARD_ANGLE: u8 = 90;
+
+/// The obstacle avoidance mode.
+///
+/// It does not own the hardware, so that the robot can switch between modes;
+/// call [AvoidMode::update] as often as possible with the drivers.
+/// The chassis can be any [Drive], and the scanner is generic over the same types as the [RangeScanner].
+pub struct AvoidMode {
+    behavior: AvoidBehavior,
+    /// Whether a single ping straight ahead is in flight.
+    pinging: bool,
+    /// The earliest time to send the next ping straight ahead.
+    next_ping_ms: u64,
+    /// Whether a scan is in progress.
+    scanning: bool,
+}
+
+impl AvoidMode {
+    pub fn new(config: AvoidConfig) -> Self {
+        Self {
+            behavior: AvoidBehavior::new(config),
+            pinging: false,
+            next_ping_ms: 0,
+            scanning: false,
+        }
+    }
+
+    /// Get the state machine, for example to look at its state or change its settings.
+    pub fn behavior(&mut self) -> &mut AvoidBehavior {
+        &mut self.behavior
+    }
+
+    /// Start over from the cruising state, pointing the sensor straight ahead.
+    pub fn restart<S, P, T>(&mut self, scanner: &mut RangeScanner<S, P, T>)
+    where
+        S: PwmPin<Duty = u16>,
+        P: OutputPin<Error = Infallible>,
+        T: EchoTimer,
+    {
+        let config = *self.behavior.config();
+        self.behavior = AvoidBehavior::new(config);
+        self.pinging = false;
+        self.scanning = false;
+        scanner.servo().set_angle(FORWARD_ANGLE);
+    }
+
+    /// Read the sensors, advance the state machine, and drive the motors.
+    pub fn update<C, S, P, T>(&mut self, chassis: &mut C, scanner: &mut RangeScanner<S, P, T>)
+    where
+        C: Drive,
+        S: PwmPin<Duty = u16>,
+        P: OutputPin<Error = Infallible>,
+        T: EchoTimer,
+    {
+        let now = clock::millis();
+        let event = if self.scanning {
+            self.poll_scan(scanner)
+        } else if self.behavior.wants_front_distance() {
+            self.poll_front(now, scanner)
+        } else {
+            AvoidEvent::Tick
+        };
+
+        match self.behavior.handle(now, event) {
+            AvoidAction::Continue => {},
+            AvoidAction::Drive(left, right) => chassis.drive(left, right),
+            AvoidAction::Brake => chassis.brake(),
+            AvoidAction::StartScan => {
+                chassis.coast();
+                // The scan takes over the sensor, so any ping in flight is abandoned.
+                self.pinging = false;
+                self.scanning = true;
+                scanner.start();
+            },
+        }
+    }
+
+    /// Move the scan along, and report the room on each side once it is finished.
+    fn poll_scan<S, P, T>(&mut self, scanner: &mut RangeScanner<S, P, T>) -> AvoidEvent
+    where
+        S: PwmPin<Duty = u16>,
+        P: OutputPin<Error = Infallible>,
+        T: EchoTimer,
+    {
+        let profile = match scanner.poll() {
+            Ok(profile) => profile,
+            Err(nb::Error::WouldBlock) => return AvoidEvent::Tick,
+            Err(nb::Error::Other(_)) => {
+                self.scanning = false;
+                return AvoidEvent::ScanFinished { left_mm: 0, right_mm: 0 };
+            },
+        };
+
+        self.scanning = false;
+        scanner.servo().set_angle(FORWARD_ANGLE);
+        AvoidEvent::ScanFinished {
+            left_mm: profile.most_room(FORWARD_ANGLE + 1, 180),
+            right_mm: profile.most_room(0, FORWARD_ANGLE - 1),
+        }
+    }
+
+    /// Keep pinging straight ahead, and report the distance whenever a ping comes back.
+    fn poll_front<S, P, T>(&mut self, now: u64, scanner: &mut RangeScanner<S, P, T>) -> AvoidEvent
+    where
+        S: PwmPin<Duty = u16>,
+        P: OutputPin<Error = Infallible>,
+        T: EchoTimer,
+    {
+        if !self.pinging {
+            // After a scan or a restart the mast swings back to the front, and pings are only sent once it is still.
+            if now >= self.next_ping_ms && scanner.servo().is_settled() {
+                scanner.sensor().start_measurement();
+                self.pinging = true;
+                self.next_ping_ms = now + MIN_CYCLE_MS;
+            }
+            return AvoidEvent::Tick;
+        }
+
+        match scanner.sensor().poll() {
+            Ok(measurement) => {
+                self.pinging = false;
+                AvoidEvent::FrontDistance(match measurement {
+                    DistanceMeasurement::Measured(distance) => Some(distance.to_mm()),
+                    DistanceMeasurement::Infinity => Some(u64::MAX),
+                    DistanceMeasurement::Unknown => None,
+                })
+            },
+            Err(nb::Error::WouldBlock) => AvoidEvent::Tick,
+            Err(nb::Error::Other(_)) => {
+                self.pinging = false;
+                AvoidEvent::Tick
+            },
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! The time since the program started, in milliseconds.
+//!
+//! The drivers read the time with [millis], which calls a source installed at startup with [set_source]:
+//! on the robot, the firmware's millisecond timer, and on the host, whatever clock a test wants to simulate.
+//! Until a source is installed, the time stands still at 0.
+
+/// The function that [millis] reads the time from.
+static mut SOURCE: fn() -> u64 = stopped;
+
+fn stopped() -> u64 {
+    0
+}
+
+/// Install the function that [millis] reads the time from.
+///
+/// # Safety
+///
+/// Nothing may call [millis] at the same time, from an interrupt or another thread,
+/// so this should be done once, at startup.
+pub unsafe fn set_source(source: fn() -> u64) {
+    SOURCE = source;
+}
+
+/// Get how many milliseconds have passed since the program started, according to the installed source.
+pub fn millis() -> u64 {
+    // SAFETY: the source is only written by `set_source`, which must not race with this.
+    let source = unsafe { SOURCE };
+    source()
+}
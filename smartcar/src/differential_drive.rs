@@ -0,0 +1,103 @@
+//! Pure math for driving the two motor pairs as a differential drive.
+//!
+//! Wheel commands are signed: the sign selects the direction (positive is forward),
+//! and the magnitude is the PWM duty cycle, from 0 to [MAX_SPEED].
+//!
+//! The motor drivers implement [Drive], so that the behaviors can steer either of them.
+
+/// The largest magnitude of a wheel command, corresponding to a 100% duty cycle.
+pub const MAX_SPEED: i16 = 255;
+
+/// Clamp a signed wheel command into the range `-MAX_SPEED..=MAX_SPEED`.
+pub fn clamp_speed(value: i16) -> i16 {
+    value.clamp(-MAX_SPEED, MAX_SPEED)
+}
+
+/// Split a signed wheel command into its direction (`true` for forward) and its duty cycle.
+///
+/// The command is clamped first, so the duty cycle always fits.
+pub fn split_speed(value: i16) -> (bool, u8) {
+    let value = clamp_speed(value);
+    (value >= 0, value.abs() as u8)
+}
+
+/// Mix a throttle and a turn rate into left and right wheel commands, arcade-style.
+///
+/// A positive throttle drives forward, and a positive turn curves to the right
+/// by speeding up the left wheels and slowing down the right ones.
+///
+/// If one of the wheels would go past [MAX_SPEED], both wheels are scaled down by the same factor,
+/// so that the ratio between them (and so the radius of the curve) is kept.
+pub fn arcade_mix(throttle: i16, turn: i16) -> (i16, i16) {
+    let throttle = clamp_speed(throttle) as i32;
+    let turn = clamp_speed(turn) as i32;
+
+    let left = throttle + turn;
+    let right = throttle - turn;
+
+    let largest = left.abs().max(right.abs());
+    if largest <= MAX_SPEED as i32 {
+        return (left as i16, right as i16);
+    }
+
+    let scale = |value: i32| (value * MAX_SPEED as i32 / largest) as i16;
+    (scale(left), scale(right))
+}
+
+/// A motor driver that drives the wheels on each side with signed commands.
+///
+/// This is what the behaviors steer the robot with, whichever motor driver the kit has.
+pub trait Drive {
+    /// Drive each side with a signed command, from -[MAX_SPEED] to [MAX_SPEED]; see [split_speed].
+    fn drive(&mut self, left: i16, right: i16);
+
+    /// Stop both sides quickly, by short-braking the motors.
+    fn brake(&mut self);
+
+    /// Stop both sides by letting the wheels spin down freely.
+    fn coast(&mut self);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamp_limits_both_directions() {
+        assert_eq!(clamp_speed(100), 100);
+        assert_eq!(clamp_speed(-100), -100);
+        assert_eq!(clamp_speed(1000), MAX_SPEED);
+        assert_eq!(clamp_speed(i16::MIN), -MAX_SPEED);
+    }
+
+    #[test]
+    fn split_gives_direction_and_duty() {
+        assert_eq!(split_speed(0), (true, 0));
+        assert_eq!(split_speed(128), (true, 128));
+        assert_eq!(split_speed(-128), (false, 128));
+        assert_eq!(split_speed(-300), (false, 255));
+    }
+
+    #[test]
+    fn arcade_straight_and_spin() {
+        assert_eq!(arcade_mix(200, 0), (200, 200));
+        assert_eq!(arcade_mix(-200, 0), (-200, -200));
+        assert_eq!(arcade_mix(0, 100), (100, -100));
+        assert_eq!(arcade_mix(0, -100), (-100, 100));
+    }
+
+    #[test]
+    fn arcade_gentle_curve() {
+        assert_eq!(arcade_mix(150, 50), (200, 100));
+        assert_eq!(arcade_mix(150, -50), (100, 200));
+    }
+
+    #[test]
+    fn arcade_keeps_ratio_when_saturated() {
+        // 255 + 255 = 510 on the left, 0 on the right: scaled by a half.
+        assert_eq!(arcade_mix(255, 255), (255, 0));
+        // 200 + 100 = 300 and 200 - 100 = 100, scaled to 255 and 85.
+        assert_eq!(arcade_mix(200, 100), (255, 85));
+        assert_eq!(arcade_mix(-200, 100), (-85, -255));
+    }
+}
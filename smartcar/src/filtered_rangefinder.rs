@@ -0,0 +1,200 @@
+//! Filtering for the readings of the HC-SR04 distance sensor.
+//!
+//! Single pings often come back as `Unknown`, or much too short when the echo bounces off something on the way.
+//! The [FilteredRangefinder] takes several pings in a row and combines them:
+//!
+//! - `Unknown` pings mean the sensor did not react, so they are not counted as votes at all.
+//! - If more pings were measured than were `Infinity`, the result is the median of the measured distances,
+//!   so a few short bounces do not move it.
+//! - Otherwise, if there were any `Infinity` pings, the result is `Infinity`.
+//! - If every ping was `Unknown`, so is the result.
+//!
+//! The result is confident when the winning kind of reading got more than half of all the pings,
+//! and (for measured distances) the readings are close enough together.
+//!
+//! Like the modes, the filter does not own the sensor, so that it can share the sensor
+//! with the [RangeScanner](crate::range_scanner::RangeScanner); the sensor is passed to every call instead.
+
+use core::convert::Infallible;
+
+use ufmt::derive::uDebug;
+
+use crate::clock;
+use crate::hal::{EchoTimer, OutputPin};
+use crate::hc_sr04_distance_sensor::{Distance, DistanceMeasurement, HC_SR04, MeasurementError, MIN_CYCLE_MS};
+use crate::sound_speed::SoundSpeed;
+
+/// The most pings that can be combined into one reading.
+pub const MAX_SAMPLES: usize = 9;
+
+/// A single ping, with the distance as the number of timer ticks.
+#[derive(Clone, Copy)]
+enum Sample {
+    Measured(u16),
+    Infinity,
+    Unknown,
+}
+
+/// The combined result of several pings.
+#[derive(uDebug, Debug)]
+pub struct FilteredMeasurement {
+    /// The median distance, or `Infinity`/`Unknown` if that is what most of the pings said.
+    pub distance: DistanceMeasurement,
+    /// The difference between the longest and the shortest measured distance, in mm.
+    pub spread_mm: u64,
+    /// Whether the pings agreed well enough to trust the result.
+    pub confident: bool,
+}
+
+/// Combines several pings of an [HC_SR04] into a single reading.
+pub struct FilteredRangefinder {
+    sample_count: usize,
+    max_spread_mm: u64,
+    samples: [Sample; MAX_SAMPLES],
+    taken: usize,
+    running: bool,
+    in_flight: bool,
+    next_ping_ms: u64,
+}
+
+impl FilteredRangefinder {
+    /// Creates a new filter taking `sample_count` pings per reading (at most [MAX_SAMPLES]).
+    ///
+    /// Readings whose measured distances differ by more than `max_spread_mm` are not confident.
+    pub fn new(sample_count: usize, max_spread_mm: u64) -> Self {
+        Self {
+            sample_count: sample_count.clamp(1, MAX_SAMPLES),
+            max_spread_mm,
+            samples: [Sample::Unknown; MAX_SAMPLES],
+            taken: 0,
+            running: false,
+            in_flight: false,
+            next_ping_ms: 0,
+        }
+    }
+
+    /// Start taking a reading in the background, abandoning any reading in progress.
+    ///
+    /// Use [FilteredRangefinder::poll] to take the pings and get the result.
+    pub fn start(&mut self) {
+        self.taken = 0;
+        self.running = true;
+        self.in_flight = false;
+        self.next_ping_ms = clock::millis();
+    }
+
+    /// Take the next ping if it is time to, and return the result once all the pings are taken.
+    ///
+    /// This must be called often, because the sensor itself needs to be polled, see [HC_SR04::poll].
+    /// If the sensor fails (for example because something else took the result of the ping),
+    /// the error is returned and the ping is forgotten, so polling again takes it again.
+    pub fn poll<P, T>(&mut self, sensor: &mut HC_SR04<P, T>) -> nb::Result<FilteredMeasurement, MeasurementError>
+    where
+        P: OutputPin<Error = Infallible>,
+        T: EchoTimer,
+    {
+        if !self.running {
+            return Err(nb::Error::Other(MeasurementError::NotStarted));
+        }
+
+        if self.in_flight {
+            let sample = match sensor.poll() {
+                Ok(DistanceMeasurement::Measured(distance)) => Sample::Measured(distance.ticks()),
+                Ok(DistanceMeasurement::Infinity) => Sample::Infinity,
+                Ok(DistanceMeasurement::Unknown) => Sample::Unknown,
+                Err(nb::Error::WouldBlock) => return Err(nb::Error::WouldBlock),
+                Err(nb::Error::Other(error)) => {
+                    self.in_flight = false;
+                    return Err(nb::Error::Other(error));
+                },
+            };
+            self.samples[self.taken] = sample;
+            self.taken += 1;
+            self.in_flight = false;
+        }
+
+        if self.taken == self.sample_count {
+            self.running = false;
+            return Ok(self.combine(sensor.sound_speed()));
+        }
+
+        let now = clock::millis();
+        if now >= self.next_ping_ms {
+            sensor.start_measurement();
+            self.in_flight = true;
+            self.next_ping_ms = now + MIN_CYCLE_MS;
+        }
+
+        Err(nb::Error::WouldBlock)
+    }
+
+    /// Take a reading, blocking until all the pings are taken.
+    pub fn measure<P, T>(&mut self, sensor: &mut HC_SR04<P, T>) -> FilteredMeasurement
+    where
+        P: OutputPin<Error = Infallible>,
+        T: EchoTimer,
+    {
+        self.start();
+        // Nothing else can take the result of a ping while this blocks, so the sensor cannot fail.
+        match nb::block!(self.poll(sensor)) {
+            Ok(measurement) => measurement,
+            Err(MeasurementError::NotStarted) => FilteredMeasurement {
+                distance: DistanceMeasurement::Unknown,
+                spread_mm: 0,
+                confident: false,
+            },
+        }
+    }
+
+    /// Combine the pings taken so far into a single reading.
+    fn combine(&self, sound_speed: SoundSpeed) -> FilteredMeasurement {
+        let samples = &self.samples[..self.taken];
+
+        let mut measured = [0u16; MAX_SAMPLES];
+        let mut measured_count = 0;
+        let mut infinity_count = 0;
+        for sample in samples {
+            match *sample {
+                Sample::Measured(ticks) => {
+                    measured[measured_count] = ticks;
+                    measured_count += 1;
+                },
+                Sample::Infinity => infinity_count += 1,
+                Sample::Unknown => {},
+            }
+        }
+
+        // More than half of all the pings, including the unknown ones, must agree.
+        let majority = samples.len() / 2 + 1;
+
+        if measured_count > 0 && measured_count > infinity_count {
+            let measured = &mut measured[..measured_count];
+            measured.sort_unstable();
+
+            let lower = measured[(measured_count - 1) / 2] as u32;
+            let upper = measured[measured_count / 2] as u32;
+            let median = ((lower + upper) / 2) as u16;
+
+            let spread = measured[measured_count - 1] - measured[0];
+            let spread_mm = Distance::new(spread, sound_speed).to_mm();
+
+            FilteredMeasurement {
+                distance: DistanceMeasurement::Measured(Distance::new(median, sound_speed)),
+                spread_mm,
+                confident: measured_count >= majority && spread_mm <= self.max_spread_mm,
+            }
+        } else if infinity_count > 0 {
+            FilteredMeasurement {
+                distance: DistanceMeasurement::Infinity,
+                spread_mm: 0,
+                confident: infinity_count >= majority,
+            }
+        } else {
+            FilteredMeasurement {
+                distance: DistanceMeasurement::Unknown,
+                spread_mm: 0,
+                confident: false,
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Running the line following behavior on the robot.
+//!
+//! This connects the steering logic from [crate::line_following] to the line tracker and the motors,
+//! through the [MeasureLine] and [Drive] traits, so it runs the same with either kit's drivers.
+
+use crate::clock;
+use crate::differential_drive::Drive;
+use crate::line_tracker::{LineBiasDirection, MeasureLine};
+use crate::line_following::{FollowConfig, LineColor, LineFollower, PidFollower};
+use crate::pid::PidGains;
+
+/// How the line following mode steers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Steering {
+    /// A fixed turn rate for every position of the line, see [LineFollower].
+    Discrete,
+    /// A PID controller on the estimated position error, see [PidFollower].
+    Pid,
+}
+
+/// The line following mode.
+///
+/// It does not own the hardware, so that the robot can switch between modes;
+/// call [FollowMode::update] as often as possible with the drivers.
+pub struct FollowMode {
+    steering: Steering,
+    follower: LineFollower,
+    pid_follower: PidFollower,
+}
+
+impl FollowMode {
+    pub fn new(config: FollowConfig, steering: Steering) -> Self {
+        Self {
+            steering,
+            follower: LineFollower::new(config),
+            pid_follower: PidFollower::new(config, PidFollower::DEFAULT_GAINS),
+        }
+    }
+
+    /// Get the discrete steering logic.
+    pub fn follower(&mut self) -> &mut LineFollower {
+        &mut self.follower
+    }
+
+    /// Get the PID steering logic, for example to tune its gains.
+    pub fn pid_follower(&mut self) -> &mut PidFollower {
+        &mut self.pid_follower
+    }
+
+    pub fn steering(&self) -> Steering {
+        self.steering
+    }
+
+    /// Start over, forgetting the line seen before, for example when the mode is entered.
+    pub fn restart(&mut self) {
+        self.follower = LineFollower::new(*self.follower.config());
+        self.pid_follower.reset();
+    }
+
+    /// Switch between discrete and PID steering.
+    pub fn set_steering(&mut self, steering: Steering) {
+        if steering != self.steering {
+            self.pid_follower.pid().reset();
+        }
+        self.steering = steering;
+    }
+
+    /// Change whether the line is dark or light, for both kinds of steering.
+    pub fn set_line_color(&mut self, line_color: LineColor) {
+        self.follower.set_line_color(line_color);
+        let mut config = *self.pid_follower.config();
+        config.line_color = line_color;
+        self.pid_follower.set_config(config);
+    }
+
+    /// Change the PID gains.
+    pub fn set_pid_gains(&mut self, gains: PidGains) {
+        self.pid_follower.pid().set_gains(gains);
+    }
+
+    /// Read the line tracker and steer toward the line.
+    ///
+    /// Returns where the line was seen, for logging.
+    pub fn update<C: Drive, L: MeasureLine>(&mut self, chassis: &mut C, line_tracker: &mut L) -> LineBiasDirection {
+        let position = line_tracker.measure_full();
+        let bias = self.follower.bias(&position);
+        let (left, right) = match self.steering {
+            Steering::Discrete => self.follower.steer(bias),
+            Steering::Pid => self.pid_follower.steer(bias, clock::millis()),
+        };
+        chassis.drive(left, right);
+        bias
+    }
+}
@@ -0,0 +1,63 @@
+//! Running a grid route on the robot.
+//!
+//! This connects the [RouteExecutor] from [crate::grid_route] to the line tracker and the motors,
+//! through the [MeasureLine] and [Drive] traits, like [crate::follow_mode] does for the line follower.
+
+use crate::clock;
+use crate::differential_drive::Drive;
+use crate::grid_route::{GridConfig, Route, RouteAction, RouteExecutor, StepReport};
+use crate::line_following::LineColor;
+use crate::line_tracker::MeasureLine;
+
+/// The grid navigation mode.
+///
+/// It does not own the hardware, so that the robot can switch between modes;
+/// call [GridMode::update] as often as possible with the drivers.
+pub struct GridMode {
+    executor: RouteExecutor,
+}
+
+impl GridMode {
+    pub fn new(config: GridConfig) -> Self {
+        Self {
+            executor: RouteExecutor::new(config),
+        }
+    }
+
+    /// Start carrying out a route.
+    pub fn start(&mut self, route: Route) {
+        self.executor.start(route, clock::millis());
+    }
+
+    /// Change the color of the line that the routes follow.
+    pub fn set_line_color(&mut self, line_color: LineColor) {
+        self.executor.set_line_color(line_color);
+    }
+
+    /// Get the route executor, for example to abort the route.
+    pub fn executor(&mut self) -> &mut RouteExecutor {
+        &mut self.executor
+    }
+
+    /// Read the line tracker, move the route along and drive the motors.
+    ///
+    /// Returns the report of the step that just finished, if any, so that it can be sent to the host.
+    /// Once the route has stopped, the wheels are left to coast, and nothing else happens until the next route.
+    pub fn update<C: Drive, L: MeasureLine>(&mut self, chassis: &mut C, line_tracker: &mut L) -> Option<StepReport> {
+        if self.executor.is_stopped() {
+            return None;
+        }
+
+        let bias = self.executor.config().follow.line_color.bias(&line_tracker.measure_full());
+        let (action, report) = self.executor.update(clock::millis(), bias);
+
+        match action {
+            RouteAction::Drive(left, right) => chassis.drive(left, right),
+            RouteAction::Brake => chassis.brake(),
+        }
+        if report.is_some() && self.executor.is_stopped() {
+            chassis.coast();
+        }
+        report
+    }
+}
@@ -0,0 +1,426 @@
+//! Navigation on a grid of lines by counting crossings.
+//!
+//! A [Route] is a list of [GridMove]s, such as "go 3 crossings forward, turn left, go 2 crossings".
+//! The [RouteExecutor] carries them out one by one: it follows the line with a [LineFollower],
+//! counts crossings with a [LineEventDetector], and turns at crossings by spinning in place
+//! until the center sensor finds the next line.
+//!
+//! Every finished step is reported as a [StepReport], whether it succeeded or failed;
+//! a failed step stops the route.
+//!
+//! The routes themselves are defined in `smartcar_protocol::route`, since they are sent with the `route` command.
+//!
+//! See [crate::grid_mode] for the part that runs it on the robot.
+
+use core::fmt;
+
+use ufmt::derive::uDebug;
+use ufmt::uDisplay;
+
+use crate::line_events::{LineEventDetector, LineEventKind};
+use crate::line_following::{FollowConfig, LineColor, LineFollower};
+use crate::line_tracker::LineBiasDirection;
+
+pub use smartcar_protocol::route::{GridMove, Route, MAX_ROUTE_MOVES};
+
+/// The settings of the route executor.
+#[derive(Debug, Clone, Copy)]
+pub struct GridConfig {
+    /// How the line is followed between crossings.
+    pub follow: FollowConfig,
+    /// How long a crossing or a lost line must be seen to be believed, in ms.
+    pub debounce_ms: u64,
+    /// How long to keep driving after reaching the last crossing of a forward move,
+    /// so that the wheels (and not the sensors, which are in front of them) end up on the crossing, in ms.
+    pub align_ms: u64,
+    /// The wheel command when spinning in place to turn.
+    pub turn_speed: i16,
+    /// How long to spin before looking for the next line, so that the current one is left behind, in ms.
+    pub turn_leave_ms: u64,
+    /// If a turn does not find a line in this time, it fails, in ms.
+    pub turn_timeout_ms: u64,
+    /// If the line stays lost for this long during a forward move, it fails, in ms.
+    pub lost_timeout_ms: u64,
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        Self {
+            follow: FollowConfig::default(),
+            debounce_ms: 30,
+            align_ms: 250,
+            turn_speed: 170,
+            turn_leave_ms: 300,
+            turn_timeout_ms: 3000,
+            lost_timeout_ms: 1500,
+        }
+    }
+}
+
+/// Why a step failed.
+#[derive(uDebug, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepFailure {
+    /// The line was lost during a forward move, and not found again in time.
+    LineLost,
+    /// A turn did not find the next line in time.
+    TurnTimeout,
+}
+
+/// The report of a finished step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepReport {
+    /// The index of the step in the route, starting from 0.
+    pub index: usize,
+    pub grid_move: GridMove,
+    pub result: Result<(), StepFailure>,
+}
+
+impl StepFailure {
+    /// The reason, as it is written in the step report.
+    pub fn description(&self) -> &'static str {
+        match self {
+            StepFailure::LineLost => "line lost",
+            StepFailure::TurnTimeout => "turn timeout",
+        }
+    }
+}
+
+/// Writes the report as it is sent over the serial port, for example `STEP 2 l: OK` or `STEP 3 f2: FAIL line lost`.
+///
+/// Steps are numbered from 1, and the move is written as in the `route` command.
+impl uDisplay for StepReport {
+    fn fmt<W>(&self, f: &mut ufmt::Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: ufmt::uWrite + ?Sized,
+    {
+        ufmt::uwrite!(f, "STEP {} ", self.index + 1)?;
+        match self.grid_move {
+            GridMove::Forward(crossings) => ufmt::uwrite!(f, "f{}", crossings)?,
+            GridMove::TurnLeft => f.write_str("l")?,
+            GridMove::TurnRight => f.write_str("r")?,
+        }
+        match self.result {
+            Ok(()) => f.write_str(": OK"),
+            Err(failure) => ufmt::uwrite!(f, ": FAIL {}", failure.description()),
+        }
+    }
+}
+
+/// The same text as the `ufmt` implementation, for the host.
+impl fmt::Display for StepReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "STEP {} {}", self.index + 1, self.grid_move)?;
+        match self.result {
+            Ok(()) => f.write_str(": OK"),
+            Err(failure) => write!(f, ": FAIL {}", failure.description()),
+        }
+    }
+}
+
+/// What the motors should do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteAction {
+    Drive(i16, i16),
+    Brake,
+}
+
+/// The progress of the current step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepState {
+    /// Following the line and counting crossings.
+    Following,
+    /// Driving straight onto the last crossing until the given time.
+    Aligning { until_ms: u64 },
+    /// Spinning away from the current line, until the given time.
+    LeavingLine { until_ms: u64 },
+    /// Spinning until the center sensor sees a line.
+    SeekingLine,
+}
+
+/// Carries out a [Route].
+pub struct RouteExecutor {
+    config: GridConfig,
+    route: Route,
+    /// The index of the current step, or the length of the route if it is finished.
+    index: usize,
+    state: StepState,
+    /// The time when the current step started.
+    step_started_ms: u64,
+    /// The time when the line was lost during a forward move.
+    lost_since_ms: Option<u64>,
+    follower: LineFollower,
+    events: LineEventDetector,
+    /// Whether the route has stopped, because it finished or a step failed.
+    stopped: bool,
+}
+
+impl RouteExecutor {
+    pub fn new(config: GridConfig) -> Self {
+        Self {
+            config,
+            route: Route::new(),
+            index: 0,
+            state: StepState::Following,
+            step_started_ms: 0,
+            lost_since_ms: None,
+            follower: LineFollower::new(config.follow),
+            events: LineEventDetector::new(config.debounce_ms),
+            stopped: true,
+        }
+    }
+
+    pub fn config(&self) -> &GridConfig {
+        &self.config
+    }
+
+    /// Change the color of the line that is followed, for this route and the following ones.
+    pub fn set_line_color(&mut self, line_color: LineColor) {
+        self.config.follow.line_color = line_color;
+        self.follower.set_line_color(line_color);
+    }
+
+    /// Start carrying out a route from its first step, at the given time.
+    pub fn start(&mut self, route: Route, now_ms: u64) {
+        self.route = route;
+        self.stopped = false;
+        self.begin_step(0, now_ms);
+    }
+
+    /// Stop the route where it is.
+    pub fn abort(&mut self) {
+        self.stopped = true;
+    }
+
+    /// Whether the route has stopped, because it finished, a step failed, or it was aborted.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// The index of the current step.
+    pub fn current_step(&self) -> usize {
+        self.index
+    }
+
+    /// Feed a line tracker reading at the given time, and get what the motors should do,
+    /// along with the report of the step that just finished, if any.
+    pub fn update(&mut self, now_ms: u64, bias: LineBiasDirection) -> (RouteAction, Option<StepReport>) {
+        if self.stopped {
+            return (RouteAction::Brake, None);
+        }
+        let grid_move = self.route.moves()[self.index];
+
+        match (grid_move, self.state) {
+            (GridMove::Forward(crossings), StepState::Following) => {
+                let wheels = self.follower.steer(bias);
+
+                for event in self.events.update(now_ms, bias) {
+                    match event.kind {
+                        LineEventKind::LineLost => self.lost_since_ms = Some(now_ms),
+                        LineEventKind::LineReacquired => self.lost_since_ms = None,
+                        _ => {},
+                    }
+                }
+                if let Some(lost_since_ms) = self.lost_since_ms {
+                    if now_ms.saturating_sub(lost_since_ms) >= self.config.lost_timeout_ms {
+                        return self.finish_step(now_ms, Err(StepFailure::LineLost));
+                    }
+                }
+
+                if self.events.crossings() >= crossings as u16 {
+                    self.state = StepState::Aligning { until_ms: now_ms + self.config.align_ms };
+                    let speed = self.config.follow.base_speed;
+                    return (RouteAction::Drive(speed, speed), None);
+                }
+
+                (RouteAction::Drive(wheels.0, wheels.1), None)
+            },
+            (GridMove::Forward(_), StepState::Aligning { until_ms }) => {
+                if now_ms >= until_ms {
+                    return self.finish_step(now_ms, Ok(()));
+                }
+                let speed = self.config.follow.base_speed;
+                (RouteAction::Drive(speed, speed), None)
+            },
+            (GridMove::TurnLeft, _) | (GridMove::TurnRight, _) => {
+                if now_ms.saturating_sub(self.step_started_ms) >= self.config.turn_timeout_ms {
+                    return self.finish_step(now_ms, Err(StepFailure::TurnTimeout));
+                }
+
+                if let StepState::LeavingLine { until_ms } = self.state {
+                    if now_ms >= until_ms {
+                        self.state = StepState::SeekingLine;
+                    }
+                } else if Self::center_sees_line(bias) {
+                    return self.finish_step(now_ms, Ok(()));
+                }
+
+                let speed = self.config.turn_speed;
+                let wheels = if grid_move == GridMove::TurnLeft { (-speed, speed) } else { (speed, -speed) };
+                (RouteAction::Drive(wheels.0, wheels.1), None)
+            },
+            // A forward move is never in a turning state, since the state is reset at the start of every step.
+            (GridMove::Forward(_), _) => {
+                self.state = StepState::Following;
+                (RouteAction::Brake, None)
+            },
+        }
+    }
+
+    /// Whether the center sensor sees the line.
+    fn center_sees_line(bias: LineBiasDirection) -> bool {
+        matches!(
+            bias,
+            LineBiasDirection::SlightlyLeft | LineBiasDirection::Center
+                | LineBiasDirection::SlightlyRight | LineBiasDirection::OnPerpendicularLine
+        )
+    }
+
+    /// Report the current step, and move on to the next one if it succeeded.
+    fn finish_step(&mut self, now_ms: u64, result: Result<(), StepFailure>) -> (RouteAction, Option<StepReport>) {
+        let report = StepReport {
+            index: self.index,
+            grid_move: self.route.moves()[self.index],
+            result,
+        };
+
+        let next = self.index + 1;
+        if result.is_err() || next == self.route.moves().len() {
+            self.stopped = true;
+        } else {
+            self.begin_step(next, now_ms);
+        }
+
+        (RouteAction::Brake, Some(report))
+    }
+
+    fn begin_step(&mut self, index: usize, now_ms: u64) {
+        self.index = index;
+        self.step_started_ms = now_ms;
+        self.lost_since_ms = None;
+        self.events = LineEventDetector::new(self.config.debounce_ms);
+        self.follower = LineFollower::new(self.config.follow);
+
+        if self.index >= self.route.moves().len() {
+            self.stopped = true;
+            return;
+        }
+
+        self.state = match self.route.moves()[index] {
+            GridMove::Forward(_) => StepState::Following,
+            GridMove::TurnLeft | GridMove::TurnRight => StepState::LeavingLine {
+                until_ms: now_ms + self.config.turn_leave_ms,
+            },
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use LineBiasDirection::{Center, NotOnLine, OnPerpendicularLine, SlightlyRight, VeryLeft};
+
+    fn route(moves: &[GridMove]) -> Route {
+        let mut route = Route::new();
+        for &grid_move in moves {
+            route.push(grid_move);
+        }
+        route
+    }
+
+    /// Feed the same reading every 10ms in `from_ms..to_ms`, and get the last action and any report on the way.
+    fn feed(
+        executor: &mut RouteExecutor,
+        from_ms: u64,
+        to_ms: u64,
+        bias: LineBiasDirection,
+    ) -> (RouteAction, Option<StepReport>) {
+        let mut last = (RouteAction::Brake, None);
+        for now_ms in (from_ms..to_ms).step_by(10) {
+            let (action, report) = executor.update(now_ms, bias);
+            last = (action, report.or(last.1));
+        }
+        last
+    }
+
+    #[test]
+    fn counts_crossings_then_aligns() {
+        let mut executor = RouteExecutor::new(GridConfig::default());
+        executor.start(route(&[GridMove::Forward(2), GridMove::TurnRight]), 0);
+        assert!(!executor.is_stopped());
+
+        // Following the line, and steering toward it.
+        assert_eq!(feed(&mut executor, 0, 100, Center), (RouteAction::Drive(140, 140), None));
+        assert_eq!(feed(&mut executor, 100, 150, SlightlyRight), (RouteAction::Drive(200, 80), None));
+
+        // A glimpse of a crossing is not counted, but a stable one is.
+        feed(&mut executor, 150, 170, OnPerpendicularLine);
+        feed(&mut executor, 170, 300, Center);
+        feed(&mut executor, 300, 400, OnPerpendicularLine);
+        assert_eq!(feed(&mut executor, 400, 500, Center), (RouteAction::Drive(140, 140), None));
+        assert_eq!(executor.current_step(), 0);
+
+        // The second crossing ends the counting, and the robot drives straight onto it,
+        // then starts turning right away.
+        feed(&mut executor, 500, 540, OnPerpendicularLine);
+        assert_eq!(feed(&mut executor, 540, 780, VeryLeft), (RouteAction::Drive(140, 140), None));
+        let report = StepReport { index: 0, grid_move: GridMove::Forward(2), result: Ok(()) };
+        assert_eq!(feed(&mut executor, 780, 800, VeryLeft), (RouteAction::Drive(170, -170), Some(report)));
+        assert_eq!(executor.current_step(), 1);
+    }
+
+    #[test]
+    fn turns_onto_the_next_line() {
+        let mut executor = RouteExecutor::new(GridConfig::default());
+        executor.start(route(&[GridMove::TurnLeft]), 1000);
+
+        // The line that the robot starts on is ignored while it spins away from it.
+        assert_eq!(feed(&mut executor, 1000, 1300, Center), (RouteAction::Drive(-170, 170), None));
+        assert_eq!(feed(&mut executor, 1300, 1600, NotOnLine), (RouteAction::Drive(-170, 170), None));
+
+        let report = StepReport { index: 0, grid_move: GridMove::TurnLeft, result: Ok(()) };
+        assert_eq!(executor.update(1600, SlightlyRight), (RouteAction::Brake, Some(report)));
+        assert!(executor.is_stopped());
+        assert_eq!(executor.update(1610, Center), (RouteAction::Brake, None));
+    }
+
+    #[test]
+    fn lost_line_fails_the_step_and_stops_the_route() {
+        let mut executor = RouteExecutor::new(GridConfig::default());
+        executor.start(route(&[GridMove::Forward(1), GridMove::TurnRight]), 0);
+        feed(&mut executor, 0, 100, Center);
+
+        // Losing the line for a moment is fine.
+        feed(&mut executor, 100, 1000, NotOnLine);
+        feed(&mut executor, 1000, 1100, Center);
+        assert!(!executor.is_stopped());
+
+        // Losing it for longer is not. The line is believed lost 30ms after it was last seen.
+        let (_, report) = feed(&mut executor, 1100, 2620, NotOnLine);
+        assert_eq!(report, None);
+        let report = StepReport { index: 0, grid_move: GridMove::Forward(1), result: Err(StepFailure::LineLost) };
+        assert_eq!(executor.update(2630, NotOnLine), (RouteAction::Brake, Some(report)));
+        assert!(executor.is_stopped());
+        assert_eq!(executor.current_step(), 0);
+    }
+
+    #[test]
+    fn turn_without_a_line_times_out() {
+        let mut executor = RouteExecutor::new(GridConfig::default());
+        executor.start(route(&[GridMove::TurnRight, GridMove::Forward(1)]), 0);
+        assert_eq!(feed(&mut executor, 0, 3000, NotOnLine), (RouteAction::Drive(170, -170), None));
+
+        let report = StepReport { index: 0, grid_move: GridMove::TurnRight, result: Err(StepFailure::TurnTimeout) };
+        assert_eq!(executor.update(3000, NotOnLine), (RouteAction::Brake, Some(report)));
+        assert!(executor.is_stopped());
+    }
+
+    #[test]
+    fn aborted_route_brakes() {
+        let mut executor = RouteExecutor::new(GridConfig::default());
+        assert!(executor.is_stopped());
+        executor.start(route(&[GridMove::Forward(3)]), 0);
+        executor.abort();
+        assert_eq!(executor.update(10, Center), (RouteAction::Brake, None));
+    }
+}
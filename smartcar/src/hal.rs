@@ -0,0 +1,46 @@
+//! The hardware traits that the drivers are generic over.
+//!
+//! Plain pins use the `embedded_hal` digital traits, and the outputs that are driven by a hardware timer
+//! (the motor enable pins and the servo) use [PwmPin]. The things that `embedded_hal` has no fitting trait for
+//! are timestamping the echo of the HC-SR04, which is the [EchoTimer] here,
+//! and reading the analog line sensors of the V4 kit, which are the [AnalogLineSensors].
+//!
+//! The pins of the robot cannot fail, so the drivers require pins with an [Infallible] error type.
+
+use core::convert::Infallible;
+
+use crate::line_tracker::LineTrackerDirection;
+
+pub use embedded_hal::digital::v2::{InputPin, OutputPin};
+pub use embedded_hal::PwmPin;
+
+/// A free-running timer that counts every 4µs, and timestamps the edges of the HC-SR04's Echo pin.
+///
+/// The edges must be timestamped as they happen, usually in an interrupt, because the main loop
+/// is much too slow to see a 4µs difference.
+pub trait EchoTimer {
+    /// The current count, which wraps around.
+    fn now(&self) -> u16;
+
+    /// Forget the edges recorded so far, and record the next rising edge and the falling edge after it.
+    fn start_capture(&mut self);
+
+    /// The counts at the recorded rising edge and at the falling edge after it, if they have happened yet.
+    fn edges(&self) -> (Option<u16>, Option<u16>);
+}
+
+/// The three analog reflectance sensors of a line tracker, read through an ADC.
+///
+/// The `embedded_hal` ADC trait is generic over a single pin type, which does not fit three pins of different types.
+pub trait AnalogLineSensors {
+    /// Read the sensor in the given direction, as a 10-bit value that is higher over a darker surface.
+    fn read(&mut self, direction: LineTrackerDirection) -> u16;
+}
+
+/// Get the value out of the result of an infallible pin operation.
+pub(crate) fn infallible<T>(result: Result<T, Infallible>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(never) => match never {},
+    }
+}
@@ -0,0 +1,297 @@
+//! The HC-SR04 is an ultrasonic distance sensor.
+//! 
+//! It has two pins, Trig and Echo.
+//! When you send a pulse on the Trig pin, the sensor will emit an ultrasonic pulse,
+//! and the Echo pin will be high for a certain amount of time.
+//! When the echo comes back, the Echo pin will go low, and the time that it was high for
+//! is twice the distance between the sensor and the object.
+//! 
+//! For measuring the distance accurately, the edges of the Echo pin are timestamped with an [EchoTimer],
+//! which has a resolution of 4µs, which corresponds to a distance of about 686µm per tick at 20°C.
+//! The speed of sound depends on the air temperature, which can be set with [HC_SR04::set_sound_speed].
+//! The sensor measures distances between 2cm and about 4m. 
+//!
+//! The measurement does not block: [HC_SR04::start_measurement] pulses the Trig pin,
+//! and then the timer records the edges of the Echo pin in the background.
+//! Call [HC_SR04::poll] to get the result once it is ready.
+//! On the robot, the timer is TC1 with the pin change interrupt of the Echo pin, see the firmware's `echo_timer` module.
+
+use core::convert::Infallible;
+use core::fmt;
+
+use smartcar_protocol::DistanceReading;
+use ufmt::derive::uDebug;
+use ufmt::uDisplay;
+
+use crate::hal::{infallible, EchoTimer, OutputPin};
+use crate::sound_speed::SoundSpeed;
+
+/// The length of a tick of the [EchoTimer], in µs.
+pub const TICK_US: u64 = 4;
+
+/// The minimum time between pings, in ms, as recommended by the HC-SR04 datasheet.
+///
+/// This lets the echoes of the previous ping die down before the next one.
+pub const MIN_CYCLE_MS: u64 = 60;
+
+/// Waiting for the timer to count 4 ticks holds the Trig pin high for at least 12µs; the datasheet asks for 10µs.
+const TRIGGER_PULSE_TICKS: u16 = 4;
+
+/// If the Echo pin does not go high within 750µs after the trigger, the sensor did not react.
+/// 750µs / (4µs per tick) = 187.5 = 188 ticks.
+const RISE_TIMEOUT_TICKS: u16 = 188;
+
+/// If the Echo pin stays high for more than 100ms, the distance is too large.
+/// 100ms / (4µs per tick) = 25000 ticks.
+const FALL_TIMEOUT_TICKS: u16 = 25000;
+
+/// This struct represents a HC-SR04 sensor, holding the Trig pin, and the timer that watches the Echo pin.
+#[allow(non_camel_case_types)]
+pub struct HC_SR04<P, T> {
+    trigger_pin: P,
+    timer: T,
+    /// Whether a measurement was started, and its result has not been returned yet.
+    measuring: bool,
+    /// The timer tick at which the Trig pin was last pulsed.
+    trigger_tick: u16,
+    /// The speed of sound used for the measurements.
+    sound_speed: SoundSpeed,
+    /// The result of the last finished measurement.
+    last_measurement: Option<DistanceMeasurement>,
+}
+
+/// The reasons that [HC_SR04::poll] can fail, other than the measurement not being ready yet.
+#[derive(uDebug, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeasurementError {
+    /// [HC_SR04::start_measurement] was not called, or its result has already been returned.
+    NotStarted,
+}
+
+/// A measurement can come back with three states, and they are represented by this enum.
+#[derive(uDebug, Debug, Clone, Copy)]
+pub enum DistanceMeasurement {
+    /// The measurement was successful, and its [`Distance`] is included.
+    Measured(Distance),
+    /// The sensor's Echo pin was high for too long (more than 100ms, which corresponds to a distance of 17 meters),
+    /// and we say the distance is too large.
+    Infinity,
+    /// When we pulsed the Trig pin, the Echo pin did not go high for a while (750µs),
+    /// which means that the sensor didn't react to the pulse.
+    Unknown,
+}
+
+/// A value of a distance measurement.
+/// Holds the number of timer ticks spent by the echo pin being high, and the speed of sound at the time of the measurement.
+#[derive(Clone, Copy)]
+pub struct Distance {
+    ticks: u16,  // bidirectional ticks, to get distance divide by 2
+    sound_speed: SoundSpeed,
+}
+
+impl Distance {
+    /// Creates a distance from the number of timer ticks that the echo pin was high for.
+    pub fn new(ticks: u16, sound_speed: SoundSpeed) -> Self {
+        Self { ticks, sound_speed }
+    }
+
+    /// Returns the distance in micrometers.
+    pub fn to_um(&self) -> u64 {
+        // 1 tick = 4µs; at 20°C, that is 1.3737mm there and back,
+        // divide by 2 -> 0.68684mm = 686.84µm.
+        // https://www.wolframalpha.com/input?i=4%C2%B5s+speed+of+sound
+
+        // NOTE: we would prefer float values, but any program using them will halt at startup.
+
+        self.sound_speed.round_trip_us_to_um(self.ticks as u64 * TICK_US)
+    }
+    
+    /// Returns the distance in millimeter.
+    pub fn to_mm(&self) -> u64 {
+        self.to_um() / 1000
+    }
+
+    /// Returns the number of timer ticks that the echo pin was high for.
+    pub fn ticks(&self) -> u16 {
+        self.ticks
+    }
+}
+
+/// Convert a measurement to the form that is sent to the host, in whole millimeters.
+impl From<DistanceMeasurement> for DistanceReading {
+    fn from(measurement: DistanceMeasurement) -> Self {
+        match measurement {
+            DistanceMeasurement::Measured(distance) => {
+                DistanceReading::Measured(distance.to_mm().min(u16::MAX as u64) as u16)
+            },
+            DistanceMeasurement::Infinity => DistanceReading::Infinity,
+            DistanceMeasurement::Unknown => DistanceReading::Unknown,
+        }
+    }
+}
+
+impl ufmt::uDebug for Distance {
+    fn fmt<W>(&self, f: &mut ufmt::Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: ufmt::uWrite + ?Sized,
+    {
+        f.debug_struct("Distance")?
+            .field("ticks", &self.ticks)?
+            .field("mm_per_sec", &self.sound_speed.mm_per_sec())?
+            .finish()
+    }
+}
+
+impl uDisplay for Distance {
+    fn fmt<W>(&self, f: &mut ufmt::Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: ufmt::uWrite + ?Sized,
+    {
+        ufmt::uwrite!(f, "{}mm", self.to_mm())
+    }
+}
+
+impl uDisplay for DistanceMeasurement {
+    fn fmt<W>(&self, f: &mut ufmt::Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: ufmt::uWrite + ?Sized,
+    {
+        match self {
+            DistanceMeasurement::Infinity => f.write_str("∞"),
+            DistanceMeasurement::Unknown => f.write_str("Ø"),
+            DistanceMeasurement::Measured(distance) => uDisplay::fmt(distance, f),
+        }
+    }
+}
+
+/// The same as the `ufmt` implementations, for the host.
+impl fmt::Debug for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Distance")
+            .field("ticks", &self.ticks)
+            .field("mm_per_sec", &self.sound_speed.mm_per_sec())
+            .finish()
+    }
+}
+
+impl fmt::Display for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}mm", self.to_mm())
+    }
+}
+
+impl fmt::Display for DistanceMeasurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DistanceMeasurement::Infinity => f.write_str("∞"),
+            DistanceMeasurement::Unknown => f.write_str("Ø"),
+            DistanceMeasurement::Measured(distance) => fmt::Display::fmt(distance, f),
+        }
+    }
+}
+
+impl<P, T> HC_SR04<P, T>
+where
+    P: OutputPin<Error = Infallible>,
+    T: EchoTimer,
+{
+    /// Creates a new HC-SR04 driver from the Trig pin, and the timer that watches the Echo pin.
+    pub fn new(mut trigger_pin: P, timer: T) -> Self {
+        infallible(trigger_pin.set_low());
+
+        Self {
+            trigger_pin,
+            timer,
+            measuring: false,
+            trigger_tick: 0,
+            sound_speed: SoundSpeed::default(),
+            last_measurement: None,
+        }
+    }
+
+    /// Set the speed of sound to use for the following measurements,
+    /// for example with [SoundSpeed::from_celsius] from a configured or measured air temperature.
+    pub fn set_sound_speed(&mut self, sound_speed: SoundSpeed) {
+        self.sound_speed = sound_speed;
+    }
+
+    /// Get the speed of sound used for the measurements.
+    pub fn sound_speed(&self) -> SoundSpeed {
+        self.sound_speed
+    }
+
+    /// Send an acoustic pulse, starting a measurement in the background.
+    ///
+    /// Use [HC_SR04::poll] to get the result.
+    /// If a measurement is already in progress, it is abandoned.
+    /// The datasheet recommends waiting at least [MIN_CYCLE_MS] between measurements, so that old echoes die down.
+    pub fn start_measurement(&mut self) {
+        self.timer.start_capture();
+
+        // Pulse the trigger pin for at least 10µs as per the HC-SR04 datasheet.
+        // The timer is started before the pulse, so that a sensor that answers quickly is not missed.
+        infallible(self.trigger_pin.set_high());
+        let pulse_start = self.timer.now();
+        while self.timer.now().wrapping_sub(pulse_start) < TRIGGER_PULSE_TICKS {}
+        infallible(self.trigger_pin.set_low());
+
+        // After the trigger pin is pulsed, audio pulses will begin.
+        // After the pulses are sent, the echo pin will be set high (usually about 500µs, see hc-sr04-ping-delay.png)
+        // The time that the echo pin is high is the in-flight time of the pulses.
+        self.trigger_tick = self.timer.now();
+        self.measuring = true;
+    }
+
+    /// Check whether the measurement started by [HC_SR04::start_measurement] is finished.
+    ///
+    /// Returns `WouldBlock` while the measurement is in progress, and the result once it is finished.
+    /// The timeouts are checked here, so this must be called more often than the timer wraps around
+    /// (every 262ms on the robot).
+    pub fn poll(&mut self) -> nb::Result<DistanceMeasurement, MeasurementError> {
+        if !self.measuring {
+            return Err(nb::Error::Other(MeasurementError::NotStarted));
+        }
+
+        // The edges must be read before the time, so that an edge recorded in between
+        // is not mistaken for a timeout.
+        let (rise_tick, fall_tick) = self.timer.edges();
+        let now = self.timer.now();
+
+        let measurement = match (rise_tick, fall_tick) {
+            (Some(rise_tick), Some(fall_tick)) => {
+                DistanceMeasurement::Measured(Distance::new(fall_tick.wrapping_sub(rise_tick), self.sound_speed))
+            },
+
+            // If the echo pin does not go high in 750µs, the sensor did not react.
+            (None, _) if now.wrapping_sub(self.trigger_tick) > RISE_TIMEOUT_TICKS => DistanceMeasurement::Unknown,
+
+            // If the pulses never return, the echo pin will stay high for about 130ms (see hc-sr04-infinity-time.png).
+            // We will set the timeout to 100ms, which corresponds to a distance of about 17m -- after that we will return Infinity.
+            (Some(rise_tick), None) if now.wrapping_sub(rise_tick) > FALL_TIMEOUT_TICKS => DistanceMeasurement::Infinity,
+
+            _ => return Err(nb::Error::WouldBlock),
+        };
+
+        self.measuring = false;
+        self.last_measurement = Some(measurement);
+        Ok(measurement)
+    }
+
+    /// The result of the last finished measurement, if there has been one.
+    ///
+    /// Whoever started it, this is the freshest distance that the robot knows of, for example for telemetry.
+    pub fn last_measurement(&self) -> Option<DistanceMeasurement> {
+        self.last_measurement
+    }
+
+    /// Send an acoustic pulse and measure the distance between the sensor and the object.
+    ///
+    /// This blocks until the measurement is finished, which can take up to 100ms.
+    pub fn get_distance(&mut self) -> DistanceMeasurement {
+        self.start_measurement();
+        match nb::block!(self.poll()) {
+            Ok(measurement) => measurement,
+            Err(MeasurementError::NotStarted) => DistanceMeasurement::Unknown,
+        }
+    }
+
+}
@@ -0,0 +1,260 @@
+//! The L287N motor driver drives the two motors on the robot.
+//! 
+//! It is controlled by 6 pins: two to set the direction for each motor, and two to enable the motor pairs.
+//!
+//! The enable pins take a PWM signal, so the duty cycle on them sets the speed of each motor pair.
+//! On the robot, they are driven by a hardware timer, see the firmware's `motor_pwm` module.
+
+use core::convert::Infallible;
+
+use crate::differential_drive::{self, Drive};
+use crate::hal::{infallible, OutputPin, PwmPin};
+
+/// The driver for the motor driver.
+///
+/// `E` is the type of the PWM outputs on the enable pins, whose duty cycle goes up to 255 at most,
+/// and `D` is the type of the direction pins.
+pub struct MotorChassis<E, D> {
+    pin_enable_a: E,
+    pin_enable_b: E,
+    pin_a1: D,
+    pin_a2: D,
+    pin_b1: D,
+    pin_b2: D,
+    /// The last signed wheel commands given to [MotorChassis::drive], or zero after stopping.
+    command: (i16, i16),
+}
+
+/// The direction for the robot to go.
+/// 
+/// Rotations are tank-style, with the pairs moving in opposite directions.
+pub enum ChassisDirection {
+    Forward,
+    Backward,
+    Left,
+    Right,
+}
+
+impl ChassisDirection {
+    /// Converts the direction to signed wheel commands for [MotorChassis::drive] at the given speed.
+    ///
+    /// This is useful for going in one of these directions through a [SpeedRamp](crate::motor_ramp::SpeedRamp).
+    pub fn wheel_speeds(&self, speed: u8) -> (i16, i16) {
+        let speed = speed as i16;
+        match self {
+            ChassisDirection::Forward => (speed, speed),
+            ChassisDirection::Backward => (-speed, -speed),
+            ChassisDirection::Left => (-speed, speed),
+            ChassisDirection::Right => (speed, -speed),
+        }
+    }
+}
+
+/// The direction for a single motor to go.
+pub enum PairDirection {
+    Forward,
+    Backward,
+}
+
+/// The way the motors are stopped.
+#[derive(Clone, Copy)]
+pub enum StopMode {
+    /// The motor terminals are shorted together, so the motors stop quickly.
+    Brake,
+    /// The motors are disconnected and are left to spin down on their own.
+    Coast,
+}
+
+impl<E, D> MotorChassis<E, D>
+where
+    E: PwmPin<Duty = u8>,
+    D: OutputPin<Error = Infallible>,
+{
+    /// Creates a new motor driver from the PWM outputs on the enable pins, and the direction pins.
+    ///
+    /// Both motors start out stopped.
+    pub fn new(pin_enable_a: E, pin_enable_b: E, pin_a1: D, pin_a2: D, pin_b1: D, pin_b2: D) -> Self {
+        let mut new_chassis = Self {
+            pin_enable_a,
+            pin_enable_b,
+            pin_a1,
+            pin_a2,
+            pin_b1,
+            pin_b2,
+            command: (0, 0),
+        };
+
+        new_chassis.set_speed(0, 0);
+        new_chassis
+    }
+
+    /// Set the direction for the A motor (the left one).
+    ///
+    /// Only sets the direction pins, does not change the state of the motor:
+    /// if the motor is currently running, it will continue to run in the new direction,
+    /// and if it is not running it will stay not running. 
+    pub fn set_pair_a_direction(&mut self, direction: PairDirection){
+        match direction {
+            PairDirection::Forward => {
+                infallible(self.pin_a1.set_high());
+                infallible(self.pin_a2.set_low());
+            },
+            PairDirection::Backward => {
+                infallible(self.pin_a1.set_low());
+                infallible(self.pin_a2.set_high());
+            },
+        }
+    }
+
+    /// Set the direction for the B motor (the right one).
+    ///
+    /// Only sets the direction pins, does not change the state of the motor:
+    /// if the motor is currently running, it will continue to run in the new direction,
+    /// and if it is not running it will stay not running. 
+    pub fn set_pair_b_direction(&mut self, direction: PairDirection){
+        match direction {
+            PairDirection::Forward => {
+                infallible(self.pin_b2.set_high());
+                infallible(self.pin_b1.set_low());
+            },
+            PairDirection::Backward => {
+                infallible(self.pin_b2.set_low());
+                infallible(self.pin_b1.set_high());
+            },
+        }
+    }
+    /// Set the direction for both motors.
+    ///
+    /// Only sets the direction pins, does not change the state of the motor:
+    /// if the motor is currently running, it will continue to run in the new direction,
+    /// and if it is not running it will stay not running. 
+    pub fn set_direction(&mut self, direction: ChassisDirection){
+        match direction {
+            ChassisDirection::Forward => {
+                self.set_pair_a_direction(PairDirection::Forward);
+                self.set_pair_b_direction(PairDirection::Forward);
+            },
+            ChassisDirection::Backward => {
+                self.set_pair_a_direction(PairDirection::Backward);
+                self.set_pair_b_direction(PairDirection::Backward);
+            },
+            ChassisDirection::Left => {
+                self.set_pair_a_direction(PairDirection::Backward);
+                self.set_pair_b_direction(PairDirection::Forward);
+
+            },
+            ChassisDirection::Right => {
+                self.set_pair_a_direction(PairDirection::Forward);
+                self.set_pair_b_direction(PairDirection::Backward);
+            },
+        }
+    }
+
+    /// Set the enablement state for both motors.
+    ///
+    /// This is separate from setting the direction for the motors.
+    /// First you need to set the direction, then run the motors with the needed direction.
+    ///
+    /// An enabled motor runs at full speed; use [MotorChassis::set_speed] to run it slower.
+    pub fn set_enabled(&mut self, pair_a_en: bool, pair_b_en: bool){
+        let speed = |enabled: bool| if enabled { 255 } else { 0 };
+        self.set_speed(speed(pair_a_en), speed(pair_b_en));
+    }
+
+    /// Set the speed for both motors, as a PWM duty cycle from 0 (stopped) to 255 (full speed).
+    ///
+    /// Like [MotorChassis::set_enabled], this does not change the direction of the motors.
+    pub fn set_speed(&mut self, left: u8, right: u8){
+        set_pwm_speed(&mut self.pin_enable_a, left);
+        set_pwm_speed(&mut self.pin_enable_b, right);
+    }
+
+    /// Drive each motor pair with a signed command, from -255 to 255.
+    ///
+    /// The sign selects the direction (positive is forward), and the magnitude selects the speed.
+    /// Commands outside of the range are clamped.
+    pub fn drive(&mut self, left: i16, right: i16){
+        let (left_forward, left_speed) = differential_drive::split_speed(left);
+        let (right_forward, right_speed) = differential_drive::split_speed(right);
+
+        self.set_pair_a_direction(if left_forward { PairDirection::Forward } else { PairDirection::Backward });
+        self.set_pair_b_direction(if right_forward { PairDirection::Forward } else { PairDirection::Backward });
+        self.set_speed(left_speed, right_speed);
+        self.command = (differential_drive::clamp_speed(left), differential_drive::clamp_speed(right));
+    }
+
+    /// The last signed wheel commands given to [MotorChassis::drive], or zero after stopping.
+    ///
+    /// Setting the speed and direction directly is not tracked here.
+    pub fn command(&self) -> (i16, i16) {
+        self.command
+    }
+
+    /// Drive the robot with a throttle and a turn rate, both from -255 to 255.
+    ///
+    /// A positive turn curves to the right. See [differential_drive::arcade_mix] for how these are mixed.
+    pub fn arcade(&mut self, throttle: i16, turn: i16){
+        let (left, right) = differential_drive::arcade_mix(throttle, turn);
+        self.drive(left, right);
+    }
+
+    /// Stop both motors in the given way.
+    pub fn stop(&mut self, mode: StopMode){
+        match mode {
+            StopMode::Brake => self.brake(),
+            StopMode::Coast => self.coast(),
+        }
+    }
+
+    /// Stop both motors by short-braking them.
+    ///
+    /// The L298N shorts the motor terminals when both direction pins of a pair are at the same level
+    /// and the pair is enabled, so the motors' own back-EMF stops them.
+    /// Call [MotorChassis::drive] or [MotorChassis::set_direction] to start moving again.
+    pub fn brake(&mut self){
+        infallible(self.pin_a1.set_low());
+        infallible(self.pin_a2.set_low());
+        infallible(self.pin_b1.set_low());
+        infallible(self.pin_b2.set_low());
+        self.set_speed(255, 255);
+        self.command = (0, 0);
+    }
+
+    /// Stop both motors by disabling them, letting the wheels spin down freely.
+    pub fn coast(&mut self){
+        self.set_speed(0, 0);
+        self.command = (0, 0);
+    }
+}
+
+impl<E, D> Drive for MotorChassis<E, D>
+where
+    E: PwmPin<Duty = u8>,
+    D: OutputPin<Error = Infallible>,
+{
+    fn drive(&mut self, left: i16, right: i16) {
+        MotorChassis::drive(self, left, right);
+    }
+
+    fn brake(&mut self) {
+        MotorChassis::brake(self);
+    }
+
+    fn coast(&mut self) {
+        MotorChassis::coast(self);
+    }
+}
+
+/// Run a PWM output at a speed from 0 to 255, scaled to its duty cycle range.
+pub(crate) fn set_pwm_speed<E: PwmPin<Duty = u8>>(pwm: &mut E, speed: u8) {
+    // A timer may still produce a short pulse on every cycle at a duty cycle of 0 (the AVR's fast PWM does),
+    // so a stopped motor is disabled instead, which holds the pin low.
+    if speed == 0 {
+        pwm.disable();
+        return;
+    }
+
+    let duty = speed as u16 * pwm.get_max_duty() as u16 / 255;
+    pwm.set_duty(duty as u8);
+    pwm.enable();
+}
@@ -0,0 +1,36 @@
+//! The drivers for the Elegoo smart robot car, and the pure logic they are built on.
+//!
+//! The drivers are generic over the `embedded_hal` pin traits, and over the small traits in [hal]
+//! for the parts that need a timer, so the same code runs on the robot and on the host,
+//! where it is tested with mock pins. The AVR implementations of the traits are in the firmware crate.
+//!
+//! The drivers get the time from [clock::millis], which the firmware connects to a hardware timer at startup.
+//! Everything is computed with integers, because a firmware that uses floats halts at startup.
+//! The modules that do not touch any hardware, such as [pid] or [line_events], are tested on the host as they are.
+//!
+//! The behaviors that the car runs (following a line, avoiding obstacles, driving routes on a grid of lines)
+//! are here too, generic over the drivers, so that they can also be run on the host against a simulated car.
+
+#![no_std]
+
+pub mod avoid_mode;
+pub mod clock;
+pub mod differential_drive;
+pub mod filtered_rangefinder;
+pub mod follow_mode;
+pub mod grid_mode;
+pub mod grid_route;
+pub mod hal;
+pub mod hc_sr04_distance_sensor;
+pub mod l287n_motor_driver;
+pub mod line_events;
+pub mod line_following;
+pub mod line_tracker;
+pub mod motor_ramp;
+pub mod obstacle_avoidance;
+pub mod pid;
+pub mod range_scanner;
+pub mod servo;
+pub mod sound_speed;
+pub mod tb6612_motor_driver;
+pub mod telemetry;
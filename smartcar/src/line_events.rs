@@ -0,0 +1,283 @@
+//! Detection of discrete events on the line, such as crossing another line.
+//!
+//! The line tracker is sampled many times a second, and a single noisy sample can look like a crossing
+//! or like the line being lost. The [LineEventDetector] only believes a change
+//! when the new reading has been stable for a while, and then reports it as a [LineEvent].
+//!
+//! Feed the detector readings with [crate::clock::millis] timestamps.
+
+use crate::line_tracker::LineBiasDirection;
+
+/// What the robot sees, coarsely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineSight {
+    /// The line is seen by one or two sensors.
+    OnLine,
+    /// All three sensors see a line, which means that the robot is on a crossing.
+    Crossing,
+    /// No sensor sees the line.
+    Lost,
+}
+
+impl From<LineBiasDirection> for LineSight {
+    fn from(bias: LineBiasDirection) -> Self {
+        match bias {
+            LineBiasDirection::OnPerpendicularLine => LineSight::Crossing,
+            LineBiasDirection::NotOnLine => LineSight::Lost,
+            _ => LineSight::OnLine,
+        }
+    }
+}
+
+/// The kinds of events on the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEventKind {
+    /// The robot drove onto a crossing line.
+    CrossingEntered,
+    /// The robot drove off a crossing line.
+    CrossingExited,
+    /// The robot no longer sees the line.
+    LineLost,
+    /// The robot sees the line again after losing it.
+    LineReacquired,
+}
+
+/// An event on the line, with the time when it started, in ms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineEvent {
+    pub kind: LineEventKind,
+    pub at_ms: u64,
+}
+
+/// The events that a single reading completes.
+///
+/// Most changes are one event, but some are two that happen at once
+/// (leaving a crossing straight into nothing is `CrossingExited` and then `LineLost`).
+/// Iterating gives them in that order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LineEvents {
+    pub first: Option<LineEvent>,
+    pub second: Option<LineEvent>,
+}
+
+impl Iterator for LineEvents {
+    type Item = LineEvent;
+
+    fn next(&mut self) -> Option<LineEvent> {
+        let event = self.first.take();
+        self.first = self.second.take();
+        event
+    }
+}
+
+/// Debounces line tracker readings into [LineEvent]s.
+pub struct LineEventDetector {
+    /// How long a reading must be stable to be believed, in ms.
+    debounce_ms: u64,
+    /// The last believed reading.
+    stable: LineSight,
+    /// A different reading that has been seen since the given time, but is not believed yet.
+    candidate: Option<(LineSight, u64)>,
+    /// The number of crossings entered since the last reset.
+    crossings: u16,
+}
+
+impl LineEventDetector {
+    /// Creates a detector that believes a reading once it has been stable for `debounce_ms`.
+    ///
+    /// The robot is assumed to start on the line.
+    pub fn new(debounce_ms: u64) -> Self {
+        Self {
+            debounce_ms,
+            stable: LineSight::OnLine,
+            candidate: None,
+            crossings: 0,
+        }
+    }
+
+    pub fn set_debounce_ms(&mut self, debounce_ms: u64) {
+        self.debounce_ms = debounce_ms;
+    }
+
+    /// The number of crossings entered since the detector was created or reset.
+    pub fn crossings(&self) -> u16 {
+        self.crossings
+    }
+
+    /// Whether the robot is on a crossing, after debouncing.
+    pub fn on_crossing(&self) -> bool {
+        self.stable == LineSight::Crossing
+    }
+
+    /// Whether the line is lost, after debouncing.
+    pub fn line_lost(&self) -> bool {
+        self.stable == LineSight::Lost
+    }
+
+    /// Reset the crossing count to zero.
+    pub fn reset_crossings(&mut self) {
+        self.crossings = 0;
+    }
+
+    /// Feed a reading at the given time, and get the events that it completes, if any.
+    pub fn update(&mut self, now_ms: u64, bias: LineBiasDirection) -> LineEvents {
+        let sight = LineSight::from(bias);
+        if sight == self.stable {
+            self.candidate = None;
+            return LineEvents::default();
+        }
+
+        let since_ms = match self.candidate {
+            Some((candidate, since_ms)) if candidate == sight => since_ms,
+            _ => {
+                self.candidate = Some((sight, now_ms));
+                now_ms
+            },
+        };
+        if now_ms.saturating_sub(since_ms) < self.debounce_ms {
+            return LineEvents::default();
+        }
+
+        let previous = self.stable;
+        self.stable = sight;
+        self.candidate = None;
+
+        let event = |kind| LineEvent { kind, at_ms: since_ms };
+        let (first, second) = match (previous, sight) {
+            (LineSight::OnLine, LineSight::Crossing) => (LineEventKind::CrossingEntered, None),
+            (LineSight::Crossing, LineSight::OnLine) => (LineEventKind::CrossingExited, None),
+            (LineSight::OnLine, LineSight::Lost) => (LineEventKind::LineLost, None),
+            (LineSight::Lost, LineSight::OnLine) => (LineEventKind::LineReacquired, None),
+            (LineSight::Crossing, LineSight::Lost) => (LineEventKind::CrossingExited, Some(LineEventKind::LineLost)),
+            (LineSight::Lost, LineSight::Crossing) => (LineEventKind::LineReacquired, Some(LineEventKind::CrossingEntered)),
+            // A change to the same state was already handled above.
+            _ => return LineEvents::default(),
+        };
+
+        if first == LineEventKind::CrossingEntered || second == Some(LineEventKind::CrossingEntered) {
+            self.crossings = self.crossings.saturating_add(1);
+        }
+
+        LineEvents { first: Some(event(first)), second: second.map(event) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use LineBiasDirection::{Center, NotOnLine, OnPerpendicularLine, SlightlyLeft};
+
+    fn none() -> LineEvents {
+        LineEvents::default()
+    }
+
+    fn one(kind: LineEventKind, at_ms: u64) -> LineEvents {
+        LineEvents { first: Some(LineEvent { kind, at_ms }), second: None }
+    }
+
+    fn two(first: LineEventKind, second: LineEventKind, at_ms: u64) -> LineEvents {
+        LineEvents { first: Some(LineEvent { kind: first, at_ms }), second: Some(LineEvent { kind: second, at_ms }) }
+    }
+
+    #[test]
+    fn noise_is_ignored() {
+        let mut detector = LineEventDetector::new(30);
+        assert_eq!(detector.update(0, Center), none());
+        assert_eq!(detector.update(10, OnPerpendicularLine), none());
+        assert_eq!(detector.update(20, SlightlyLeft), none());
+        assert_eq!(detector.update(30, NotOnLine), none());
+        assert_eq!(detector.update(40, Center), none());
+        // A change that came back before the debounce time starts over the next time it is seen.
+        assert_eq!(detector.update(50, OnPerpendicularLine), none());
+        assert_eq!(detector.update(70, OnPerpendicularLine), none());
+        assert_eq!(detector.crossings(), 0);
+        assert!(!detector.on_crossing());
+    }
+
+    #[test]
+    fn stable_change_is_reported_from_its_start() {
+        let mut detector = LineEventDetector::new(30);
+        assert_eq!(detector.update(100, OnPerpendicularLine), none());
+        assert_eq!(detector.update(120, OnPerpendicularLine), none());
+        assert_eq!(detector.update(130, OnPerpendicularLine), one(LineEventKind::CrossingEntered, 100));
+        assert!(detector.on_crossing());
+        assert_eq!(detector.update(140, OnPerpendicularLine), none());
+
+        assert_eq!(detector.update(200, SlightlyLeft), none());
+        assert_eq!(detector.update(240, Center), one(LineEventKind::CrossingExited, 200));
+        assert_eq!(detector.crossings(), 1);
+    }
+
+    #[test]
+    fn lost_and_reacquired() {
+        let mut detector = LineEventDetector::new(0);
+        assert_eq!(detector.update(10, NotOnLine), one(LineEventKind::LineLost, 10));
+        assert!(detector.line_lost());
+        assert_eq!(detector.update(20, NotOnLine), none());
+        assert_eq!(detector.update(30, Center), one(LineEventKind::LineReacquired, 30));
+        assert!(!detector.line_lost());
+    }
+
+    #[test]
+    fn double_changes_report_both_events() {
+        let mut detector = LineEventDetector::new(0);
+        assert_eq!(detector.update(0, OnPerpendicularLine), one(LineEventKind::CrossingEntered, 0));
+
+        // Leaving a crossing straight into nothing.
+        let events = detector.update(10, NotOnLine);
+        assert_eq!(events, two(LineEventKind::CrossingExited, LineEventKind::LineLost, 10));
+        assert_eq!(events.map(|event| event.kind).last(), Some(LineEventKind::LineLost));
+        assert_eq!(detector.update(11, NotOnLine), none());
+        assert!(detector.line_lost());
+
+        // Finding the line right on a crossing counts the crossing.
+        let events = detector.update(20, OnPerpendicularLine);
+        assert_eq!(events, two(LineEventKind::LineReacquired, LineEventKind::CrossingEntered, 20));
+        assert_eq!(detector.crossings(), 2);
+    }
+
+    #[test]
+    fn back_to_back_changes_are_all_seen() {
+        let mut detector = LineEventDetector::new(0);
+        assert_eq!(detector.update(0, NotOnLine), one(LineEventKind::LineLost, 0));
+
+        // Back onto the line right on a crossing, and straight off it again.
+        let events = detector.update(10, OnPerpendicularLine);
+        assert_eq!(events, two(LineEventKind::LineReacquired, LineEventKind::CrossingEntered, 10));
+        assert_eq!(detector.update(11, Center), one(LineEventKind::CrossingExited, 11));
+        assert_eq!(detector.crossings(), 1);
+
+        // With debouncing, the start of a change that comes right after another one is kept.
+        let mut detector = LineEventDetector::new(20);
+        detector.update(0, NotOnLine);
+        assert_eq!(detector.update(20, NotOnLine), one(LineEventKind::LineLost, 0));
+        detector.update(30, OnPerpendicularLine);
+        assert_eq!(detector.update(50, OnPerpendicularLine), two(LineEventKind::LineReacquired, LineEventKind::CrossingEntered, 30));
+        assert_eq!(detector.update(51, Center), none());
+        assert_eq!(detector.update(71, Center), one(LineEventKind::CrossingExited, 51));
+    }
+
+    #[test]
+    fn counts_crossings_until_reset() {
+        let mut detector = LineEventDetector::new(20);
+        let mut now = 0;
+        for _ in 0..3 {
+            for &bias in &[OnPerpendicularLine, OnPerpendicularLine, Center, Center] {
+                detector.update(now, bias);
+                now += 20;
+            }
+        }
+        assert_eq!(detector.crossings(), 3);
+
+        detector.reset_crossings();
+        assert_eq!(detector.crossings(), 0);
+        // A crossing that is already entered is not counted again.
+        detector.update(now, OnPerpendicularLine);
+        detector.update(now + 20, OnPerpendicularLine);
+        assert_eq!(detector.crossings(), 1);
+        detector.reset_crossings();
+        detector.update(now + 40, OnPerpendicularLine);
+        assert_eq!(detector.crossings(), 0);
+    }
+}
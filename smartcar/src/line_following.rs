@@ -0,0 +1,393 @@
+//! The steering logic of the line following behavior.
+//!
+//! Every reading of the line tracker is classified into a [LineBiasDirection],
+//! and each of those is turned into wheel commands: straight ahead when the line is in the center,
+//! a gentle curve when it is slightly to the side, and a sharp curve when it is only seen by an outer sensor.
+//!
+//! When the line is lost, the robot spins in place toward the side where it last saw the line, until it finds it again.
+//!
+//! Switching between a handful of fixed turn rates makes the robot oscillate around the line,
+//! so there is also the [PidFollower], which steers with a [Pid] controller
+//! on the position error estimated by the [LineErrorEstimator].
+//!
+//! This module only computes wheel commands; see [crate::follow_mode] for the part that runs it on the robot.
+
+use crate::differential_drive;
+use crate::line_tracker::{LineBiasDirection, LinePosition, LineTrackerDirection, LINE_ERROR_STEP};
+
+use crate::pid::{Pid, PidGains};
+
+/// The color of the line that is being followed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineColor {
+    /// A dark line on a light background.
+    Dark,
+    /// A light line on a dark background.
+    Light,
+}
+
+/// Convert the color selected with the `line-color` console command.
+impl From<smartcar_protocol::command::LineColor> for LineColor {
+    fn from(color: smartcar_protocol::command::LineColor) -> Self {
+        match color {
+            smartcar_protocol::command::LineColor::Dark => LineColor::Dark,
+            smartcar_protocol::command::LineColor::Light => LineColor::Light,
+        }
+    }
+}
+
+impl LineColor {
+    /// Classify a line tracker reading for a line of this color.
+    pub fn bias(&self, position: &LinePosition) -> LineBiasDirection {
+        match self {
+            LineColor::Dark => position.get_bias_direction_dark(),
+            LineColor::Light => position.get_bias_direction_light(),
+        }
+    }
+}
+
+/// The settings of the line following behavior.
+#[derive(Debug, Clone, Copy)]
+pub struct FollowConfig {
+    /// The color of the line.
+    pub line_color: LineColor,
+    /// The throttle when driving along the line.
+    pub base_speed: i16,
+    /// The turn rate when the line is slightly to the side.
+    pub slight_turn: i16,
+    /// The turn rate when the line is only seen by an outer sensor.
+    pub sharp_turn: i16,
+    /// The wheel command when spinning in place to find a lost line.
+    pub search_speed: i16,
+}
+
+impl Default for FollowConfig {
+    fn default() -> Self {
+        Self {
+            line_color: LineColor::Dark,
+            base_speed: 140,
+            slight_turn: 60,
+            sharp_turn: 140,
+            search_speed: 150,
+        }
+    }
+}
+
+/// The line following steering logic.
+pub struct LineFollower {
+    config: FollowConfig,
+    /// The side that the line was last seen on, which is where to look for it when it is lost.
+    last_side: LineTrackerDirection,
+}
+
+impl LineFollower {
+    pub fn new(config: FollowConfig) -> Self {
+        Self {
+            config,
+            last_side: LineTrackerDirection::Center,
+        }
+    }
+
+    pub fn config(&self) -> &FollowConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: FollowConfig) {
+        self.config = config;
+    }
+
+    /// Change whether the line is dark or light, for example when moving to a different course.
+    pub fn set_line_color(&mut self, line_color: LineColor) {
+        self.config.line_color = line_color;
+    }
+
+    /// The side that the line was last seen on.
+    pub fn last_side(&self) -> LineTrackerDirection {
+        self.last_side
+    }
+
+    /// Classify a line tracker reading according to the configured line color.
+    pub fn bias(&self, position: &LinePosition) -> LineBiasDirection {
+        self.config.line_color.bias(position)
+    }
+
+    /// Get the wheel commands for a line tracker reading.
+    pub fn steer_position(&mut self, position: &LinePosition) -> (i16, i16) {
+        let bias = self.bias(position);
+        self.steer(bias)
+    }
+
+    /// Get the wheel commands to follow the line, given where the line is.
+    ///
+    /// Positive turns are to the right, as in [differential_drive::arcade_mix].
+    pub fn steer(&mut self, bias: LineBiasDirection) -> (i16, i16) {
+        let config = self.config;
+
+        match bias {
+            LineBiasDirection::VeryLeft | LineBiasDirection::SlightlyLeft
+                | LineBiasDirection::VeryRight | LineBiasDirection::SlightlyRight => {
+                self.last_side = bias.to_line_tracker_direction();
+            },
+            _ => {},
+        }
+
+        match bias {
+            LineBiasDirection::Center | LineBiasDirection::OnPerpendicularLine => {
+                differential_drive::arcade_mix(config.base_speed, 0)
+            },
+            LineBiasDirection::SlightlyLeft => differential_drive::arcade_mix(config.base_speed, -config.slight_turn),
+            LineBiasDirection::VeryLeft => differential_drive::arcade_mix(config.base_speed, -config.sharp_turn),
+            LineBiasDirection::SlightlyRight => differential_drive::arcade_mix(config.base_speed, config.slight_turn),
+            LineBiasDirection::VeryRight => differential_drive::arcade_mix(config.base_speed, config.sharp_turn),
+            LineBiasDirection::NotOnLine => self.search(),
+        }
+    }
+
+    /// Spin in place toward the side where the line was last seen.
+    ///
+    /// If the line was last seen in the center, we search to the left.
+    fn search(&self) -> (i16, i16) {
+        let speed = self.config.search_speed;
+        match self.last_side {
+            LineTrackerDirection::Right => (speed, -speed),
+            LineTrackerDirection::Left | LineTrackerDirection::Center => (-speed, speed),
+        }
+    }
+}
+
+/// Estimates a continuous line position error from the five discrete positions that the sensors can tell apart.
+///
+/// When the reading changes from one position to the next, the line must be right at the boundary between them,
+/// so the estimate is the midpoint. The time between transitions gives the speed at which the line is drifting,
+/// and between transitions the estimate keeps moving at that speed, without leaving the band of the current reading.
+///
+/// When the line is lost, the estimate is the largest error on the side where the line was last seen,
+/// so the controller turns hard to find it again.
+pub struct LineErrorEstimator {
+    /// The last discrete error that was read, or `None` if the line was not seen.
+    level: Option<i16>,
+    /// The estimated error at the time of the last transition.
+    anchor: i32,
+    anchor_ms: u64,
+    /// The estimated drift of the error, per second.
+    velocity: i32,
+    /// The time of the last transition between two positions on the line.
+    last_transition_ms: Option<u64>,
+    /// The sign of the last non-zero error, which is the side to look for a lost line on.
+    last_side: i32,
+}
+
+impl Default for LineErrorEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineErrorEstimator {
+    /// The error reported when the line is lost: half a step past the outermost position.
+    pub const LOST_ERROR: i32 = 5 * LINE_ERROR_STEP as i32 / 2;
+
+    pub fn new() -> Self {
+        Self {
+            level: None,
+            anchor: 0,
+            anchor_ms: 0,
+            velocity: 0,
+            last_transition_ms: None,
+            last_side: 0,
+        }
+    }
+
+    /// Feed a reading (from [LineBiasDirection::position_error]) at the given time, and get the estimated error.
+    pub fn update(&mut self, now_ms: u64, reading: Option<i16>) -> i32 {
+        let reading = match reading {
+            Some(reading) => reading as i32,
+            None => {
+                self.level = None;
+                self.last_transition_ms = None;
+                return self.last_side * Self::LOST_ERROR;
+            },
+        };
+
+        if reading != 0 {
+            self.last_side = reading.signum();
+        }
+
+        match self.level {
+            Some(level) if level as i32 == reading => {
+                let elapsed = now_ms.saturating_sub(self.anchor_ms) as i64;
+                let estimate = self.anchor as i64 + self.velocity as i64 * elapsed / 1000;
+                let half_step = LINE_ERROR_STEP as i64 / 2;
+                estimate.max(reading as i64 - half_step).min(reading as i64 + half_step) as i32
+            },
+            Some(level) => {
+                let level = level as i32;
+                if let Some(last_transition_ms) = self.last_transition_ms {
+                    let dt_ms = now_ms.saturating_sub(last_transition_ms).max(1) as i64;
+                    self.velocity = ((reading - level) as i64 * 1000 / dt_ms) as i32;
+                }
+                self.level = Some(reading as i16);
+                self.anchor = (level + reading) / 2;
+                self.anchor_ms = now_ms;
+                self.last_transition_ms = Some(now_ms);
+                self.anchor
+            },
+            None => {
+                // The line was just found, so we know nothing about its movement yet.
+                self.level = Some(reading as i16);
+                self.anchor = reading;
+                self.anchor_ms = now_ms;
+                self.velocity = 0;
+                reading
+            },
+        }
+    }
+}
+
+/// Line following steering with a [Pid] controller on the estimated position error.
+pub struct PidFollower {
+    config: FollowConfig,
+    estimator: LineErrorEstimator,
+    pid: Pid,
+}
+
+impl PidFollower {
+    /// Reasonable starting gains: full turn for a line seen only by an outer sensor, and a little damping.
+    pub const DEFAULT_GAINS: PidGains = PidGains { kp: 100, ki: 20, kd: 10 };
+
+    /// The largest integral, in error-seconds.
+    const INTEGRAL_LIMIT: i32 = 2000;
+
+    /// Creates a new follower. The turn rates in the config are not used, the gains set them instead.
+    pub fn new(config: FollowConfig, gains: PidGains) -> Self {
+        Self {
+            config,
+            estimator: LineErrorEstimator::new(),
+            pid: Pid::new(gains, Self::INTEGRAL_LIMIT, differential_drive::MAX_SPEED as i32),
+        }
+    }
+
+    pub fn config(&self) -> &FollowConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: FollowConfig) {
+        self.config = config;
+    }
+
+    /// Get the controller, for example to tune its gains.
+    pub fn pid(&mut self) -> &mut Pid {
+        &mut self.pid
+    }
+
+    /// Forget everything learned about the line so far, for example when the robot is put on a new line.
+    ///
+    /// Without this, the first update after a pause sees the whole pause as one long interval,
+    /// and the integral winds up to its limit right away.
+    pub fn reset(&mut self) {
+        self.estimator = LineErrorEstimator::new();
+        self.pid.reset();
+    }
+
+    /// Get the wheel commands for a line tracker reading at the given time.
+    pub fn steer_position(&mut self, position: &LinePosition, now_ms: u64) -> (i16, i16) {
+        let bias = self.config.line_color.bias(position);
+        self.steer(bias, now_ms)
+    }
+
+    /// Get the wheel commands to follow the line, given where the line is at the given time.
+    pub fn steer(&mut self, bias: LineBiasDirection, now_ms: u64) -> (i16, i16) {
+        let error = self.estimator.update(now_ms, bias.position_error());
+        let turn = self.pid.update(error, now_ms);
+        differential_drive::arcade_mix(self.config.base_speed, turn as i16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn follower() -> LineFollower {
+        LineFollower::new(FollowConfig::default())
+    }
+
+    #[test]
+    fn goes_straight_on_center() {
+        let mut follower = follower();
+        assert_eq!(follower.steer(LineBiasDirection::Center), (140, 140));
+        assert_eq!(follower.steer(LineBiasDirection::OnPerpendicularLine), (140, 140));
+    }
+
+    #[test]
+    fn turns_toward_the_line() {
+        let mut follower = follower();
+        assert_eq!(follower.steer(LineBiasDirection::SlightlyLeft), (80, 200));
+        assert_eq!(follower.steer(LineBiasDirection::VeryLeft), (0, 255));
+        assert_eq!(follower.steer(LineBiasDirection::SlightlyRight), (200, 80));
+        assert_eq!(follower.steer(LineBiasDirection::VeryRight), (255, 0));
+    }
+
+    #[test]
+    fn searches_toward_last_seen_side() {
+        let mut follower = follower();
+        follower.steer(LineBiasDirection::SlightlyRight);
+        follower.steer(LineBiasDirection::Center);
+        assert_eq!(follower.last_side(), LineTrackerDirection::Right);
+        assert_eq!(follower.steer(LineBiasDirection::NotOnLine), (150, -150));
+
+        follower.steer(LineBiasDirection::VeryLeft);
+        assert_eq!(follower.steer(LineBiasDirection::NotOnLine), (-150, 150));
+        assert_eq!(follower.last_side(), LineTrackerDirection::Left);
+    }
+
+    #[test]
+    fn estimator_interpolates_between_transitions() {
+        let mut estimator = LineErrorEstimator::new();
+        assert_eq!(estimator.update(0, Some(0)), 0);
+        // The first transition gives the boundary, but no speed yet.
+        assert_eq!(estimator.update(100, Some(1000)), 500);
+        assert_eq!(estimator.update(150, Some(1000)), 500);
+        // The second transition 200ms later gives a drift of 5000 per second.
+        assert_eq!(estimator.update(300, Some(2000)), 1500);
+        assert_eq!(estimator.update(400, Some(2000)), 2000);
+        // The estimate does not leave the band of the reading.
+        assert_eq!(estimator.update(1000, Some(2000)), 2500);
+    }
+
+    #[test]
+    fn estimator_points_to_last_side_when_lost() {
+        let mut estimator = LineErrorEstimator::new();
+        assert_eq!(estimator.update(0, None), 0);
+        estimator.update(10, Some(-1000));
+        estimator.update(20, Some(0));
+        assert_eq!(estimator.update(30, None), -LineErrorEstimator::LOST_ERROR);
+        // When the line is found again, the estimate starts over from the reading.
+        assert_eq!(estimator.update(40, Some(1000)), 1000);
+    }
+
+    #[test]
+    fn pid_follower_steers_toward_line() {
+        let gains = PidGains { kp: 100, ki: 0, kd: 0 };
+        let mut follower = PidFollower::new(FollowConfig::default(), gains);
+        assert_eq!(follower.steer(LineBiasDirection::Center, 0), (140, 140));
+
+        let mut follower = PidFollower::new(FollowConfig::default(), gains);
+        assert_eq!(follower.steer(LineBiasDirection::SlightlyRight, 0), (240, 40));
+    }
+
+    #[test]
+    fn pid_follower_reset_forgets_the_pause() {
+        let gains = PidGains { kp: 0, ki: 100, kd: 0 };
+        let mut follower = PidFollower::new(FollowConfig::default(), gains);
+        follower.steer(LineBiasDirection::SlightlyRight, 0);
+        // Ten seconds later, the whole pause is folded into the integral, which winds up to its limit.
+        assert_eq!(follower.steer(LineBiasDirection::SlightlyRight, 10_000), (255, -45));
+        assert_eq!(follower.pid().integral(), 2000);
+
+        let mut follower = PidFollower::new(FollowConfig::default(), gains);
+        follower.steer(LineBiasDirection::SlightlyRight, 0);
+        follower.reset();
+        assert_eq!(follower.steer(LineBiasDirection::SlightlyRight, 10_000), (140, 140));
+        assert_eq!(follower.pid().integral(), 0);
+    }
+}
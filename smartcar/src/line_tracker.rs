@@ -2,15 +2,22 @@
 //! 
 //! There are three separate sensors, to the left, right, and center.
 //! Together, these can inform the robot on the direction to go to follow a line.
+//!
+//! The sensors of the V3 kit have digital outputs, and are read by the [LineTracker].
+//! The sensors of the V4 kit are analog, and are read by the [AnalogLineTracker].
+//! Both implement [MeasureLine], so that the behaviors can follow a line with either of them.
+
+use core::convert::Infallible;
+use core::ops::Range;
 
-use arduino_hal::port::Pin;
-use arduino_hal::port::mode::{Input, AnyInput};
 use ufmt::derive::uDebug;
 
+use crate::hal::{infallible, AnalogLineSensors, InputPin};
+
 /// The state of a single line tracker.
 /// 
 /// Dark means that it is on the line, light means that it is not.
-#[derive(uDebug, Clone, Copy)]
+#[derive(uDebug, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LineState {
     Light,
     Dark,
@@ -26,7 +33,7 @@ impl From<bool> for LineState {
 }
 
 /// Represents a choice of the three possible line trackers.
-#[derive(uDebug, Clone, Copy)]
+#[derive(uDebug, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LineTrackerDirection {
     Left,
     Center,
@@ -34,14 +41,21 @@ pub enum LineTrackerDirection {
 }
 
 /// The result of the measurement of the three line trackers taken together.
-#[derive(uDebug)]
+#[derive(uDebug, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct LinePosition {
     left: LineState,
     mid: LineState,
     right: LineState,
 }
 
+/// The size of one step of the line position error, between neighbouring [LineBiasDirection]s.
+///
+/// The error goes from `-2 * LINE_ERROR_STEP` (the line is only seen on the left)
+/// to `2 * LINE_ERROR_STEP` (the line is only seen on the right).
+pub const LINE_ERROR_STEP: i16 = 1000;
+
 /// The direction that the robot is offset from the line.
+#[derive(uDebug, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LineBiasDirection {
     /// The robot only sees the line on the left.
     VeryLeft,
@@ -71,10 +85,46 @@ impl LineBiasDirection {
             _ => LineTrackerDirection::Center,
         }
     }
+
+    /// Converts the line bias direction to a signed position error: where the line is, relative to the center sensor.
+    ///
+    /// The sensors are weighted so that every direction is one [LINE_ERROR_STEP] away from its neighbours:
+    /// `VeryLeft` is -2 steps, `SlightlyLeft` is -1, `Center` is 0, and so on.
+    /// A perpendicular line counts as centered, and `None` is returned if the robot is not on a line.
+    pub fn position_error(self) -> Option<i16> {
+        let steps = match self {
+            LineBiasDirection::VeryLeft => -2,
+            LineBiasDirection::SlightlyLeft => -1,
+            LineBiasDirection::Center | LineBiasDirection::OnPerpendicularLine => 0,
+            LineBiasDirection::SlightlyRight => 1,
+            LineBiasDirection::VeryRight => 2,
+            LineBiasDirection::NotOnLine => return None,
+        };
+        Some(steps * LINE_ERROR_STEP)
+    }
 }
 
 
 impl LinePosition {
+    /// Returns whether the tracker in the given direction sees a dark surface.
+    pub fn is_dark(&self, direction: LineTrackerDirection) -> bool {
+        let state = match direction {
+            LineTrackerDirection::Left => self.left,
+            LineTrackerDirection::Center => self.mid,
+            LineTrackerDirection::Right => self.right,
+        };
+        matches!(state, LineState::Dark)
+    }
+
+    /// Returns whether the left, center and right trackers see a dark surface.
+    pub fn dark_states(&self) -> (bool, bool, bool) {
+        (
+            self.is_dark(LineTrackerDirection::Left),
+            self.is_dark(LineTrackerDirection::Center),
+            self.is_dark(LineTrackerDirection::Right),
+        )
+    }
+
     /// Returns the direction that the sensor state is pointing to,
     /// when the robot is following a dark line on a light background.
     /// 
@@ -122,17 +172,33 @@ impl LinePosition {
             (LineState::Light, LineState::Dark, LineState::Light) => LineBiasDirection::NotOnLine,
         }
     }
+
+    /// Returns the signed position error of a dark line on a light background, see [LineBiasDirection::position_error].
+    pub fn get_position_error_dark(&self) -> Option<i16> {
+        self.get_bias_direction_dark().position_error()
+    }
+
+    /// Returns the signed position error of a light line on a dark background, see [LineBiasDirection::position_error].
+    pub fn get_position_error_light(&self) -> Option<i16> {
+        self.get_bias_direction_light().position_error()
+    }
+}
+
+/// A line tracker that measures its three sensors together.
+pub trait MeasureLine {
+    /// Measure the three line trackers, packed into a [LinePosition].
+    fn measure_full(&mut self) -> LinePosition;
 }
 
 /// The driver for the line tracker module board, which has three pins corresponding to each one of the three line trackers.
-pub struct LineTracker {
-    pin_left: Pin<Input<AnyInput>>,
-    pin_center: Pin<Input<AnyInput>>,
-    pin_right: Pin<Input<AnyInput>>,
+pub struct LineTracker<P> {
+    pin_left: P,
+    pin_center: P,
+    pin_right: P,
 }
 
-impl LineTracker {
-    pub fn new(pin_left: Pin<Input<AnyInput>>, pin_center: Pin<Input<AnyInput>>, pin_right: Pin<Input<AnyInput>>) -> Self {
+impl<P: InputPin<Error = Infallible>> LineTracker<P> {
+    pub fn new(pin_left: P, pin_center: P, pin_right: P) -> Self {
         Self {
             pin_left,
             pin_center,
@@ -149,16 +215,61 @@ impl LineTracker {
         };
 
         // The line tracker drives the pin low when it is on the line, and it is tied high otherwise.
-        let state = pin.is_low();
+        let state = infallible(pin.is_low());
         LineState::from(state)
     }
 
     /// Measure the three line trackers together, packed into a [LinePosition].
     pub fn measure_full(&mut self) -> LinePosition {
         LinePosition {
-            left: LineState::from(self.pin_left.is_low()),
-            mid: LineState::from(self.pin_center.is_low()),
-            right: LineState::from(self.pin_right.is_low()),
+            left: LineState::from(infallible(self.pin_left.is_low())),
+            mid: LineState::from(infallible(self.pin_center.is_low())),
+            right: LineState::from(infallible(self.pin_right.is_low())),
+        }
+    }
+}
+impl<P: InputPin<Error = Infallible>> MeasureLine for LineTracker<P> {
+    fn measure_full(&mut self) -> LinePosition {
+        LineTracker::measure_full(self)
+    }
+}
+
+/// The readings of the V4 kit's ITR20001 sensors that mean a dark surface.
+///
+/// The sensors read lower over a light surface, and higher than this range when the car is lifted off the ground,
+/// which should not look like a line.
+pub const ITR20001_DARK_RANGE: Range<u16> = 250..850;
+
+/// The driver for a line tracker with three analog sensors, which compares their readings to a range.
+pub struct AnalogLineTracker<S> {
+    sensors: S,
+    dark_range: Range<u16>,
+}
+
+impl<S: AnalogLineSensors> AnalogLineTracker<S> {
+    /// Creates the driver, with the range of sensor readings that mean a dark surface, like [ITR20001_DARK_RANGE].
+    pub fn new(sensors: S, dark_range: Range<u16>) -> Self {
+        Self { sensors, dark_range }
+    }
+
+    /// Measure a single line tracker in the specified direction.
+    pub fn measure_direction(&mut self, direction: LineTrackerDirection) -> LineState {
+        let value = self.sensors.read(direction);
+        LineState::from(self.dark_range.contains(&value))
+    }
+
+    /// Measure the three line trackers one after another, packed into a [LinePosition].
+    pub fn measure_full(&mut self) -> LinePosition {
+        LinePosition {
+            left: self.measure_direction(LineTrackerDirection::Left),
+            mid: self.measure_direction(LineTrackerDirection::Center),
+            right: self.measure_direction(LineTrackerDirection::Right),
         }
     }
-}
\ No newline at end of file
+}
+
+impl<S: AnalogLineSensors> MeasureLine for AnalogLineTracker<S> {
+    fn measure_full(&mut self) -> LinePosition {
+        AnalogLineTracker::measure_full(self)
+    }
+}
@@ -0,0 +1,228 @@
+//! Acceleration ramping for the motor commands.
+//!
+//! Jumping straight from one speed to another (especially from forward to backward)
+//! jerks the chassis and can brown out the Arduino on weak batteries.
+//! The [SpeedRamp] moves the wheel commands toward their targets at a limited acceleration instead.
+//!
+//! The ramp does not keep time by itself: call [SpeedRamp::update] with the current time as often as possible,
+//! and pass the result to the motor driver. The [RampedDrive] does that for any [Drive],
+//! so that the behaviors, which only know the [Drive] trait, are ramped without knowing about it.
+
+use crate::clock;
+use crate::differential_drive::{self, Drive};
+
+/// A slew-rate limiter for the two signed wheel commands.
+pub struct SpeedRamp {
+    /// How much a wheel command may change per second.
+    acceleration: u16,
+    current: (i16, i16),
+    target: (i16, i16),
+    /// The time of the last update that changed the current commands.
+    last_update_ms: u64,
+}
+
+impl SpeedRamp {
+    /// Creates a new ramp with both wheels stopped.
+    ///
+    /// `acceleration` is the change in wheel command per second:
+    /// for example, 510 takes half a second to go from stopped to full speed.
+    pub fn new(acceleration: u16, now_ms: u64) -> Self {
+        Self {
+            acceleration,
+            current: (0, 0),
+            target: (0, 0),
+            last_update_ms: now_ms,
+        }
+    }
+
+    /// Set the acceleration, as the change in wheel command per second.
+    pub fn set_acceleration(&mut self, acceleration: u16) {
+        self.acceleration = acceleration;
+    }
+
+    /// Set the wheel commands that the ramp should move towards.
+    pub fn set_target(&mut self, left: i16, right: i16) {
+        self.target = (
+            differential_drive::clamp_speed(left),
+            differential_drive::clamp_speed(right),
+        );
+    }
+
+    /// Stop ramping and jump to the given wheel commands right away, for example after an emergency brake.
+    pub fn reset(&mut self, left: i16, right: i16, now_ms: u64) {
+        self.set_target(left, right);
+        self.current = self.target;
+        self.last_update_ms = now_ms;
+    }
+
+    /// The wheel commands that the ramp is moving towards.
+    pub fn target(&self) -> (i16, i16) {
+        self.target
+    }
+
+    /// The wheel commands as of the last update.
+    pub fn current(&self) -> (i16, i16) {
+        self.current
+    }
+
+    /// Whether the current wheel commands have reached the target.
+    pub fn is_done(&self) -> bool {
+        self.current == self.target
+    }
+
+    /// Move the current wheel commands toward the target, according to the time passed since the last update.
+    ///
+    /// Returns the new wheel commands.
+    pub fn update(&mut self, now_ms: u64) -> (i16, i16) {
+        let elapsed = now_ms.saturating_sub(self.last_update_ms);
+        let step = (self.acceleration as u64 * elapsed / 1000).min(i16::MAX as u64) as i16;
+
+        // If the update is called very often, the step may be rounded down to zero.
+        // In that case, we leave the time of the last update alone, so the elapsed time keeps accumulating.
+        if step == 0 {
+            if self.acceleration == 0 {
+                self.last_update_ms = now_ms;
+            }
+            return self.current;
+        }
+
+        self.current = (
+            Self::approach(self.current.0, self.target.0, step),
+            Self::approach(self.current.1, self.target.1, step),
+        );
+        self.last_update_ms = now_ms;
+        self.current
+    }
+
+    /// Move one wheel command toward its target by at most `step`.
+    ///
+    /// A change of direction always stops at zero first, so the motor is never reversed in a single step.
+    fn approach(current: i16, target: i16, step: i16) -> i16 {
+        let next = if current < target {
+            current.saturating_add(step).min(target)
+        } else {
+            current.saturating_sub(step).max(target)
+        };
+
+        if (current > 0 && next < 0) || (current < 0 && next > 0) {
+            0
+        } else {
+            next
+        }
+    }
+}
+
+/// A motor driver whose wheel commands go through a [SpeedRamp].
+///
+/// [Drive::drive] only sets the target, so [RampedDrive::update] must be called on every pass of the main loop
+/// to keep the wheels moving toward it. Braking and coasting are not ramped, since they are meant to stop the robot now.
+pub struct RampedDrive<C> {
+    chassis: C,
+    ramp: SpeedRamp,
+}
+
+impl<C: Drive> RampedDrive<C> {
+    /// Wraps a motor driver, with the wheels assumed to be stopped.
+    ///
+    /// `acceleration` is the change in wheel command per second, see [SpeedRamp::new].
+    pub fn new(chassis: C, acceleration: u16) -> Self {
+        Self {
+            chassis,
+            ramp: SpeedRamp::new(acceleration, clock::millis()),
+        }
+    }
+
+    /// Get the wrapped motor driver, to command it directly, bypassing the ramp.
+    pub fn chassis(&mut self) -> &mut C {
+        &mut self.chassis
+    }
+
+    /// Get the ramp, for example to change its acceleration.
+    pub fn ramp(&mut self) -> &mut SpeedRamp {
+        &mut self.ramp
+    }
+
+    /// Give back the wrapped motor driver.
+    pub fn release(self) -> C {
+        self.chassis
+    }
+
+    /// The wheel commands that the motors are running at, on the way to the target.
+    pub fn command(&self) -> (i16, i16) {
+        self.ramp.current()
+    }
+
+    /// Move the wheel commands toward the target, and drive the motors with them if they changed.
+    pub fn update(&mut self) {
+        let before = self.ramp.current();
+        let (left, right) = self.ramp.update(clock::millis());
+        if (left, right) != before {
+            self.chassis.drive(left, right);
+        }
+    }
+}
+
+impl<C: Drive> Drive for RampedDrive<C> {
+    fn drive(&mut self, left: i16, right: i16) {
+        self.ramp.set_target(left, right);
+        self.update();
+    }
+
+    fn brake(&mut self) {
+        self.ramp.reset(0, 0, clock::millis());
+        self.chassis.brake();
+    }
+
+    fn coast(&mut self) {
+        self.ramp.reset(0, 0, clock::millis());
+        self.chassis.coast();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_the_slew_rate() {
+        let mut ramp = SpeedRamp::new(510, 0);
+        ramp.set_target(255, 100);
+        assert_eq!(ramp.update(100), (51, 51));
+        assert_eq!(ramp.update(200), (102, 100));
+        assert!(!ramp.is_done());
+        assert_eq!(ramp.update(1000), (255, 100));
+        assert!(ramp.is_done());
+    }
+
+    #[test]
+    fn short_updates_accumulate() {
+        let mut ramp = SpeedRamp::new(100, 0);
+        ramp.set_target(100, 100);
+        // Less than one step per millisecond is rounded down, but the time is not lost.
+        for now in 1..10 {
+            assert_eq!(ramp.update(now), (0, 0));
+        }
+        assert_eq!(ramp.update(10), (1, 1));
+    }
+
+    #[test]
+    fn reversal_stops_at_zero() {
+        let mut ramp = SpeedRamp::new(1000, 0);
+        ramp.reset(50, -50, 0);
+        ramp.set_target(-200, 200);
+        // A step of 100 would take the wheels straight to -50 and 50, but they stop at zero first.
+        assert_eq!(ramp.update(100), (0, 0));
+        assert_eq!(ramp.update(200), (-100, 100));
+        assert_eq!(ramp.update(300), (-200, 200));
+    }
+
+    #[test]
+    fn targets_are_clamped_and_reset_jumps() {
+        let mut ramp = SpeedRamp::new(510, 0);
+        ramp.set_target(1000, -1000);
+        assert_eq!(ramp.target(), (255, -255));
+        ramp.reset(0, 0, 50);
+        assert_eq!(ramp.current(), (0, 0));
+        assert!(ramp.is_done());
+    }
+}
@@ -0,0 +1,254 @@
+//! The decision logic of the obstacle avoidance behavior.
+//!
+//! The robot drives forward until the distance sensor sees an obstacle closer than a threshold.
+//! Then it stops, scans to the left and to the right with the servo, and turns toward the side with more room.
+//! If both sides are blocked, it backs up and scans again.
+//!
+//! This module is only the state machine: it takes events (time passing, sensor readings)
+//! and says what the motors and the scanner should do;
+//! see [crate::avoid_mode] for the part that runs it on the robot.
+
+/// The settings of the obstacle avoidance behavior.
+#[derive(Debug, Clone, Copy)]
+pub struct AvoidConfig {
+    /// Stop when an obstacle in front is closer than this, in mm.
+    pub obstacle_mm: u64,
+    /// A side is only worth turning to if it has at least this much room, in mm.
+    pub clear_mm: u64,
+    /// The wheel command when driving forward or backing up.
+    pub cruise_speed: i16,
+    /// The wheel command when turning in place.
+    pub turn_speed: i16,
+    /// How long to turn in place toward the chosen side, in ms.
+    pub turn_ms: u64,
+    /// How long to back up when both sides are blocked, in ms.
+    pub backup_ms: u64,
+    /// How long to wait after braking before scanning, in ms, so the chassis stops rocking.
+    pub stop_ms: u64,
+}
+
+impl Default for AvoidConfig {
+    fn default() -> Self {
+        Self {
+            obstacle_mm: 250,
+            clear_mm: 400,
+            cruise_speed: 160,
+            turn_speed: 180,
+            turn_ms: 400,
+            backup_ms: 500,
+            stop_ms: 200,
+        }
+    }
+}
+
+/// A side of the robot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// The state of the obstacle avoidance behavior.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AvoidState {
+    /// Driving forward and watching the distance in front.
+    Cruising,
+    /// Braked in front of an obstacle, waiting for the chassis to settle until the given time.
+    Stopping { until_ms: u64 },
+    /// Waiting for the scan of both sides to finish.
+    Scanning,
+    /// Turning in place toward the given side until the given time.
+    Turning { side: Side, until_ms: u64 },
+    /// Backing up until the given time.
+    BackingUp { until_ms: u64 },
+}
+
+/// Something that happened, which the behavior must react to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AvoidEvent {
+    /// Nothing new, but time has passed.
+    Tick,
+    /// A reading of the distance in front, in mm. `None` means the sensor did not get a reading;
+    /// an infinite distance should be given as `Some(u64::MAX)`.
+    FrontDistance(Option<u64>),
+    /// The scan has finished, with the most room seen on each side, in mm.
+    ScanFinished { left_mm: u64, right_mm: u64 },
+}
+
+/// What the robot should do in response to an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AvoidAction {
+    /// Keep doing what it was doing.
+    Continue,
+    /// Drive the wheels with these signed commands.
+    Drive(i16, i16),
+    /// Brake the motors.
+    Brake,
+    /// Stop the motors and scan both sides, then report back with [AvoidEvent::ScanFinished].
+    StartScan,
+}
+
+/// The obstacle avoidance state machine.
+pub struct AvoidBehavior {
+    config: AvoidConfig,
+    state: AvoidState,
+}
+
+impl AvoidBehavior {
+    /// Creates the behavior in the cruising state.
+    ///
+    /// The robot is not moving yet: the first [AvoidEvent::Tick] starts it.
+    pub fn new(config: AvoidConfig) -> Self {
+        Self {
+            config,
+            state: AvoidState::Cruising,
+        }
+    }
+
+    pub fn state(&self) -> AvoidState {
+        self.state
+    }
+
+    pub fn config(&self) -> &AvoidConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: AvoidConfig) {
+        self.config = config;
+    }
+
+    /// Whether the behavior wants the distance in front to be measured.
+    pub fn wants_front_distance(&self) -> bool {
+        self.state == AvoidState::Cruising
+    }
+
+    /// React to an event at the given time, possibly changing the state.
+    pub fn handle(&mut self, now_ms: u64, event: AvoidEvent) -> AvoidAction {
+        let config = self.config;
+        let forward = Self::drive(config.cruise_speed, config.cruise_speed);
+
+        match (self.state, event) {
+            (AvoidState::Cruising, AvoidEvent::FrontDistance(Some(distance))) if distance < config.obstacle_mm => {
+                self.state = AvoidState::Stopping { until_ms: now_ms + config.stop_ms };
+                AvoidAction::Brake
+            },
+            (AvoidState::Cruising, _) => forward,
+
+            (AvoidState::Stopping { until_ms }, AvoidEvent::Tick) if now_ms >= until_ms => {
+                self.state = AvoidState::Scanning;
+                AvoidAction::StartScan
+            },
+
+            (AvoidState::Scanning, AvoidEvent::ScanFinished { left_mm, right_mm }) => {
+                if left_mm < config.clear_mm && right_mm < config.clear_mm {
+                    self.state = AvoidState::BackingUp { until_ms: now_ms + config.backup_ms };
+                    return Self::drive(-config.cruise_speed, -config.cruise_speed);
+                }
+
+                let side = if left_mm >= right_mm { Side::Left } else { Side::Right };
+                self.state = AvoidState::Turning { side, until_ms: now_ms + config.turn_ms };
+                match side {
+                    Side::Left => Self::drive(-config.turn_speed, config.turn_speed),
+                    Side::Right => Self::drive(config.turn_speed, -config.turn_speed),
+                }
+            },
+
+            (AvoidState::Turning { until_ms, .. }, AvoidEvent::Tick) if now_ms >= until_ms => {
+                self.state = AvoidState::Cruising;
+                forward
+            },
+
+            // After backing up, look around again before going anywhere.
+            (AvoidState::BackingUp { until_ms }, AvoidEvent::Tick) if now_ms >= until_ms => {
+                self.state = AvoidState::Stopping { until_ms: now_ms + config.stop_ms };
+                AvoidAction::Brake
+            },
+
+            _ => AvoidAction::Continue,
+        }
+    }
+
+    fn drive(left: i16, right: i16) -> AvoidAction {
+        AvoidAction::Drive(left, right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn behavior() -> AvoidBehavior {
+        AvoidBehavior::new(AvoidConfig::default())
+    }
+
+    /// Drive the behavior up to the scanning state, with an obstacle seen at time 0.
+    fn scanning_behavior() -> AvoidBehavior {
+        let mut avoid = behavior();
+        avoid.handle(0, AvoidEvent::FrontDistance(Some(100)));
+        avoid.handle(1000, AvoidEvent::Tick);
+        assert_eq!(avoid.state(), AvoidState::Scanning);
+        avoid
+    }
+
+    #[test]
+    fn cruises_while_clear() {
+        let mut avoid = behavior();
+        assert_eq!(avoid.handle(0, AvoidEvent::Tick), AvoidAction::Drive(160, 160));
+        assert_eq!(avoid.handle(10, AvoidEvent::FrontDistance(Some(1000))), AvoidAction::Drive(160, 160));
+        assert_eq!(avoid.handle(20, AvoidEvent::FrontDistance(None)), AvoidAction::Drive(160, 160));
+        assert_eq!(avoid.handle(30, AvoidEvent::FrontDistance(Some(u64::MAX))), AvoidAction::Drive(160, 160));
+        assert_eq!(avoid.state(), AvoidState::Cruising);
+    }
+
+    #[test]
+    fn brakes_then_scans_on_obstacle() {
+        let mut avoid = behavior();
+        assert_eq!(avoid.handle(100, AvoidEvent::FrontDistance(Some(200))), AvoidAction::Brake);
+        assert_eq!(avoid.state(), AvoidState::Stopping { until_ms: 300 });
+        assert!(!avoid.wants_front_distance());
+
+        assert_eq!(avoid.handle(299, AvoidEvent::Tick), AvoidAction::Continue);
+        assert_eq!(avoid.handle(300, AvoidEvent::Tick), AvoidAction::StartScan);
+        assert_eq!(avoid.state(), AvoidState::Scanning);
+    }
+
+    #[test]
+    fn turns_toward_the_roomier_side() {
+        let mut avoid = scanning_behavior();
+        let action = avoid.handle(2000, AvoidEvent::ScanFinished { left_mm: 1500, right_mm: 500 });
+        assert_eq!(action, AvoidAction::Drive(-180, 180));
+        assert_eq!(avoid.state(), AvoidState::Turning { side: Side::Left, until_ms: 2400 });
+
+        let mut avoid = scanning_behavior();
+        let action = avoid.handle(2000, AvoidEvent::ScanFinished { left_mm: 500, right_mm: u64::MAX });
+        assert_eq!(action, AvoidAction::Drive(180, -180));
+        assert_eq!(avoid.state(), AvoidState::Turning { side: Side::Right, until_ms: 2400 });
+    }
+
+    #[test]
+    fn resumes_cruising_after_turn() {
+        let mut avoid = scanning_behavior();
+        avoid.handle(2000, AvoidEvent::ScanFinished { left_mm: 1500, right_mm: 500 });
+        assert_eq!(avoid.handle(2399, AvoidEvent::Tick), AvoidAction::Continue);
+        assert_eq!(avoid.handle(2400, AvoidEvent::Tick), AvoidAction::Drive(160, 160));
+        assert_eq!(avoid.state(), AvoidState::Cruising);
+    }
+
+    #[test]
+    fn backs_up_and_rescans_when_boxed_in() {
+        let mut avoid = scanning_behavior();
+        let action = avoid.handle(2000, AvoidEvent::ScanFinished { left_mm: 300, right_mm: 100 });
+        assert_eq!(action, AvoidAction::Drive(-160, -160));
+        assert_eq!(avoid.state(), AvoidState::BackingUp { until_ms: 2500 });
+
+        assert_eq!(avoid.handle(2500, AvoidEvent::Tick), AvoidAction::Brake);
+        assert_eq!(avoid.handle(2700, AvoidEvent::Tick), AvoidAction::StartScan);
+    }
+
+    #[test]
+    fn ignores_front_distance_while_not_cruising() {
+        let mut avoid = scanning_behavior();
+        assert_eq!(avoid.handle(2000, AvoidEvent::FrontDistance(Some(10))), AvoidAction::Continue);
+        assert_eq!(avoid.state(), AvoidState::Scanning);
+    }
+}
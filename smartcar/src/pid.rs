@@ -0,0 +1,192 @@
+//! A fixed-point PID controller.
+//!
+//! The gains are given in thousandths, so a `kp` of 150 means that an error of 1000 gives an output of 150.
+//! The integral is in error-seconds and the derivative in error per second,
+//! so the gains do not depend on how often the controller is updated.
+//!
+//! The integral is clamped to a limit, so that it does not wind up while the output is saturated
+//! (for example while the robot is searching for a lost line).
+
+/// The gains of a [Pid] controller, in thousandths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PidGains {
+    pub kp: i32,
+    pub ki: i32,
+    pub kd: i32,
+}
+
+/// A PID controller.
+pub struct Pid {
+    gains: PidGains,
+    /// The largest magnitude that the integral term may reach, in error-seconds.
+    integral_limit: i32,
+    /// The largest magnitude of the output.
+    output_limit: i32,
+    /// The accumulated error, in error-milliseconds, so that short updates are not rounded away.
+    integral_ms: i64,
+    /// The error and the time of the last update.
+    last: Option<(i32, u64)>,
+}
+
+impl Pid {
+    pub fn new(gains: PidGains, integral_limit: i32, output_limit: i32) -> Self {
+        Self {
+            gains,
+            integral_limit,
+            output_limit,
+            integral_ms: 0,
+            last: None,
+        }
+    }
+
+    pub fn gains(&self) -> PidGains {
+        self.gains
+    }
+
+    /// Change the gains, for example while tuning the robot over the serial port.
+    ///
+    /// The accumulated integral is kept, so the output does not jump when only `kp` or `kd` change.
+    pub fn set_gains(&mut self, gains: PidGains) {
+        self.gains = gains;
+    }
+
+    pub fn set_integral_limit(&mut self, integral_limit: i32) {
+        self.integral_limit = integral_limit;
+        self.clamp_integral();
+    }
+
+    pub fn set_output_limit(&mut self, output_limit: i32) {
+        self.output_limit = output_limit;
+    }
+
+    /// The accumulated integral, in error-seconds.
+    pub fn integral(&self) -> i32 {
+        (self.integral_ms / 1000) as i32
+    }
+
+    /// Forget the accumulated integral and the last error, for example when the robot is put on a new line.
+    pub fn reset(&mut self) {
+        self.integral_ms = 0;
+        self.last = None;
+    }
+
+    /// Feed the current error at the given time, and get the output.
+    ///
+    /// The first update after creating or resetting the controller only has a proportional term,
+    /// since there is no time interval yet.
+    pub fn update(&mut self, error: i32, now_ms: u64) -> i32 {
+        let mut derivative: i64 = 0;
+
+        if let Some((last_error, last_ms)) = self.last {
+            let dt_ms = now_ms.saturating_sub(last_ms) as i64;
+            if dt_ms > 0 {
+                self.integral_ms += error as i64 * dt_ms;
+                self.clamp_integral();
+                derivative = (error as i64 - last_error as i64) * 1000 / dt_ms;
+            }
+        }
+        self.last = Some((error, now_ms));
+
+        let output = (self.gains.kp as i64 * error as i64
+            + self.gains.ki as i64 * self.integral_ms / 1000
+            + self.gains.kd as i64 * derivative)
+            / 1000;
+
+        let limit = self.output_limit as i64;
+        output.max(-limit).min(limit) as i32
+    }
+
+    fn clamp_integral(&mut self) {
+        let limit = self.integral_limit as i64 * 1000;
+        self.integral_ms = self.integral_ms.max(-limit).min(limit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pid(kp: i32, ki: i32, kd: i32) -> Pid {
+        Pid::new(PidGains { kp, ki, kd }, 1000, 255)
+    }
+
+    #[test]
+    fn proportional_only() {
+        let mut pid = pid(100, 0, 0);
+        assert_eq!(pid.update(1000, 0), 100);
+        assert_eq!(pid.update(-500, 10), -50);
+        assert_eq!(pid.update(0, 20), 0);
+    }
+
+    #[test]
+    fn integral_accumulates_over_time() {
+        let mut pid = pid(0, 100, 0);
+        assert_eq!(pid.update(500, 0), 0);
+        // 500 for 1s is 500 error-seconds.
+        assert_eq!(pid.update(500, 1000), 50);
+        assert_eq!(pid.integral(), 500);
+        // Many short updates add up the same as one long one.
+        for t in 1..=100 {
+            pid.update(500, 1000 + t * 10);
+        }
+        assert_eq!(pid.integral(), 1000);
+    }
+
+    #[test]
+    fn integral_windup_is_clamped() {
+        let mut pid = pid(0, 100, 0);
+        pid.update(2000, 0);
+        pid.update(2000, 10_000);
+        assert_eq!(pid.integral(), 1000);
+        assert_eq!(pid.update(2000, 20_000), 100);
+
+        // Since the integral did not wind up past the limit, it unwinds quickly once the error changes sign.
+        pid.update(-2000, 20_500);
+        assert_eq!(pid.integral(), 0);
+        pid.update(-2000, 40_000);
+        assert_eq!(pid.integral(), -1000);
+    }
+
+    #[test]
+    fn lowering_integral_limit_clamps_integral() {
+        let mut pid = pid(0, 100, 0);
+        pid.update(1000, 0);
+        pid.update(1000, 1000);
+        pid.set_integral_limit(200);
+        assert_eq!(pid.integral(), 200);
+    }
+
+    #[test]
+    fn derivative_responds_to_change() {
+        let mut pid = pid(0, 0, 10);
+        assert_eq!(pid.update(0, 0), 0);
+        // The error grows by 1000 in 100ms: 10000 per second.
+        assert_eq!(pid.update(1000, 100), 100);
+        assert_eq!(pid.update(1000, 200), 0);
+    }
+
+    #[test]
+    fn output_is_clamped() {
+        let mut pid = pid(1000, 0, 0);
+        assert_eq!(pid.update(2000, 0), 255);
+        assert_eq!(pid.update(-2000, 10), -255);
+    }
+
+    #[test]
+    fn gains_can_change_at_runtime() {
+        let mut pid = pid(100, 0, 0);
+        assert_eq!(pid.update(1000, 0), 100);
+        pid.set_gains(PidGains { kp: 200, ki: 0, kd: 0 });
+        assert_eq!(pid.update(1000, 10), 200);
+    }
+
+    #[test]
+    fn reset_forgets_history() {
+        let mut pid = pid(0, 100, 10);
+        pid.update(1000, 0);
+        pid.update(1000, 1000);
+        pid.reset();
+        assert_eq!(pid.integral(), 0);
+        assert_eq!(pid.update(1000, 2000), 0);
+    }
+}
@@ -0,0 +1,375 @@
+//! Sweeping the distance sensor with the servo to get a picture of the surroundings.
+//!
+//! The HC-SR04 sits on the servo stick, so by turning the servo step by step
+//! and pinging at every step, we get the distance to the nearest object in every direction.
+//! The result is a [ScanProfile], which is what the obstacle avoidance behaviors decide on.
+//!
+//! The servo angles are as the servo sees them: 90 degrees is straight ahead,
+//! and which side is 0 degrees depends on how the servo is mounted.
+
+use core::convert::Infallible;
+
+use ufmt::derive::uDebug;
+
+use crate::hal::{EchoTimer, OutputPin, PwmPin};
+use crate::hc_sr04_distance_sensor::{Distance, DistanceMeasurement, HC_SR04, MeasurementError};
+use crate::servo::Servo;
+
+/// The most points that a single scan can have: enough for 0 to 180 degrees every 10 degrees.
+pub const MAX_SCAN_POINTS: usize = 19;
+
+/// Which angles to scan, and how fast to turn the servo between them.
+#[derive(Clone, Copy)]
+pub struct ScanConfig {
+    /// The angle of the first point, in degrees.
+    pub start_angle: u8,
+    /// The angle of the last point, in degrees. It may be smaller than the start angle, to scan the other way.
+    pub end_angle: u8,
+    /// The number of degrees between points.
+    pub step: u8,
+    /// How fast to turn the servo between points, in degrees per second.
+    pub sweep_speed: u16,
+}
+
+impl Default for ScanConfig {
+    /// From 0 to 180 degrees every 15 degrees.
+    fn default() -> Self {
+        Self {
+            start_angle: 0,
+            end_angle: 180,
+            step: 15,
+            sweep_speed: 180,
+        }
+    }
+}
+
+/// The ways that a [ScanConfig] can be wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanConfigError {
+    /// The step between points is zero.
+    ZeroStep,
+    /// The scan has more points than a [ScanProfile] can hold, see [MAX_SCAN_POINTS].
+    TooManyPoints,
+}
+
+impl ScanConfig {
+    /// Check that the scan has a step, and that all of its points fit into a [ScanProfile].
+    pub fn validate(&self) -> Result<(), ScanConfigError> {
+        if self.step == 0 {
+            return Err(ScanConfigError::ZeroStep);
+        }
+        if self.point_count() > MAX_SCAN_POINTS {
+            return Err(ScanConfigError::TooManyPoints);
+        }
+        Ok(())
+    }
+
+    /// The number of points in the scan.
+    ///
+    /// The last point is the last one that does not go past the end angle.
+    /// A config with a zero step is not valid, and only has its first point.
+    pub fn point_count(&self) -> usize {
+        if self.step == 0 {
+            return 1;
+        }
+        let range = (self.end_angle as i16 - self.start_angle as i16).abs() as usize;
+        range / self.step as usize + 1
+    }
+
+    /// The angle of the point with the given index.
+    pub fn angle_at(&self, index: usize) -> u8 {
+        let offset = (index * self.step as usize) as i16;
+        if self.end_angle >= self.start_angle {
+            self.start_angle.saturating_add(offset as u8)
+        } else {
+            self.start_angle.saturating_sub(offset as u8)
+        }
+    }
+}
+
+/// A range of consecutive clear directions in a [ScanProfile].
+#[derive(uDebug, Debug, Clone, Copy)]
+pub struct ScanGap {
+    /// The angle of the first clear point.
+    pub start_angle: u8,
+    /// The angle of the last clear point.
+    pub end_angle: u8,
+    /// The number of clear points in the gap.
+    pub points: usize,
+}
+
+impl ScanGap {
+    /// The angle in the middle of the gap, which is the best direction to go through it.
+    pub fn center_angle(&self) -> u8 {
+        ((self.start_angle as u16 + self.end_angle as u16) / 2) as u8
+    }
+}
+
+/// The distances measured in every direction of a scan.
+#[derive(Clone, Copy)]
+pub struct ScanProfile {
+    points: [(u8, DistanceMeasurement); MAX_SCAN_POINTS],
+    len: usize,
+}
+
+impl Default for ScanProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScanProfile {
+    /// Creates an empty profile.
+    pub fn new() -> Self {
+        Self {
+            points: [(0, DistanceMeasurement::Unknown); MAX_SCAN_POINTS],
+            len: 0,
+        }
+    }
+
+    /// Add a point to the profile. If the profile is full, the point is dropped.
+    pub fn push(&mut self, angle: u8, measurement: DistanceMeasurement) {
+        if self.len < MAX_SCAN_POINTS {
+            self.points[self.len] = (angle, measurement);
+            self.len += 1;
+        }
+    }
+
+    /// The points of the profile, as `(angle, measurement)`, in the order they were scanned.
+    pub fn points(&self) -> &[(u8, DistanceMeasurement)] {
+        &self.points[..self.len]
+    }
+
+    /// The measurement at the point nearest to the given angle.
+    pub fn measurement_at(&self, angle: u8) -> Option<DistanceMeasurement> {
+        self.points()
+            .iter()
+            .min_by_key(|(point_angle, _)| (*point_angle as i16 - angle as i16).abs())
+            .map(|(_, measurement)| *measurement)
+    }
+
+    /// The angle and distance of the closest measured object.
+    pub fn nearest_obstacle(&self) -> Option<(u8, Distance)> {
+        self.points()
+            .iter()
+            .filter_map(|(angle, measurement)| match measurement {
+                DistanceMeasurement::Measured(distance) => Some((*angle, *distance)),
+                _ => None,
+            })
+            .min_by_key(|(_, distance)| distance.to_um())
+    }
+
+    /// The most room seen at any point with an angle in `from_angle..=to_angle`, in mm.
+    ///
+    /// `Infinity` points count as `u64::MAX`, and `Unknown` points (or no points at all) as 0.
+    pub fn most_room(&self, from_angle: u8, to_angle: u8) -> u64 {
+        self.points()
+            .iter()
+            .filter(|(angle, _)| from_angle <= *angle && *angle <= to_angle)
+            .map(|(_, measurement)| match measurement {
+                DistanceMeasurement::Measured(distance) => distance.to_mm(),
+                DistanceMeasurement::Infinity => u64::MAX,
+                DistanceMeasurement::Unknown => 0,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The longest run of consecutive points where there is at least `min_clear_mm` of free space.
+    ///
+    /// A point counts as clear if it is `Infinity` or measured further than that.
+    /// `Unknown` points are not clear, since we cannot be sure about them.
+    pub fn widest_gap(&self, min_clear_mm: u64) -> Option<ScanGap> {
+        let mut widest: Option<ScanGap> = None;
+        let mut current: Option<ScanGap> = None;
+
+        for (angle, measurement) in self.points() {
+            let clear = match measurement {
+                DistanceMeasurement::Measured(distance) => distance.to_mm() >= min_clear_mm,
+                DistanceMeasurement::Infinity => true,
+                DistanceMeasurement::Unknown => false,
+            };
+
+            current = if clear {
+                Some(match current {
+                    Some(gap) => ScanGap { end_angle: *angle, points: gap.points + 1, ..gap },
+                    None => ScanGap { start_angle: *angle, end_angle: *angle, points: 1 },
+                })
+            } else {
+                None
+            };
+
+            if let Some(gap) = current {
+                if widest.map_or(true, |widest| gap.points > widest.points) {
+                    widest = Some(gap);
+                }
+            }
+        }
+
+        widest
+    }
+}
+
+/// The progress of a scan.
+#[derive(Clone, Copy)]
+enum ScanState {
+    Idle,
+    /// The servo is moving to the point with the given index.
+    Moving(usize),
+    /// The sensor is pinging at the point with the given index.
+    Pinging(usize),
+}
+
+/// Drives the servo and the distance sensor together to produce a [ScanProfile].
+///
+/// `S` is the type of the servo's output, and `P` and `T` are the Trig pin and the echo timer of the distance sensor.
+pub struct RangeScanner<S, P, T> {
+    servo: Servo<S>,
+    sensor: HC_SR04<P, T>,
+    config: ScanConfig,
+    state: ScanState,
+    profile: ScanProfile,
+}
+
+impl<S, P, T> RangeScanner<S, P, T>
+where
+    S: PwmPin<Duty = u16>,
+    P: OutputPin<Error = Infallible>,
+    T: EchoTimer,
+{
+    /// Creates a scanner, if the config is valid, see [ScanConfig::validate].
+    pub fn new(servo: Servo<S>, sensor: HC_SR04<P, T>, config: ScanConfig) -> Result<Self, ScanConfigError> {
+        config.validate()?;
+        Ok(Self {
+            servo,
+            sensor,
+            config,
+            state: ScanState::Idle,
+            profile: ScanProfile::new(),
+        })
+    }
+
+    /// Get the servo, for example to point it somewhere between scans.
+    pub fn servo(&mut self) -> &mut Servo<S> {
+        &mut self.servo
+    }
+
+    /// Get the distance sensor, for example to take a single reading between scans.
+    pub fn sensor(&mut self) -> &mut HC_SR04<P, T> {
+        &mut self.sensor
+    }
+
+    /// Give back the servo and the distance sensor.
+    pub fn release(self) -> (Servo<S>, HC_SR04<P, T>) {
+        (self.servo, self.sensor)
+    }
+
+    /// Change which angles the following scans cover.
+    ///
+    /// If the config is not valid, the old one is kept, see [ScanConfig::validate].
+    pub fn set_config(&mut self, config: ScanConfig) -> Result<(), ScanConfigError> {
+        config.validate()?;
+        self.config = config;
+        Ok(())
+    }
+
+    pub fn config(&self) -> &ScanConfig {
+        &self.config
+    }
+
+    /// Start a scan in the background, abandoning any scan in progress.
+    ///
+    /// Use [RangeScanner::poll] to move it along and get the result.
+    pub fn start(&mut self) {
+        self.profile = ScanProfile::new();
+        self.servo.move_to(self.config.angle_at(0), self.config.sweep_speed);
+        self.state = ScanState::Moving(0);
+    }
+
+    /// Move the scan along, and return the profile once every point is measured.
+    ///
+    /// If the sensor fails, the scan is abandoned and the error is returned, so a new scan can be started.
+    pub fn poll(&mut self) -> nb::Result<ScanProfile, MeasurementError> {
+        self.servo.update();
+
+        match self.state {
+            ScanState::Idle => Err(nb::Error::Other(MeasurementError::NotStarted)),
+            ScanState::Moving(index) => {
+                // Wait for the mast to stop wobbling before pinging.
+                if self.servo.is_settled() {
+                    self.sensor.start_measurement();
+                    self.state = ScanState::Pinging(index);
+                }
+                Err(nb::Error::WouldBlock)
+            },
+            ScanState::Pinging(index) => {
+                let measurement = match self.sensor.poll() {
+                    Ok(measurement) => measurement,
+                    Err(nb::Error::WouldBlock) => return Err(nb::Error::WouldBlock),
+                    Err(nb::Error::Other(error)) => {
+                        self.state = ScanState::Idle;
+                        return Err(nb::Error::Other(error));
+                    },
+                };
+                self.profile.push(self.config.angle_at(index), measurement);
+
+                let next = index + 1;
+                if next == self.config.point_count() {
+                    self.state = ScanState::Idle;
+                    return Ok(self.profile);
+                }
+
+                self.servo.move_to(self.config.angle_at(next), self.config.sweep_speed);
+                self.state = ScanState::Moving(next);
+                Err(nb::Error::WouldBlock)
+            },
+        }
+    }
+
+    /// Do a whole scan, blocking until it is finished.
+    pub fn scan(&mut self) -> ScanProfile {
+        self.start();
+        // The scan was just started, so it cannot fail with `NotStarted`.
+        nb::block!(self.poll()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        let config = ScanConfig::default();
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.point_count(), 13);
+        assert_eq!(config.angle_at(12), 180);
+    }
+
+    #[test]
+    fn rejects_a_zero_step() {
+        let config = ScanConfig { step: 0, ..ScanConfig::default() };
+        assert_eq!(config.validate(), Err(ScanConfigError::ZeroStep));
+    }
+
+    #[test]
+    fn rejects_scans_that_do_not_fit() {
+        // 0 to 180 every 5 degrees is 37 points.
+        let config = ScanConfig { step: 5, ..ScanConfig::default() };
+        assert_eq!(config.point_count(), 37);
+        assert_eq!(config.validate(), Err(ScanConfigError::TooManyPoints));
+
+        // Half of it fits.
+        let config = ScanConfig { step: 5, end_angle: 90, ..ScanConfig::default() };
+        assert_eq!(config.point_count(), MAX_SCAN_POINTS);
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn scans_backwards() {
+        let config = ScanConfig { start_angle: 150, end_angle: 30, step: 40, ..ScanConfig::default() };
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.point_count(), 4);
+        assert_eq!(config.angle_at(3), 30);
+    }
+}
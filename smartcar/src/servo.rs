@@ -0,0 +1,281 @@
+//! Driver for the servo motor attached to the distance sensor stick.
+//!
+//! Information:
+//! Servos use PWM to control the angle.
+//!
+//! To control a servo, you must send a rising edge once every 20ms.
+//! The time between the rising edge and the falling edge is the pulse width, and it determines the angle.
+//! Usually the smallest angle is achieved when the pulse width is 1ms, and the largest angle is when the pulse width is 2ms,
+//! but real servos differ, so the range is configured with a [ServoCalibration].
+//!
+//! The pulses are generated in the background by a [PwmPin] with a period of 20ms,
+//! so the servo keeps holding its position, and setting the angle does not block.
+//! On the robot, the servo is connected to Arduino port 3 (PD3), and the pulses come from the timer
+//! that also runs the millisecond clock, see the firmware's `servo_pulses` module.
+
+use crate::hal::PwmPin;
+
+/// The length of a 20ms servo frame, which is the period of the PWM output, in µs.
+pub const FRAME_US: u32 = 20_000;
+
+/// How fast the servo moves on its own when it is set to a new angle, in degrees per second.
+///
+/// The SG90 is rated at 0.1s per 60 degrees; this is used to guess when a jump has finished.
+const FREE_SPEED_DEG_PER_SEC: u32 = 600;
+
+/// How long to wait after the last commanded movement before the servo is considered settled, in ms.
+///
+/// This lets the mast stop wobbling before the distance sensor is used.
+const SETTLE_TIME_MS: u64 = 100;
+
+/// The calibration of a particular servo: which pulse widths correspond to which angles.
+///
+/// The angle range 0..=180 degrees is mapped linearly onto the pulse width range `min_us..=max_us`.
+#[derive(Debug, Clone, Copy)]
+pub struct ServoCalibration {
+    /// The pulse width for 0 degrees, in µs.
+    pub min_us: u16,
+    /// The pulse width for 180 degrees, in µs.
+    pub max_us: u16,
+    /// The number of degrees to add to every angle, to correct for a servo horn that is off-center.
+    pub center_trim: i8,
+    /// The smallest angle that the servo is allowed to go to; smaller angles are clamped to this.
+    pub min_angle: u8,
+    /// The largest angle that the servo is allowed to go to; larger angles are clamped to this.
+    pub max_angle: u8,
+}
+
+impl ServoCalibration {
+    /// The calibration of the SG90 servo that comes with the kit.
+    pub const SG90: Self = Self {
+        min_us: 544,
+        max_us: 2400,
+        center_trim: 0,
+        min_angle: 0,
+        max_angle: 180,
+    };
+
+    /// Clamp an angle to the allowed range, and apply the trim.
+    ///
+    /// The result is always between 0 and 180 degrees.
+    pub fn trimmed_angle(&self, angle: u8) -> u8 {
+        let angle = angle.max(self.min_angle).min(self.max_angle) as i16;
+        let trimmed = angle + self.center_trim as i16;
+        trimmed.clamp(0, 180) as u8
+    }
+
+    /// Get the pulse width for an angle, in µs.
+    pub fn pulse_width_us(&self, angle: u8) -> u16 {
+        // The span may be negative for a servo that is mounted the other way around.
+        let angle = self.trimmed_angle(angle) as i32;
+        let span = self.max_us as i32 - self.min_us as i32;
+        (self.min_us as i32 + span * angle / 180) as u16
+    }
+}
+
+impl Default for ServoCalibration {
+    /// The textbook servo calibration: 1ms to 2ms over 0 to 180 degrees.
+    fn default() -> Self {
+        Self {
+            min_us: 1000,
+            max_us: 2000,
+            center_trim: 0,
+            min_angle: 0,
+            max_angle: 180,
+        }
+    }
+}
+
+/// The representation of a servo position.
+/// 
+/// You can create one of these using [`ServoPhase::from_angle`] or [`ServoPhase::from_calibrated_angle`].
+/// This also contains a value which is implementation detail.
+#[derive(Debug, Clone, Copy)]
+pub struct ServoPhase {
+    pulse_us: u16,
+}
+
+impl ServoPhase {
+    /// Create a phase for an angle using the default calibration.
+    ///
+    /// Angles above 180 degrees are clamped to 180.
+    pub fn from_angle(angle: u8) -> Self {
+        Self::from_calibrated_angle(angle, &ServoCalibration::default())
+    }
+
+    /// Create a phase for an angle using the given calibration.
+    pub fn from_calibrated_angle(angle: u8, calibration: &ServoCalibration) -> Self {
+        Self {
+            pulse_us: calibration.pulse_width_us(angle),
+        }
+    }
+
+    /// The width of the pulse for this phase, in µs.
+    pub fn pulse_width_us(&self) -> u16 {
+        self.pulse_us
+    }
+}
+
+/// The driver for the servo motor, sending its pulses with a [PwmPin].
+///
+/// The PWM output must have a period of [FRAME_US], so its duty cycle is the pulse width.
+pub struct Servo<P> {
+    output: P,
+    calibration: ServoCalibration,
+    current_phase: ServoPhase,
+    /// The commanded position of the servo, in thousandths of a degree, so that slow sweeps do not stall on rounding.
+    position_mdeg: u32,
+    /// The angle that the servo is moving to, in degrees.
+    target_angle: u8,
+    /// The speed of the current sweep, in degrees per second.
+    speed: u16,
+    /// The time when the position was last updated.
+    last_update_ms: u64,
+    /// The time when the servo is expected to have physically stopped, if the commanded position has reached the target.
+    settled_at_ms: Option<u64>,
+}
+
+impl<P: PwmPin<Duty = u16>> Servo<P> {
+    /// Creates a new servo driver, and moves the servo to 90 degrees.
+    pub fn new(output: P, calibration: ServoCalibration) -> Self {
+        let mut new_servo = Self {
+            output,
+            calibration,
+            current_phase: ServoPhase::from_calibrated_angle(90, &calibration),
+            position_mdeg: 90_000,
+            target_angle: 90,
+            speed: 0,
+            last_update_ms: 0,
+            settled_at_ms: None,
+        };
+
+        new_servo.set_angle(90);
+        new_servo
+    }
+
+    /// Set the angle of the servo, in degrees, jumping straight to it.
+    ///
+    /// Angles outside of the calibration's allowed range are clamped.
+    /// This cancels any sweep started with [Servo::move_to].
+    pub fn set_angle(&mut self, angle: u8) {
+        let angle = self.clamp_angle(angle);
+        let now = crate::clock::millis();
+
+        // The servo moves at its own top speed, so guess how long that takes.
+        let distance = (angle as i16 - self.current_angle() as i16).abs() as u32;
+        let travel_ms = (distance * 1000 / FREE_SPEED_DEG_PER_SEC) as u64;
+
+        self.position_mdeg = angle as u32 * 1000;
+        self.target_angle = angle;
+        self.speed = 0;
+        self.last_update_ms = now;
+        self.settled_at_ms = Some(now + travel_ms + SETTLE_TIME_MS);
+        self.write_angle(angle);
+    }
+
+    /// Start a smooth sweep to the given angle, at the given speed in degrees per second.
+    ///
+    /// This returns right away; call [Servo::update] regularly to move the servo along,
+    /// and [Servo::is_settled] to find out when it has arrived.
+    pub fn move_to(&mut self, angle: u8, deg_per_sec: u16) {
+        if deg_per_sec == 0 {
+            self.set_angle(angle);
+            return;
+        }
+
+        self.target_angle = self.clamp_angle(angle);
+        self.speed = deg_per_sec;
+        self.last_update_ms = crate::clock::millis();
+        self.settled_at_ms = None;
+        self.update();
+    }
+
+    /// Move the servo along the sweep started with [Servo::move_to], according to the time passed since the last update.
+    ///
+    /// Does nothing if there is no sweep in progress.
+    pub fn update(&mut self) {
+        if self.settled_at_ms.is_some() {
+            return;
+        }
+
+        let now = crate::clock::millis();
+        // Degrees per second times milliseconds gives thousandths of a degree.
+        let step = self.speed as u64 * now.saturating_sub(self.last_update_ms);
+        if step == 0 {
+            return;
+        }
+        self.last_update_ms = now;
+
+        let target_mdeg = self.target_angle as u32 * 1000;
+        let step = step.min(u32::MAX as u64) as u32;
+        self.position_mdeg = if self.position_mdeg < target_mdeg {
+            self.position_mdeg.saturating_add(step).min(target_mdeg)
+        } else {
+            self.position_mdeg.saturating_sub(step).max(target_mdeg)
+        };
+
+        if self.position_mdeg == target_mdeg {
+            self.settled_at_ms = Some(now + SETTLE_TIME_MS);
+        }
+        self.write_angle(self.current_angle());
+    }
+
+    /// Whether the servo has reached its target angle and had time to stop moving.
+    pub fn is_settled(&self) -> bool {
+        match self.settled_at_ms {
+            Some(settled_at) => crate::clock::millis() >= settled_at,
+            None => false,
+        }
+    }
+
+    /// The angle that the servo is currently commanded to, in degrees.
+    ///
+    /// During a sweep, this is somewhere between the starting angle and [Servo::target_angle].
+    pub fn current_angle(&self) -> u8 {
+        (self.position_mdeg / 1000) as u8
+    }
+
+    /// The angle that the servo is moving to, in degrees.
+    pub fn target_angle(&self) -> u8 {
+        self.target_angle
+    }
+
+    /// Clamp an angle to the range allowed by the calibration.
+    fn clamp_angle(&self, angle: u8) -> u8 {
+        angle.max(self.calibration.min_angle).min(self.calibration.max_angle)
+    }
+
+    /// Output the given angle to the servo, without changing the sweep state.
+    fn write_angle(&mut self, angle: u8) {
+        let phase = ServoPhase::from_calibrated_angle(angle, &self.calibration);
+        self.set_phase(phase);
+    }
+
+    /// Get the calibration that this servo uses.
+    pub fn calibration(&self) -> &ServoCalibration {
+        &self.calibration
+    }
+
+    /// Set the servo by a [ServoPhase].
+    ///
+    /// This returns right away; the servo will start moving on the next 20ms frame,
+    /// and will keep holding the position until it is changed or [Servo::detach] is called.
+    pub fn set_phase(&mut self, phase: ServoPhase) {
+        self.current_phase = phase;
+        let duty = phase.pulse_width_us() as u32 * self.output.get_max_duty() as u32 / FRAME_US;
+        self.output.set_duty(duty as u16);
+        self.output.enable();
+    }
+
+    /// Get the [ServoPhase] that the servo was last set to.
+    pub fn get_phase(&self) -> ServoPhase {
+        self.current_phase
+    }
+
+    /// Stop sending pulses, so the servo no longer holds its position.
+    ///
+    /// Setting the angle or phase again will resume the pulses.
+    pub fn detach(&mut self) {
+        self.output.disable();
+    }
+}
@@ -0,0 +1,89 @@
+//! The speed of sound in air, which depends on the air temperature.
+//!
+//! The HC-SR04 measures the time of flight of the sound, so to turn it into a distance
+//! we need to know how fast the sound travels.
+//! Between 0°C and 35°C, it goes from about 331m/s to 352m/s, which is a difference of 6%.
+//! The speed is a whole number of mm/s, from a linear approximation in the temperature.
+
+/// The speed of sound at 0°C, in mm/s.
+const SPEED_AT_ZERO_MM_PER_SEC: i32 = 331_300;
+
+/// How much the speed of sound grows per degree Celsius, in mm/s.
+///
+/// This is the linear approximation of `331.3 * sqrt(1 + T / 273.15)`,
+/// which is within 0.2% of it between -20°C and 40°C.
+const SPEED_PER_DEGREE_MM_PER_SEC: i32 = 606;
+
+/// The speed of sound, stored in mm/s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoundSpeed {
+    mm_per_sec: u32,
+}
+
+impl SoundSpeed {
+    /// Creates a speed of sound from a known value, in mm/s.
+    pub const fn from_mm_per_sec(mm_per_sec: u32) -> Self {
+        Self { mm_per_sec }
+    }
+
+    /// Creates the speed of sound in air at the given temperature, in °C.
+    pub fn from_celsius(celsius: i8) -> Self {
+        let mm_per_sec = SPEED_AT_ZERO_MM_PER_SEC + SPEED_PER_DEGREE_MM_PER_SEC * celsius as i32;
+        Self { mm_per_sec: mm_per_sec as u32 }
+    }
+
+    /// The speed of sound, in mm/s.
+    pub fn mm_per_sec(&self) -> u32 {
+        self.mm_per_sec
+    }
+
+    /// Converts the time that a sound took to get to an object and back, in µs, to the distance to that object in µm.
+    pub fn round_trip_us_to_um(&self, round_trip_us: u64) -> u64 {
+        // µs * mm/s = nm; halve it for the one-way trip, and divide by 1000 to get µm.
+        round_trip_us * self.mm_per_sec as u64 / 2000
+    }
+}
+
+impl Default for SoundSpeed {
+    /// The speed of sound at room temperature (20°C).
+    fn default() -> Self {
+        Self::from_celsius(20)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that the value is within 0.5% of the reference.
+    fn assert_close(value: u64, reference: u64) {
+        let difference = if value > reference { value - reference } else { reference - value };
+        assert!(difference * 200 <= reference, "{} is not close to {}", value, reference);
+    }
+
+    #[test]
+    fn speed_at_reference_temperatures() {
+        // Reference values from `331.3 * sqrt(1 + T / 273.15)`.
+        assert_close(SoundSpeed::from_celsius(0).mm_per_sec() as u64, 331_300);
+        assert_close(SoundSpeed::from_celsius(20).mm_per_sec() as u64, 343_210);
+        assert_close(SoundSpeed::from_celsius(35).mm_per_sec() as u64, 351_880);
+    }
+
+    #[test]
+    fn distance_at_reference_temperatures() {
+        // An echo of 5831µs, which is 1m at 343m/s.
+        assert_eq!(SoundSpeed::from_celsius(0).round_trip_us_to_um(5831), 965_905);
+        assert_eq!(SoundSpeed::from_celsius(20).round_trip_us_to_um(5831), 1_001_241);
+        assert_eq!(SoundSpeed::from_celsius(35).round_trip_us_to_um(5831), 1_027_742);
+
+        // The same distances using the reference speeds.
+        assert_close(SoundSpeed::from_celsius(0).round_trip_us_to_um(5831), 965_905);
+        assert_close(SoundSpeed::from_celsius(20).round_trip_us_to_um(5831), 1_000_642);
+        assert_close(SoundSpeed::from_celsius(35).round_trip_us_to_um(5831), 1_025_923);
+    }
+
+    #[test]
+    fn default_is_room_temperature() {
+        assert_eq!(SoundSpeed::default(), SoundSpeed::from_celsius(20));
+    }
+}
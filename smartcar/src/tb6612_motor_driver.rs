@@ -0,0 +1,131 @@
+//! The TB6612 motor driver drives the two motors on the V4 kit's shield.
+//!
+//! It is controlled by 5 pins: one to set the direction of each motor, a PWM input for the speed of each motor,
+//! and a standby pin that switches both motors off. The shield derives each motor's second direction input
+//! from the first, so the driver's own short brake (both inputs high) cannot be reached.
+//!
+//! When a PWM input is low, the TB6612 shorts the motor terminals, so a motor at zero speed is braked;
+//! letting the motors coast takes the standby pin.
+
+use core::convert::Infallible;
+
+use crate::differential_drive::{self, Drive};
+use crate::hal::{infallible, OutputPin, PwmPin};
+use crate::l287n_motor_driver::{set_pwm_speed, StopMode};
+
+/// The driver for the motor driver.
+///
+/// `E` is the type of the PWM outputs, whose duty cycle goes up to 255 at most,
+/// and `D` is the type of the direction and standby pins.
+pub struct Tb6612Chassis<E, D> {
+    pin_pwm_left: E,
+    pin_pwm_right: E,
+    pin_direction_left: D,
+    pin_direction_right: D,
+    pin_standby: D,
+    /// The last signed wheel commands given to [Tb6612Chassis::drive], or zero after stopping.
+    command: (i16, i16),
+}
+
+impl<E, D> Tb6612Chassis<E, D>
+where
+    E: PwmPin<Duty = u8>,
+    D: OutputPin<Error = Infallible>,
+{
+    /// Creates a new motor driver from the PWM outputs, the direction pins and the standby pin.
+    ///
+    /// Both motors start out in standby.
+    pub fn new(pin_pwm_left: E, pin_pwm_right: E, pin_direction_left: D, pin_direction_right: D, pin_standby: D) -> Self {
+        let mut new_chassis = Self {
+            pin_pwm_left,
+            pin_pwm_right,
+            pin_direction_left,
+            pin_direction_right,
+            pin_standby,
+            command: (0, 0),
+        };
+
+        new_chassis.coast();
+        new_chassis
+    }
+
+    /// Drive each motor with a signed command, from -255 to 255.
+    ///
+    /// The sign selects the direction (positive is forward), and the magnitude selects the speed.
+    /// Commands outside of the range are clamped.
+    pub fn drive(&mut self, left: i16, right: i16){
+        let (left_forward, left_speed) = differential_drive::split_speed(left);
+        let (right_forward, right_speed) = differential_drive::split_speed(right);
+
+        set_direction(&mut self.pin_direction_left, left_forward);
+        set_direction(&mut self.pin_direction_right, right_forward);
+        set_pwm_speed(&mut self.pin_pwm_left, left_speed);
+        set_pwm_speed(&mut self.pin_pwm_right, right_speed);
+        infallible(self.pin_standby.set_high());
+        self.command = (differential_drive::clamp_speed(left), differential_drive::clamp_speed(right));
+    }
+
+    /// The last signed wheel commands given to [Tb6612Chassis::drive], or zero after stopping.
+    pub fn command(&self) -> (i16, i16) {
+        self.command
+    }
+
+    /// Drive the robot with a throttle and a turn rate, both from -255 to 255.
+    ///
+    /// A positive turn curves to the right. See [differential_drive::arcade_mix] for how these are mixed.
+    pub fn arcade(&mut self, throttle: i16, turn: i16){
+        let (left, right) = differential_drive::arcade_mix(throttle, turn);
+        self.drive(left, right);
+    }
+
+    /// Stop both motors in the given way.
+    pub fn stop(&mut self, mode: StopMode){
+        match mode {
+            StopMode::Brake => self.brake(),
+            StopMode::Coast => self.coast(),
+        }
+    }
+
+    /// Stop both motors by short-braking them, which holds the PWM inputs low while the driver is active.
+    pub fn brake(&mut self){
+        set_pwm_speed(&mut self.pin_pwm_left, 0);
+        set_pwm_speed(&mut self.pin_pwm_right, 0);
+        infallible(self.pin_standby.set_high());
+        self.command = (0, 0);
+    }
+
+    /// Stop both motors by putting the driver in standby, letting the wheels spin down freely.
+    pub fn coast(&mut self){
+        infallible(self.pin_standby.set_low());
+        set_pwm_speed(&mut self.pin_pwm_left, 0);
+        set_pwm_speed(&mut self.pin_pwm_right, 0);
+        self.command = (0, 0);
+    }
+}
+
+impl<E, D> Drive for Tb6612Chassis<E, D>
+where
+    E: PwmPin<Duty = u8>,
+    D: OutputPin<Error = Infallible>,
+{
+    fn drive(&mut self, left: i16, right: i16) {
+        Tb6612Chassis::drive(self, left, right);
+    }
+
+    fn brake(&mut self) {
+        Tb6612Chassis::brake(self);
+    }
+
+    fn coast(&mut self) {
+        Tb6612Chassis::coast(self);
+    }
+}
+
+/// Set a direction pin, which is high to go forward.
+fn set_direction<D: OutputPin<Error = Infallible>>(pin: &mut D, forward: bool) {
+    if forward {
+        infallible(pin.set_high());
+    } else {
+        infallible(pin.set_low());
+    }
+}
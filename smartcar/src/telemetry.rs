@@ -0,0 +1,204 @@
+//! Scheduling of the telemetry records that the robot streams to the host.
+//!
+//! The host turns telemetry on with a period and picks the channels it wants (see `smartcar_protocol::telemetry`).
+//! The [TelemetryScheduler] decides when the next record is due, and keeps track of how often and how slowly
+//! the main loop runs in between, which is the timing channel. The other channels are filled in by the caller.
+//!
+//! The scheduler is fed [crate::clock::millis] timestamps by the caller.
+
+use smartcar_protocol::telemetry::{Channels, TelemetryRecord};
+
+/// How the records are sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TelemetryFormat {
+    /// As lines of text, when telemetry was turned on from the text console.
+    Text,
+    /// As binary frames, when telemetry was turned on with a binary message.
+    Binary,
+}
+
+/// Decides when telemetry records are due.
+pub struct TelemetryScheduler {
+    /// The time between records, in ms, or 0 if telemetry is off.
+    period_ms: u16,
+    channels: Channels,
+    format: TelemetryFormat,
+    /// The time when the next record is due.
+    next_ms: u64,
+    /// The number of records sent so far, which numbers the binary records.
+    sequence: u8,
+    /// The number of passes of the main loop since the last record.
+    loop_count: u16,
+    /// The longest pass of the main loop since the last record, in ms.
+    max_loop_ms: u16,
+    /// The time when the current pass of the main loop started.
+    loop_started_ms: Option<u64>,
+}
+
+impl Default for TelemetryScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TelemetryScheduler {
+    /// Creates a scheduler with telemetry turned off, and all channels selected.
+    pub fn new() -> Self {
+        Self {
+            period_ms: 0,
+            channels: Channels::ALL,
+            format: TelemetryFormat::Text,
+            next_ms: 0,
+            sequence: 0,
+            loop_count: 0,
+            max_loop_ms: 0,
+            loop_started_ms: None,
+        }
+    }
+
+    /// Send records every `period_ms` in the given format, starting now, or stop if it is 0.
+    pub fn configure(&mut self, period_ms: u16, format: TelemetryFormat, now_ms: u64) {
+        self.period_ms = period_ms;
+        self.format = format;
+        self.next_ms = now_ms;
+        self.loop_count = 0;
+        self.max_loop_ms = 0;
+    }
+
+    pub fn period_ms(&self) -> u16 {
+        self.period_ms
+    }
+
+    pub fn format(&self) -> TelemetryFormat {
+        self.format
+    }
+
+    pub fn channels(&self) -> Channels {
+        self.channels
+    }
+
+    pub fn set_channels(&mut self, channels: Channels) {
+        self.channels = channels;
+    }
+
+    /// Turn some channels on or off.
+    pub fn set_channel(&mut self, channels: Channels, enabled: bool) {
+        self.channels = self.channels.with(channels, enabled);
+    }
+
+    /// The sequence number of the last record.
+    pub fn sequence(&self) -> u8 {
+        self.sequence
+    }
+
+    /// Call at the start of every pass of the main loop, to measure the loop timing.
+    pub fn loop_tick(&mut self, now_ms: u64) {
+        if let Some(started_ms) = self.loop_started_ms {
+            let loop_ms = now_ms.saturating_sub(started_ms).min(u16::MAX as u64) as u16;
+            self.max_loop_ms = self.max_loop_ms.max(loop_ms);
+            self.loop_count = self.loop_count.saturating_add(1);
+        }
+        self.loop_started_ms = Some(now_ms);
+    }
+
+    /// If a record is due, get it with the timing channel filled in; the caller fills in the other selected channels.
+    ///
+    /// If the loop was too slow to keep up, the missed records are skipped rather than sent in a burst.
+    pub fn poll(&mut self, now_ms: u64) -> Option<TelemetryRecord> {
+        if self.period_ms == 0 || now_ms < self.next_ms {
+            return None;
+        }
+
+        let period_ms = self.period_ms as u64;
+        self.next_ms += period_ms;
+        if self.next_ms <= now_ms {
+            self.next_ms = now_ms + period_ms;
+        }
+        self.sequence = self.sequence.wrapping_add(1);
+
+        let mut record = TelemetryRecord::new(now_ms as u32);
+        record.channels = self.channels;
+        if self.channels.contains(Channels::TIMING) {
+            record.loop_count = self.loop_count;
+            record.max_loop_ms = self.max_loop_ms;
+        }
+        self.loop_count = 0;
+        self.max_loop_ms = 0;
+        Some(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn off_until_configured() {
+        let mut scheduler = TelemetryScheduler::new();
+        assert_eq!(scheduler.poll(0), None);
+        assert_eq!(scheduler.poll(10_000), None);
+
+        scheduler.configure(100, TelemetryFormat::Text, 10_000);
+        assert!(scheduler.poll(10_000).is_some());
+        scheduler.configure(0, TelemetryFormat::Text, 10_050);
+        assert_eq!(scheduler.poll(20_000), None);
+    }
+
+    #[test]
+    fn records_come_at_the_configured_rate() {
+        let mut scheduler = TelemetryScheduler::new();
+        scheduler.configure(100, TelemetryFormat::Binary, 0);
+
+        let times: [u64; 7] = [0, 50, 99, 100, 150, 201, 299];
+        let due: [bool; 7] = [true, false, false, true, false, true, false];
+        for (&time, &due) in times.iter().zip(due.iter()) {
+            assert_eq!(scheduler.poll(time).is_some(), due, "at {}ms", time);
+        }
+        assert_eq!(scheduler.sequence(), 3);
+        assert_eq!(scheduler.format(), TelemetryFormat::Binary);
+    }
+
+    #[test]
+    fn slow_loop_skips_missed_records() {
+        let mut scheduler = TelemetryScheduler::new();
+        scheduler.configure(100, TelemetryFormat::Text, 0);
+        assert!(scheduler.poll(0).is_some());
+        assert!(scheduler.poll(450).is_some());
+        assert!(scheduler.poll(500).is_none());
+        assert!(scheduler.poll(550).is_some());
+    }
+
+    #[test]
+    fn measures_loop_timing() {
+        let mut scheduler = TelemetryScheduler::new();
+        scheduler.configure(100, TelemetryFormat::Text, 0);
+        for &time in &[0, 2, 4, 34, 35] {
+            scheduler.loop_tick(time);
+        }
+        let record = scheduler.poll(100).unwrap();
+        assert_eq!(record.at_ms, 100);
+        assert_eq!(record.loop_count, 4);
+        assert_eq!(record.max_loop_ms, 30);
+
+        // The counters start over for the next record.
+        scheduler.loop_tick(110);
+        let record = scheduler.poll(200).unwrap();
+        assert_eq!(record.loop_count, 1);
+        assert_eq!(record.max_loop_ms, 75);
+    }
+
+    #[test]
+    fn channels_are_selected() {
+        let mut scheduler = TelemetryScheduler::new();
+        scheduler.configure(100, TelemetryFormat::Text, 0);
+        scheduler.set_channel(Channels::ALL, false);
+        scheduler.set_channel(Channels::LINE, true);
+        scheduler.loop_tick(0);
+        scheduler.loop_tick(5);
+
+        let record = scheduler.poll(0).unwrap();
+        assert_eq!(record.channels, Channels::LINE);
+        // The timing channel is off, so it is not filled in.
+        assert_eq!(record.loop_count, 0);
+    }
+}
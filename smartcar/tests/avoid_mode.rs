@@ -0,0 +1,84 @@
+mod mock;
+
+use mock::{advance_millis, install_clock, MockEchoTimer, MockPin, MockPwm};
+use smartcar::avoid_mode::AvoidMode;
+use smartcar::differential_drive::Drive;
+use smartcar::hc_sr04_distance_sensor::HC_SR04;
+use smartcar::obstacle_avoidance::{AvoidConfig, AvoidState};
+use smartcar::range_scanner::{RangeScanner, ScanConfig};
+use smartcar::servo::{Servo, ServoCalibration};
+
+type Scanner = RangeScanner<MockPwm<u16>, MockPin, MockEchoTimer>;
+
+/// The wheels are not looked at here.
+struct Wheels;
+
+impl Drive for Wheels {
+    fn drive(&mut self, _left: i16, _right: i16) {}
+
+    fn brake(&mut self) {}
+
+    fn coast(&mut self) {}
+}
+
+/// Update the mode every 10ms until it sends a ping, and return how long that took.
+fn wait_for_ping(mode: &mut AvoidMode, scanner: &mut Scanner, timer: &MockEchoTimer) -> u64 {
+    let captures = timer.captures();
+    let mut waited = 0;
+    while timer.captures() == captures {
+        mode.update(&mut Wheels, scanner);
+        advance_millis(10);
+        waited += 10;
+        assert!(waited < 5000, "no ping was sent");
+    }
+    waited
+}
+
+/// Answer the ping in flight with an echo of the given length.
+fn echo(timer: &MockEchoTimer, ticks: u16) {
+    timer.rise();
+    timer.advance(ticks);
+    timer.fall();
+}
+
+#[test]
+fn cruise_pings_wait_for_the_mast_to_come_back() {
+    install_clock();
+    let timer = MockEchoTimer::new();
+    let servo = Servo::new(MockPwm::new(20_000), ServoCalibration::SG90);
+    let sensor = HC_SR04::new(MockPin::new(), timer.clone());
+    let config = ScanConfig { start_angle: 0, end_angle: 180, step: 90, sweep_speed: 0 };
+    let mut scanner = RangeScanner::new(servo, sensor, config).unwrap();
+    // A turn that is shorter than the mast's swing back to the front.
+    let mut mode = AvoidMode::new(AvoidConfig { turn_ms: 10, ..AvoidConfig::default() });
+
+    // The mast is only settled a while after pointing it to the front.
+    mode.restart(&mut scanner);
+    assert!(wait_for_ping(&mut mode, &mut scanner, &timer) > 10);
+
+    // An obstacle right in front: brake, then scan every point.
+    echo(&timer, 100);
+    while mode.behavior().state() != AvoidState::Scanning {
+        mode.update(&mut Wheels, &mut scanner);
+        advance_millis(10);
+    }
+    for _ in 0..3 {
+        wait_for_ping(&mut mode, &mut scanner, &timer);
+        echo(&timer, 2000);
+    }
+    while mode.behavior().state() == AvoidState::Scanning {
+        mode.update(&mut Wheels, &mut scanner);
+    }
+
+    // The scan ended at 180 degrees, and the mast has to swing back to 90 before the next ping.
+    while mode.behavior().state() != AvoidState::Cruising {
+        mode.update(&mut Wheels, &mut scanner);
+        advance_millis(10);
+    }
+    let captures = timer.captures();
+    mode.update(&mut Wheels, &mut scanner);
+    assert_eq!(timer.captures(), captures);
+    assert!(!scanner.servo().is_settled());
+    wait_for_ping(&mut mode, &mut scanner, &timer);
+    assert!(scanner.servo().is_settled());
+}
@@ -0,0 +1,103 @@
+mod mock;
+
+use mock::{MockEchoTimer, MockPin};
+use smartcar::hc_sr04_distance_sensor::{DistanceMeasurement, MeasurementError, HC_SR04};
+
+fn sensor() -> (HC_SR04<MockPin, MockEchoTimer>, MockPin, MockEchoTimer) {
+    let (trigger, timer) = (MockPin::new(), MockEchoTimer::new());
+    (HC_SR04::new(trigger.clone(), timer.clone()), trigger, timer)
+}
+
+#[test]
+fn measures_the_echo_width() {
+    let (mut sensor, trigger, timer) = sensor();
+    assert!(matches!(sensor.poll(), Err(nb::Error::Other(MeasurementError::NotStarted))));
+
+    sensor.start_measurement();
+    assert_eq!(trigger.rising_edges(), 1);
+    assert!(!trigger.is_set_high());
+    assert_eq!(timer.captures(), 1);
+
+    timer.advance(100);
+    timer.rise();
+    assert!(matches!(sensor.poll(), Err(nb::Error::WouldBlock)));
+
+    // 1000 ticks of 4µs there and back is about 686mm at 20°C. The poll above read the timer once.
+    timer.advance(1000 - 1);
+    timer.fall();
+    match sensor.poll() {
+        Ok(DistanceMeasurement::Measured(distance)) => {
+            assert_eq!(distance.ticks(), 1000);
+            assert_eq!(distance.to_mm(), 686);
+        },
+        other => panic!("expected a distance, got {:?}", other),
+    }
+    assert!(matches!(sensor.last_measurement(), Some(DistanceMeasurement::Measured(_))));
+
+    // The result is only returned once.
+    assert!(matches!(sensor.poll(), Err(nb::Error::Other(MeasurementError::NotStarted))));
+}
+
+#[test]
+fn no_echo_is_unknown() {
+    let (mut sensor, _, timer) = sensor();
+    sensor.start_measurement();
+    timer.advance(150);
+    assert!(matches!(sensor.poll(), Err(nb::Error::WouldBlock)));
+    timer.advance(100);
+    assert!(matches!(sensor.poll(), Ok(DistanceMeasurement::Unknown)));
+}
+
+#[test]
+fn endless_echo_is_infinity() {
+    let (mut sensor, _, timer) = sensor();
+    sensor.start_measurement();
+    timer.advance(120);
+    timer.rise();
+    timer.advance(20_000);
+    assert!(matches!(sensor.poll(), Err(nb::Error::WouldBlock)));
+    timer.advance(6_000);
+    assert!(matches!(sensor.poll(), Ok(DistanceMeasurement::Infinity)));
+}
+
+#[test]
+fn timer_wraps_around() {
+    let (mut sensor, _, timer) = sensor();
+    timer.advance(u16::MAX - 50);
+    sensor.start_measurement();
+    timer.advance(100);
+    timer.rise();
+    timer.advance(500);
+    timer.fall();
+    match sensor.poll() {
+        Ok(DistanceMeasurement::Measured(distance)) => assert_eq!(distance.ticks(), 500),
+        other => panic!("expected a distance, got {:?}", other),
+    }
+}
+
+/// Collects what `ufmt` writes, like the firmware's serial port.
+struct Text(String);
+
+impl ufmt::uWrite for Text {
+    type Error = core::convert::Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.0.push_str(s);
+        Ok(())
+    }
+}
+
+#[test]
+fn measurements_are_written_with_ufmt() {
+    let (mut sensor, _, timer) = sensor();
+    sensor.start_measurement();
+    timer.rise();
+    timer.advance(1000);
+    timer.fall();
+    let measurement = sensor.poll().unwrap();
+
+    let mut text = Text(String::new());
+    ufmt::uwrite!(&mut text, "{} {} {}", measurement, DistanceMeasurement::Infinity, DistanceMeasurement::Unknown).unwrap();
+    assert_eq!(text.0, "686mm ∞ Ø");
+    assert_eq!(measurement.to_string(), "686mm");
+}
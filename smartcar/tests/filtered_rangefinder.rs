@@ -0,0 +1,132 @@
+mod mock;
+
+use mock::{advance_millis, install_clock, MockEchoTimer, MockPin};
+use smartcar::filtered_rangefinder::{FilteredMeasurement, FilteredRangefinder};
+use smartcar::hc_sr04_distance_sensor::{DistanceMeasurement, MeasurementError, HC_SR04, MIN_CYCLE_MS};
+
+type Sensor = HC_SR04<MockPin, MockEchoTimer>;
+
+fn sensor() -> (Sensor, MockEchoTimer) {
+    install_clock();
+    let timer = MockEchoTimer::new();
+    (HC_SR04::new(MockPin::new(), timer.clone()), timer)
+}
+
+/// What a single ping comes back as.
+#[derive(Clone, Copy)]
+enum Echo {
+    /// An echo this many ticks long.
+    Ticks(u16),
+    /// No echo at all.
+    Infinity,
+    /// The sensor did not react.
+    Unknown,
+}
+
+/// Answer the ping that was just sent.
+fn answer(timer: &MockEchoTimer, echo: Echo) {
+    match echo {
+        Echo::Ticks(ticks) => {
+            timer.rise();
+            timer.advance(ticks);
+            timer.fall();
+        },
+        Echo::Infinity => {
+            timer.rise();
+            timer.advance(30_000);
+        },
+        Echo::Unknown => timer.advance(1_000),
+    }
+}
+
+/// Take a whole reading with the given pings.
+fn measure(filter: &mut FilteredRangefinder, sensor: &mut Sensor, timer: &MockEchoTimer, echoes: &[Echo]) -> FilteredMeasurement {
+    filter.start();
+    for (i, &echo) in echoes.iter().enumerate() {
+        // The first poll sends the ping.
+        assert!(matches!(filter.poll(sensor), Err(nb::Error::WouldBlock)));
+        answer(timer, echo);
+        if i + 1 < echoes.len() {
+            // The result is taken, but the next ping has to wait for the echoes to die down.
+            assert!(matches!(filter.poll(sensor), Err(nb::Error::WouldBlock)));
+            advance_millis(MIN_CYCLE_MS);
+        }
+    }
+    filter.poll(sensor).expect("the reading should be finished")
+}
+
+fn ticks(measurement: &FilteredMeasurement) -> u16 {
+    match measurement.distance {
+        DistanceMeasurement::Measured(distance) => distance.ticks(),
+        other => panic!("expected a distance, got {:?}", other),
+    }
+}
+
+#[test]
+fn median_rejects_outliers() {
+    let (mut sensor, timer) = sensor();
+    let mut filter = FilteredRangefinder::new(5, 50);
+
+    // Two short bounces do not move the median.
+    let echoes = [Echo::Ticks(1000), Echo::Ticks(120), Echo::Ticks(1010), Echo::Ticks(990), Echo::Ticks(150)];
+    let measurement = measure(&mut filter, &mut sensor, &timer, &echoes);
+    assert_eq!(ticks(&measurement), 990);
+    // The bounces are far from the rest, so the reading is not trusted.
+    assert!(!measurement.confident);
+
+    let echoes = [Echo::Ticks(1000), Echo::Unknown, Echo::Ticks(1010), Echo::Ticks(990), Echo::Infinity];
+    let measurement = measure(&mut filter, &mut sensor, &timer, &echoes);
+    assert_eq!(ticks(&measurement), 1000);
+    // 20 ticks there and back are 13.7mm, rounded down.
+    assert_eq!(measurement.spread_mm, 13);
+    assert!(measurement.confident);
+}
+
+#[test]
+fn even_count_averages_the_middle() {
+    let (mut sensor, timer) = sensor();
+    let mut filter = FilteredRangefinder::new(4, 50);
+    let echoes = [Echo::Ticks(1000), Echo::Ticks(1020), Echo::Ticks(1010), Echo::Ticks(990)];
+    assert_eq!(ticks(&measure(&mut filter, &mut sensor, &timer, &echoes)), 1005);
+}
+
+#[test]
+fn mostly_infinity_or_unknown() {
+    let (mut sensor, timer) = sensor();
+    let mut filter = FilteredRangefinder::new(3, 50);
+
+    let echoes = [Echo::Infinity, Echo::Ticks(500), Echo::Infinity];
+    let measurement = measure(&mut filter, &mut sensor, &timer, &echoes);
+    assert!(matches!(measurement.distance, DistanceMeasurement::Infinity));
+    assert!(measurement.confident);
+
+    let echoes = [Echo::Unknown, Echo::Unknown, Echo::Unknown];
+    let measurement = measure(&mut filter, &mut sensor, &timer, &echoes);
+    assert!(matches!(measurement.distance, DistanceMeasurement::Unknown));
+    assert!(!measurement.confident);
+}
+
+#[test]
+fn recovers_from_a_sensor_error() {
+    let (mut sensor, timer) = sensor();
+    let mut filter = FilteredRangefinder::new(2, 50);
+    assert!(matches!(filter.poll(&mut sensor), Err(nb::Error::Other(MeasurementError::NotStarted))));
+
+    filter.start();
+    assert!(matches!(filter.poll(&mut sensor), Err(nb::Error::WouldBlock)));
+    answer(&timer, Echo::Ticks(800));
+    // Something else takes the result of the ping, so the filter's poll of the sensor fails.
+    assert!(sensor.poll().is_ok());
+    assert!(matches!(filter.poll(&mut sensor), Err(nb::Error::Other(MeasurementError::NotStarted))));
+
+    // The lost ping is taken again, instead of waiting forever for its result.
+    advance_millis(MIN_CYCLE_MS);
+    assert!(matches!(filter.poll(&mut sensor), Err(nb::Error::WouldBlock)));
+    assert_eq!(timer.captures(), 2);
+    answer(&timer, Echo::Ticks(800));
+    assert!(matches!(filter.poll(&mut sensor), Err(nb::Error::WouldBlock)));
+    advance_millis(MIN_CYCLE_MS);
+    assert!(matches!(filter.poll(&mut sensor), Err(nb::Error::WouldBlock)));
+    answer(&timer, Echo::Ticks(820));
+    assert_eq!(ticks(&filter.poll(&mut sensor).unwrap()), 810);
+}
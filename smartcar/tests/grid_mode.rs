@@ -0,0 +1,123 @@
+mod mock;
+
+use mock::{advance_millis, install_clock, MockPin};
+use smartcar::differential_drive::Drive;
+use smartcar::grid_mode::GridMode;
+use smartcar::grid_route::{GridConfig, GridMove, Route};
+use smartcar::line_following::LineColor;
+use smartcar::line_tracker::LineTracker;
+
+/// What the chassis was last told to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wheels {
+    Driving(i16, i16),
+    Braking,
+    Coasting,
+}
+
+impl Drive for Wheels {
+    fn drive(&mut self, left: i16, right: i16) {
+        *self = Wheels::Driving(left, right);
+    }
+
+    fn brake(&mut self) {
+        *self = Wheels::Braking;
+    }
+
+    fn coast(&mut self) {
+        *self = Wheels::Coasting;
+    }
+}
+
+/// The pins of the line tracker, which are low over a dark line.
+struct Sensors(MockPin, MockPin, MockPin);
+
+impl Sensors {
+    fn see(&self, left: bool, center: bool, right: bool) {
+        self.0.set(!left);
+        self.1.set(!center);
+        self.2.set(!right);
+    }
+}
+
+#[test]
+fn drives_a_route_and_reports_its_steps() {
+    install_clock();
+    let sensors = Sensors(MockPin::new(), MockPin::new(), MockPin::new());
+    let mut tracker = LineTracker::new(sensors.0.clone(), sensors.1.clone(), sensors.2.clone());
+    let mut wheels = Wheels::Braking;
+    let mut mode = GridMode::new(GridConfig::default());
+
+    let mut route = Route::new();
+    route.push(GridMove::Forward(1));
+    route.push(GridMove::TurnLeft);
+    mode.start(route);
+
+    let mut reports = Vec::new();
+    let mut run = |ms: u64, wheels: &mut Wheels, tracker: &mut LineTracker<MockPin>| {
+        for _ in 0..ms / 10 {
+            if let Some(report) = mode.update(wheels, tracker) {
+                reports.push(report.to_string());
+            }
+            advance_millis(10);
+        }
+    };
+
+    sensors.see(false, true, false);
+    run(100, &mut wheels, &mut tracker);
+    assert_eq!(wheels, Wheels::Driving(140, 140));
+
+    // Over the crossing, then onto it.
+    sensors.see(true, true, true);
+    run(100, &mut wheels, &mut tracker);
+    sensors.see(false, true, false);
+    run(300, &mut wheels, &mut tracker);
+    assert_eq!(wheels, Wheels::Driving(-170, 170));
+
+    // The turn leaves the line, and finds the next one.
+    sensors.see(false, false, false);
+    run(300, &mut wheels, &mut tracker);
+    sensors.see(false, true, false);
+    run(20, &mut wheels, &mut tracker);
+
+    assert_eq!(reports, ["STEP 1 f1: OK", "STEP 2 l: OK"]);
+    assert_eq!(wheels, Wheels::Coasting);
+}
+
+#[test]
+fn follows_a_light_line_after_the_color_changes() {
+    install_clock();
+    let sensors = Sensors(MockPin::new(), MockPin::new(), MockPin::new());
+    let mut tracker = LineTracker::new(sensors.0.clone(), sensors.1.clone(), sensors.2.clone());
+    let mut wheels = Wheels::Braking;
+    let mut mode = GridMode::new(GridConfig::default());
+    mode.set_line_color(LineColor::Light);
+
+    let mut route = Route::new();
+    route.push(GridMove::Forward(1));
+    mode.start(route);
+
+    let mut reports = Vec::new();
+    let mut run = |ms: u64, wheels: &mut Wheels, tracker: &mut LineTracker<MockPin>| {
+        for _ in 0..ms / 10 {
+            if let Some(report) = mode.update(wheels, tracker) {
+                reports.push(report.to_string());
+            }
+            advance_millis(10);
+        }
+    };
+
+    // A light line on a dark floor: the sensors only see the floor as dark.
+    sensors.see(true, false, true);
+    run(100, &mut wheels, &mut tracker);
+    assert_eq!(wheels, Wheels::Driving(140, 140));
+
+    // A light crossing, then back onto the line.
+    sensors.see(false, false, false);
+    run(100, &mut wheels, &mut tracker);
+    sensors.see(true, false, true);
+    run(300, &mut wheels, &mut tracker);
+
+    assert_eq!(reports, ["STEP 1 f1: OK"]);
+    assert_eq!(wheels, Wheels::Coasting);
+}
@@ -0,0 +1,41 @@
+mod mock;
+
+use mock::{MockLineSensors, MockPin};
+use smartcar::line_tracker::{
+    AnalogLineTracker, LineBiasDirection, LineState, LineTracker, LineTrackerDirection, ITR20001_DARK_RANGE,
+};
+
+#[test]
+fn low_pins_are_dark() {
+    let (left, center, right) = (MockPin::new(), MockPin::new(), MockPin::new());
+    let mut tracker = LineTracker::new(left.clone(), center.clone(), right.clone());
+
+    // The sensors pull their pins low when they are over the line.
+    left.set(true);
+    center.set(false);
+    right.set(true);
+    let position = tracker.measure_full();
+    assert_eq!(position.dark_states(), (false, true, false));
+    assert_eq!(position.get_bias_direction_dark(), LineBiasDirection::Center);
+    assert_eq!(position.get_bias_direction_light(), LineBiasDirection::NotOnLine);
+
+    right.set(false);
+    assert_eq!(tracker.measure_direction(LineTrackerDirection::Right), LineState::Dark);
+    assert_eq!(tracker.measure_full().get_bias_direction_dark(), LineBiasDirection::SlightlyRight);
+    assert_eq!(tracker.measure_direction(LineTrackerDirection::Left), LineState::Light);
+}
+
+#[test]
+fn analog_readings_in_the_range_are_dark() {
+    let sensors = MockLineSensors::new();
+    let mut tracker = AnalogLineTracker::new(sensors.clone(), ITR20001_DARK_RANGE);
+
+    sensors.set(60, 700, 400);
+    assert_eq!(tracker.measure_full().dark_states(), (false, true, true));
+    assert_eq!(tracker.measure_full().get_bias_direction_dark(), LineBiasDirection::SlightlyRight);
+
+    // Lifted off the ground, nothing is reflected, which is not a line.
+    sensors.set(1000, 1000, 1000);
+    assert_eq!(tracker.measure_direction(LineTrackerDirection::Center), LineState::Light);
+    assert_eq!(tracker.measure_full().get_bias_direction_dark(), LineBiasDirection::NotOnLine);
+}
@@ -0,0 +1,229 @@
+//! Mock implementations of the hardware traits, for testing the drivers on the host.
+//!
+//! Every mock is a handle to shared state, so a test can keep a clone to look at the outputs
+//! and to drive the inputs, while the driver owns the other one.
+
+// Not every test file uses every mock.
+#![allow(dead_code)]
+
+use std::cell::Cell;
+use std::convert::Infallible;
+use std::rc::Rc;
+use std::sync::Once;
+
+use smartcar::clock;
+use smartcar::hal::{AnalogLineSensors, EchoTimer, InputPin, OutputPin, PwmPin};
+use smartcar::line_tracker::LineTrackerDirection;
+
+/// A digital pin, which can be used both as an output and as an input.
+#[derive(Clone, Default)]
+pub struct MockPin {
+    high: Rc<Cell<bool>>,
+    /// The number of times the pin went from low to high.
+    rising_edges: Rc<Cell<u32>>,
+}
+
+impl MockPin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_set_high(&self) -> bool {
+        self.high.get()
+    }
+
+    /// Drive the pin from outside, like a sensor would.
+    pub fn set(&self, high: bool) {
+        self.high.set(high);
+    }
+
+    pub fn rising_edges(&self) -> u32 {
+        self.rising_edges.get()
+    }
+}
+
+impl OutputPin for MockPin {
+    type Error = Infallible;
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        if !self.high.get() {
+            self.rising_edges.set(self.rising_edges.get() + 1);
+        }
+        self.high.set(true);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.high.set(false);
+        Ok(())
+    }
+}
+
+impl InputPin for MockPin {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Infallible> {
+        Ok(self.high.get())
+    }
+
+    fn is_low(&self) -> Result<bool, Infallible> {
+        Ok(!self.high.get())
+    }
+}
+
+/// A PWM output, with its duty cycle in any unit up to a fixed maximum.
+#[derive(Clone)]
+pub struct MockPwm<D> {
+    enabled: Rc<Cell<bool>>,
+    duty: Rc<Cell<D>>,
+    max_duty: D,
+}
+
+impl<D: Copy + Default> MockPwm<D> {
+    /// A disabled output with a duty cycle of zero.
+    pub fn new(max_duty: D) -> Self {
+        Self {
+            enabled: Rc::new(Cell::new(false)),
+            duty: Rc::new(Cell::new(D::default())),
+            max_duty,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    /// The duty cycle, or `None` while the output is disabled.
+    pub fn output(&self) -> Option<D> {
+        if self.enabled.get() {
+            Some(self.duty.get())
+        } else {
+            None
+        }
+    }
+}
+
+impl<D: Copy> PwmPin for MockPwm<D> {
+    type Duty = D;
+
+    fn disable(&mut self) {
+        self.enabled.set(false);
+    }
+
+    fn enable(&mut self) {
+        self.enabled.set(true);
+    }
+
+    fn get_duty(&self) -> D {
+        self.duty.get()
+    }
+
+    fn get_max_duty(&self) -> D {
+        self.max_duty
+    }
+
+    fn set_duty(&mut self, duty: D) {
+        self.duty.set(duty);
+    }
+}
+
+/// An echo timer that advances by one tick every time it is read, and whose edges are set by the test.
+#[derive(Clone, Default)]
+pub struct MockEchoTimer {
+    now: Rc<Cell<u16>>,
+    edges: Rc<Cell<(Option<u16>, Option<u16>)>>,
+    captures: Rc<Cell<u32>>,
+}
+
+impl MockEchoTimer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move the time forward without reading it.
+    pub fn advance(&self, ticks: u16) {
+        self.now.set(self.now.get().wrapping_add(ticks));
+    }
+
+    /// Record a rising edge now.
+    pub fn rise(&self) {
+        self.edges.set((Some(self.now.get()), None));
+    }
+
+    /// Record a falling edge now, after the rising edge.
+    pub fn fall(&self) {
+        let (rise, _) = self.edges.get();
+        self.edges.set((rise, Some(self.now.get())));
+    }
+
+    /// The number of times a capture was started.
+    pub fn captures(&self) -> u32 {
+        self.captures.get()
+    }
+}
+
+impl EchoTimer for MockEchoTimer {
+    fn now(&self) -> u16 {
+        let now = self.now.get();
+        self.now.set(now.wrapping_add(1));
+        now
+    }
+
+    fn start_capture(&mut self) {
+        self.edges.set((None, None));
+        self.captures.set(self.captures.get() + 1);
+    }
+
+    fn edges(&self) -> (Option<u16>, Option<u16>) {
+        self.edges.get()
+    }
+}
+
+/// Three analog line sensors, whose readings are set by the test.
+#[derive(Clone, Default)]
+pub struct MockLineSensors {
+    readings: Rc<Cell<(u16, u16, u16)>>,
+}
+
+impl MockLineSensors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the left, center and right readings.
+    pub fn set(&self, left: u16, center: u16, right: u16) {
+        self.readings.set((left, center, right));
+    }
+}
+
+impl AnalogLineSensors for MockLineSensors {
+    fn read(&mut self, direction: LineTrackerDirection) -> u16 {
+        let (left, center, right) = self.readings.get();
+        match direction {
+            LineTrackerDirection::Left => left,
+            LineTrackerDirection::Center => center,
+            LineTrackerDirection::Right => right,
+        }
+    }
+}
+
+thread_local! {
+    /// The time of the mock clock, which is separate for every test thread.
+    static NOW_MS: Cell<u64> = Cell::new(0);
+}
+
+fn thread_millis() -> u64 {
+    NOW_MS.with(Cell::get)
+}
+
+/// Make [clock::millis] read the mock clock of the calling thread, which starts at 0.
+pub fn install_clock() {
+    static INSTALL: Once = Once::new();
+    // SAFETY: the `Once` makes every thread wait until the source is installed, before any of them reads it.
+    INSTALL.call_once(|| unsafe { clock::set_source(thread_millis) });
+}
+
+/// Move the mock clock of the calling thread forward.
+pub fn advance_millis(ms: u64) {
+    NOW_MS.with(|now| now.set(now.get() + ms));
+}
@@ -0,0 +1,94 @@
+mod mock;
+
+use mock::{MockPin, MockPwm};
+use smartcar::l287n_motor_driver::MotorChassis;
+
+struct Pins {
+    enable_a: MockPwm<u8>,
+    enable_b: MockPwm<u8>,
+    a1: MockPin,
+    a2: MockPin,
+    b1: MockPin,
+    b2: MockPin,
+}
+
+fn chassis(max_duty: u8) -> (MotorChassis<MockPwm<u8>, MockPin>, Pins) {
+    let pins = Pins {
+        enable_a: MockPwm::new(max_duty),
+        enable_b: MockPwm::new(max_duty),
+        a1: MockPin::new(),
+        a2: MockPin::new(),
+        b1: MockPin::new(),
+        b2: MockPin::new(),
+    };
+    let chassis = MotorChassis::new(
+        pins.enable_a.clone(),
+        pins.enable_b.clone(),
+        pins.a1.clone(),
+        pins.a2.clone(),
+        pins.b1.clone(),
+        pins.b2.clone(),
+    );
+    (chassis, pins)
+}
+
+#[test]
+fn starts_stopped() {
+    let (chassis, pins) = chassis(255);
+    assert!(!pins.enable_a.is_enabled());
+    assert!(!pins.enable_b.is_enabled());
+    assert_eq!(chassis.command(), (0, 0));
+}
+
+#[test]
+fn drive_sets_directions_and_speeds() {
+    let (mut chassis, pins) = chassis(255);
+    chassis.drive(200, -100);
+
+    // The left pair goes forward on A1, and the right pair backward on B1.
+    assert!(pins.a1.is_set_high() && !pins.a2.is_set_high());
+    assert!(pins.b1.is_set_high() && !pins.b2.is_set_high());
+    assert_eq!(pins.enable_a.output(), Some(200));
+    assert_eq!(pins.enable_b.output(), Some(100));
+    assert_eq!(chassis.command(), (200, -100));
+
+    chassis.drive(-300, 0);
+    assert!(!pins.a1.is_set_high() && pins.a2.is_set_high());
+    assert_eq!(pins.enable_a.output(), Some(255));
+    // A zero speed disables the output instead of running it at a zero duty cycle.
+    assert_eq!(pins.enable_b.output(), None);
+    assert_eq!(chassis.command(), (-255, 0));
+}
+
+#[test]
+fn speed_is_scaled_to_the_duty_range() {
+    let (mut chassis, pins) = chassis(100);
+    chassis.set_speed(255, 51);
+    assert_eq!(pins.enable_a.output(), Some(100));
+    assert_eq!(pins.enable_b.output(), Some(20));
+}
+
+#[test]
+fn brake_shorts_the_motors() {
+    let (mut chassis, pins) = chassis(255);
+    chassis.drive(150, 150);
+    chassis.brake();
+
+    for pin in &[&pins.a1, &pins.a2, &pins.b1, &pins.b2] {
+        assert!(!pin.is_set_high());
+    }
+    assert_eq!(pins.enable_a.output(), Some(255));
+    assert_eq!(pins.enable_b.output(), Some(255));
+    assert_eq!(chassis.command(), (0, 0));
+}
+
+#[test]
+fn coast_disables_the_motors() {
+    let (mut chassis, pins) = chassis(255);
+    chassis.drive(150, -150);
+    chassis.coast();
+
+    assert_eq!(pins.enable_a.output(), None);
+    assert_eq!(pins.enable_b.output(), None);
+    assert_eq!(chassis.command(), (0, 0));
+}
@@ -0,0 +1,82 @@
+mod mock;
+
+use mock::{advance_millis, install_clock, MockEchoTimer, MockPin, MockPwm};
+use smartcar::hc_sr04_distance_sensor::{DistanceMeasurement, MeasurementError, HC_SR04};
+use smartcar::range_scanner::{RangeScanner, ScanConfig, ScanConfigError};
+use smartcar::servo::{Servo, ServoCalibration};
+
+type Scanner = RangeScanner<MockPwm<u16>, MockPin, MockEchoTimer>;
+
+fn scanner(config: ScanConfig) -> (Result<Scanner, ScanConfigError>, MockEchoTimer) {
+    install_clock();
+    let timer = MockEchoTimer::new();
+    let servo = Servo::new(MockPwm::new(20_000), ServoCalibration::SG90);
+    let sensor = HC_SR04::new(MockPin::new(), timer.clone());
+    (RangeScanner::new(servo, sensor, config), timer)
+}
+
+/// Two points, straight ahead and a little to the side.
+const SHORT_SCAN: ScanConfig = ScanConfig { start_angle: 90, end_angle: 100, step: 10, sweep_speed: 0 };
+
+/// Poll the scanner until its servo has settled and it sends a ping.
+fn wait_for_ping(scanner: &mut Scanner, timer: &MockEchoTimer) {
+    let captures = timer.captures();
+    while timer.captures() == captures {
+        assert!(matches!(scanner.poll(), Err(nb::Error::WouldBlock)));
+        advance_millis(10);
+    }
+}
+
+#[test]
+fn rejects_bad_configs() {
+    let (result, _) = scanner(ScanConfig { step: 0, ..ScanConfig::default() });
+    assert!(matches!(result, Err(ScanConfigError::ZeroStep)));
+    let (result, _) = scanner(ScanConfig { step: 5, ..ScanConfig::default() });
+    assert!(matches!(result, Err(ScanConfigError::TooManyPoints)));
+
+    let (result, _) = scanner(SHORT_SCAN);
+    let mut scanner = result.unwrap();
+    assert_eq!(scanner.set_config(ScanConfig { step: 1, ..ScanConfig::default() }), Err(ScanConfigError::TooManyPoints));
+    assert_eq!(scanner.config().step, 10);
+}
+
+#[test]
+fn scans_every_point() {
+    let (result, timer) = scanner(SHORT_SCAN);
+    let mut scanner = result.unwrap();
+    scanner.start();
+
+    for &ticks in &[1000, 2000] {
+        wait_for_ping(&mut scanner, &timer);
+        timer.rise();
+        timer.advance(ticks);
+        timer.fall();
+    }
+    let profile = scanner.poll().unwrap();
+    let angles: Vec<u8> = profile.points().iter().map(|(angle, _)| *angle).collect();
+    assert_eq!(angles, [90, 100]);
+    assert!(matches!(profile.measurement_at(100), Some(DistanceMeasurement::Measured(distance)) if distance.ticks() == 2000));
+}
+
+#[test]
+fn sensor_error_abandons_the_scan() {
+    let (result, timer) = scanner(SHORT_SCAN);
+    let mut scanner = result.unwrap();
+    scanner.start();
+    wait_for_ping(&mut scanner, &timer);
+
+    // Something else takes the result of the ping, so the scanner's poll of the sensor fails.
+    timer.advance(1000);
+    assert!(matches!(scanner.sensor().poll(), Ok(DistanceMeasurement::Unknown)));
+    assert!(matches!(scanner.poll(), Err(nb::Error::Other(MeasurementError::NotStarted))));
+    assert!(matches!(scanner.poll(), Err(nb::Error::Other(MeasurementError::NotStarted))));
+
+    // A new scan starts from the first point.
+    scanner.start();
+    for _ in 0..2 {
+        wait_for_ping(&mut scanner, &timer);
+        timer.advance(1000);
+    }
+    let profile = scanner.poll().unwrap();
+    assert_eq!(profile.points().len(), 2);
+}
@@ -0,0 +1,62 @@
+mod mock;
+
+use mock::{advance_millis, install_clock, MockPwm};
+use smartcar::servo::{Servo, ServoCalibration};
+
+fn servo(max_duty: u16) -> (Servo<MockPwm<u16>>, MockPwm<u16>) {
+    install_clock();
+    let output = MockPwm::new(max_duty);
+    (Servo::new(output.clone(), ServoCalibration::SG90), output)
+}
+
+#[test]
+fn starts_centered() {
+    let (servo, output) = servo(20_000);
+    assert_eq!(servo.current_angle(), 90);
+    // 544µs + (2400µs - 544µs) / 2 for the SG90.
+    assert_eq!(output.output(), Some(1472));
+}
+
+#[test]
+fn duty_is_scaled_to_the_frame() {
+    let (mut servo, output) = servo(40_000);
+    servo.set_angle(0);
+    assert_eq!(output.output(), Some(2 * 544));
+
+    servo.detach();
+    assert_eq!(output.output(), None);
+    servo.set_angle(180);
+    assert_eq!(output.output(), Some(2 * 2400));
+}
+
+#[test]
+fn angles_are_clamped_to_the_calibration() {
+    install_clock();
+    let calibration = ServoCalibration { min_angle: 30, max_angle: 150, ..ServoCalibration::SG90 };
+    let mut servo = Servo::new(MockPwm::new(20_000), calibration);
+    servo.set_angle(10);
+    assert_eq!(servo.current_angle(), 30);
+    servo.set_angle(170);
+    assert_eq!(servo.current_angle(), 150);
+}
+
+#[test]
+fn sweeps_with_the_clock() {
+    let (mut servo, _) = servo(20_000);
+    advance_millis(1000);
+    assert!(servo.is_settled());
+
+    servo.move_to(180, 90);
+    advance_millis(500);
+    servo.update();
+    assert_eq!(servo.current_angle(), 135);
+    assert!(!servo.is_settled());
+
+    advance_millis(500);
+    servo.update();
+    assert_eq!(servo.current_angle(), 180);
+    // The mast is given some time to stop wobbling.
+    assert!(!servo.is_settled());
+    advance_millis(100);
+    assert!(servo.is_settled());
+}
@@ -0,0 +1,75 @@
+mod mock;
+
+use mock::{MockPin, MockPwm};
+use smartcar::tb6612_motor_driver::Tb6612Chassis;
+
+struct Pins {
+    pwm_left: MockPwm<u8>,
+    pwm_right: MockPwm<u8>,
+    direction_left: MockPin,
+    direction_right: MockPin,
+    standby: MockPin,
+}
+
+fn chassis() -> (Tb6612Chassis<MockPwm<u8>, MockPin>, Pins) {
+    let pins = Pins {
+        pwm_left: MockPwm::new(255),
+        pwm_right: MockPwm::new(255),
+        direction_left: MockPin::new(),
+        direction_right: MockPin::new(),
+        standby: MockPin::new(),
+    };
+    let chassis = Tb6612Chassis::new(
+        pins.pwm_left.clone(),
+        pins.pwm_right.clone(),
+        pins.direction_left.clone(),
+        pins.direction_right.clone(),
+        pins.standby.clone(),
+    );
+    (chassis, pins)
+}
+
+#[test]
+fn starts_in_standby() {
+    let (chassis, pins) = chassis();
+    assert!(!pins.standby.is_set_high());
+    assert!(!pins.pwm_left.is_enabled());
+    assert!(!pins.pwm_right.is_enabled());
+    assert_eq!(chassis.command(), (0, 0));
+}
+
+#[test]
+fn drive_sets_directions_and_speeds() {
+    let (mut chassis, pins) = chassis();
+    chassis.drive(200, -300);
+
+    assert!(pins.standby.is_set_high());
+    assert!(pins.direction_left.is_set_high());
+    assert!(!pins.direction_right.is_set_high());
+    assert_eq!(pins.pwm_left.output(), Some(200));
+    assert_eq!(pins.pwm_right.output(), Some(255));
+    assert_eq!(chassis.command(), (200, -255));
+}
+
+#[test]
+fn brake_holds_the_pwm_low_while_active() {
+    let (mut chassis, pins) = chassis();
+    chassis.drive(150, 150);
+    chassis.brake();
+
+    assert!(pins.standby.is_set_high());
+    assert_eq!(pins.pwm_left.output(), None);
+    assert_eq!(pins.pwm_right.output(), None);
+    assert_eq!(chassis.command(), (0, 0));
+}
+
+#[test]
+fn coast_goes_to_standby() {
+    let (mut chassis, pins) = chassis();
+    chassis.drive(150, -150);
+    chassis.coast();
+
+    assert!(!pins.standby.is_set_high());
+    assert_eq!(pins.pwm_left.output(), None);
+    assert_eq!(chassis.command(), (0, 0));
+}